@@ -0,0 +1,215 @@
+use std::path::Path;
+
+use crate::{
+    lexer::{
+        token::{LexerError, TokenKind},
+        Position, Span,
+    },
+    parser::{one_of, ParseError},
+    runtime::RuntimeError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message about the source code, shown to the player together with the line it's about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>, // shown next to the caret
+    pub expected: Vec<String>, // what the parser would have accepted
+    pub found: Option<String>, // what it got instead
+    pub notes: Vec<String>,    // `help:` lines
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            span: None,
+            label: None,
+            expected: vec![],
+            found: None,
+            notes: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    fn with_found(mut self, found: impl Into<String>) -> Self {
+        self.found = Some(found.into());
+        self
+    }
+
+    pub fn from_lexer_error(err: &LexerError, span: Span) -> Self {
+        let diagnostic = match err {
+            LexerError::UnexpectedToken(value) => {
+                Diagnostic::error(format!("unexpected `{}`", value))
+                    .with_label("not part of the language")
+                    .with_found(value.clone())
+            }
+            LexerError::ParseNumberError(value) => {
+                Diagnostic::error(format!("invalid number `{}`", value))
+                    .with_label("too big or badly written")
+                    .with_found(value.clone())
+            }
+            LexerError::UnexpectedEOF => Diagnostic::error("unexpected end of file")
+                .with_label("this is never finished")
+                .with_note("check for a missing closing `\"`"),
+        };
+        diagnostic.with_span(span)
+    }
+
+    pub fn from_parse_error(err: &ParseError, source: &str) -> Self {
+        let span = err.span().unwrap_or_else(|| end_of(source));
+        let diagnostic = match err {
+            ParseError::UnexpectedToken(kind, expected, span) => {
+                let found = describe(*kind, *span, source);
+                let diagnostic =
+                    Diagnostic::error(format!("unexpected {}", found)).with_found(found);
+                match expected.is_empty() {
+                    true => diagnostic.with_label(format!("unexpected {}", kind)),
+                    false => Diagnostic {
+                        expected: expected.iter().map(|kind| kind.to_string()).collect(),
+                        ..diagnostic.with_label(format!("expected {}", one_of(expected)))
+                    },
+                }
+            }
+            ParseError::ExpectedExpression(kind, span) => {
+                let found = describe(*kind, *span, source);
+                Diagnostic {
+                    expected: vec!["expression".to_string()],
+                    ..Diagnostic::error(format!("expected an expression, found {}", found))
+                        .with_label("expected an expression")
+                        .with_found(found)
+                }
+            }
+            ParseError::ConstantNotInitialized(name, _) => {
+                Diagnostic::error(format!("constant `{}` has no value", name))
+                    .with_label("needs a value")
+                    .with_note(format!(
+                        "give it a value like `const {} = 0`, or use `let` if it changes later",
+                        name
+                    ))
+            }
+            ParseError::OutsideOfLoop(kind, _) => {
+                Diagnostic::error(format!("{} outside of a loop", kind))
+                    .with_label("not inside a loop")
+                    .with_note(format!(
+                        "{} can only be used inside `for` and `while` loops",
+                        kind
+                    ))
+            }
+            ParseError::MissingDefault(name, _) => {
+                Diagnostic::error(format!("parameter `{}` needs a default value", name))
+                    .with_label("follows a parameter with a default value")
+                    .with_note("give it a default or move it before the parameters that have one")
+            }
+            ParseError::DuplicateParameter(name, _) => {
+                Diagnostic::error(format!("parameter `{}` is declared more than once", name))
+                    .with_label("declared again here")
+                    .with_note("every parameter needs its own name")
+            }
+            ParseError::PositionalAfterNamed(_) => {
+                Diagnostic::error("positional argument after a named argument")
+                    .with_label("positional argument")
+                    .with_note(
+                        "pass positional arguments first and named ones like `speed=30` last",
+                    )
+            }
+            ParseError::InvalidAssignmentTarget(_) => {
+                Diagnostic::error("invalid assignment target")
+                    .with_label("cannot be assigned to")
+                    .with_note("only variables, properties and list items can be assigned to")
+            }
+            ParseError::UnexpectedEOF => Diagnostic::error("unexpected end of file")
+                .with_label("the file ends here")
+                .with_found(TokenKind::EOF.to_string()),
+        };
+        diagnostic.with_span(span)
+    }
+
+    /// Points at the code that raised `err` if it comes from `file`, the one being rendered,
+    /// errors raised in other files (imported modules) only mention where they happened.
+    pub fn from_runtime_error(err: &RuntimeError, file: Option<&Path>) -> Self {
+        let diagnostic = Diagnostic::error(err.to_string());
+        match err.location() {
+            Some(location) if location.file.as_deref() == file => {
+                diagnostic.with_span(location.span)
+            }
+            Some(location) => {
+                let file = match &location.file {
+                    Some(file) => file.display().to_string(),
+                    None => "<script>".to_string(),
+                };
+                diagnostic.with_note(format!(
+                    "raised in {}:{}:{}",
+                    file,
+                    location.span.start.line,
+                    location.span.start.column + 1
+                ))
+            }
+            None => diagnostic,
+        }
+    }
+}
+
+/// The source text of a token in backticks, or the name of its kind when it has no text.
+fn describe(kind: TokenKind, span: Span, source: &str) -> String {
+    let text = source
+        .get(span.start.offset..span.end.offset)
+        .unwrap_or("")
+        .trim();
+    match kind {
+        TokenKind::EOF | TokenKind::Newline => kind.to_string(),
+        _ if text.is_empty() => kind.to_string(),
+        _ => format!("`{}`", text),
+    }
+}
+
+/// An empty span after the last character of `source`.
+fn end_of(source: &str) -> Span {
+    let last_line = source.rsplit('\n').next().unwrap_or("");
+    let end = Position {
+        offset: source.len(),
+        line: source.matches('\n').count() + 1,
+        column: last_line.chars().count(),
+    };
+    Span::new(end, end)
+}
@@ -0,0 +1,5 @@
+mod diagnostic;
+mod render;
+
+pub use diagnostic::{Diagnostic, Severity};
+pub use render::{json, render};
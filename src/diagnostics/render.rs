@@ -0,0 +1,254 @@
+use std::fmt::Write;
+
+use crate::lexer::{Position, Span};
+
+use super::Diagnostic;
+
+/// Renders diagnostics the way compilers do, with the source line and a caret under the problem:
+///
+/// ```text
+/// error: unexpected `1`
+///  --> robot.pl:1:6
+///   |
+/// 1 | fn f(1 + 2)
+///   |      ^ expected one of `)`, `*` or identifier
+/// ```
+pub fn render(diagnostics: &[Diagnostic], source: &str, file: &str) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| render_one(diagnostic, source, file))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_one(diagnostic: &Diagnostic, source: &str, file: &str) -> String {
+    let mut out = format!("{}: {}\n", diagnostic.severity, diagnostic.message);
+    let Some(span) = diagnostic.span else {
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "  = help: {}", note);
+        }
+        return out;
+    };
+
+    let line_number = span.start.line.to_string();
+    let pad = " ".repeat(line_number.len());
+    let line = source
+        .lines()
+        .nth(span.start.line.saturating_sub(1))
+        .unwrap_or("");
+    // columns are counted in characters, the caret goes under the same ones
+    let before = span.start.column.min(line.chars().count());
+    let width = match span.end.line == span.start.line {
+        true => span.end.column.saturating_sub(span.start.column),
+        false => line.chars().count().saturating_sub(before),
+    };
+
+    let _ = writeln!(
+        out,
+        "{}--> {}:{}:{}",
+        pad,
+        file,
+        span.start.line,
+        span.start.column + 1
+    );
+    let _ = writeln!(out, "{} |", pad);
+    let _ = writeln!(out, "{} | {}", line_number, line);
+    let _ = write!(
+        out,
+        "{} | {}{}",
+        pad,
+        " ".repeat(before),
+        "^".repeat(width.max(1))
+    );
+    if let Some(label) = &diagnostic.label {
+        let _ = write!(out, " {}", label);
+    }
+    out.push('\n');
+    for note in &diagnostic.notes {
+        let _ = writeln!(out, "{} = help: {}", pad, note);
+    }
+    out
+}
+
+/// Diagnostics as a JSON array, for editors and other tools. Lines start at 1 and columns at 0,
+/// like everywhere else in the crate.
+pub fn json(diagnostics: &[Diagnostic], file: &str) -> String {
+    let items: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let span = match diagnostic.span {
+                Some(span) => json_span(span),
+                None => "null".to_string(),
+            };
+            format!(
+                "{{\"severity\":{},\"message\":{},\"file\":{},\"span\":{},\"label\":{},\"expected\":{},\"found\":{},\"notes\":{}}}",
+                json_string(&diagnostic.severity.to_string()),
+                json_string(&diagnostic.message),
+                json_string(file),
+                span,
+                json_option(diagnostic.label.as_deref()),
+                json_list(&diagnostic.expected),
+                json_option(diagnostic.found.as_deref()),
+                json_list(&diagnostic.notes),
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+fn json_span(span: Span) -> String {
+    let position = |position: Position| {
+        format!(
+            "{{\"offset\":{},\"line\":{},\"column\":{}}}",
+            position.offset, position.line, position.column
+        )
+    };
+    format!(
+        "{{\"start\":{},\"end\":{}}}",
+        position(span.start),
+        position(span.end)
+    )
+}
+
+fn json_option(value: Option<&str>) -> String {
+    value.map_or("null".to_string(), json_string)
+}
+
+fn json_list(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::Path, rc::Rc};
+
+    use crate::{
+        lexer::Lexer,
+        parser::{ParseError, Parser},
+        runtime::RuntimeError,
+    };
+
+    fn parse_error(source: &str) -> Diagnostic {
+        let mut lexer = Lexer::new(source.to_string());
+        lexer.tokenize().unwrap();
+        match Parser::new(lexer.tokens).produce_ast() {
+            Err(err) => Diagnostic::from_parse_error(&err, source),
+            Ok(_) => panic!("{} should not parse", source),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "let x = 1\nfn f(1 + 2) {}";
+        let diagnostic = parse_error(source);
+        assert_eq!(diagnostic.expected, vec!["`)`", "`*`", "identifier"],);
+        assert_eq!(diagnostic.found.as_deref(), Some("`1`"));
+        assert_eq!(
+            render(&[diagnostic], source, "robot.pl"),
+            "error: unexpected `1`\n \
+             --> robot.pl:2:6\n  \
+             |\n\
+             2 | fn f(1 + 2) {}\n  \
+             |      ^ expected one of `)`, `*` or identifier\n"
+        );
+
+        let source = "while true {}\nbreak";
+        assert_eq!(
+            render(&[parse_error(source)], source, "robot.pl"),
+            "error: `break` outside of a loop\n \
+             --> robot.pl:2:1\n  \
+             |\n\
+             2 | break\n  \
+             | ^^^^^ not inside a loop\n  \
+             = help: `break` can only be used inside `for` and `while` loops\n"
+        );
+
+        let source = "move(\n";
+        let diagnostic = Diagnostic::from_parse_error(&ParseError::UnexpectedEOF, source);
+        let position = Position {
+            offset: 6,
+            line: 2,
+            column: 0,
+        };
+        assert_eq!(diagnostic.span, Some(Span::new(position, position)));
+
+        let diagnostic = Diagnostic::error("stack overflow").with_note("don't recurse forever");
+        assert_eq!(
+            render(&[diagnostic], "", "robot.pl"),
+            "error: stack overflow\n  = help: don't recurse forever\n"
+        );
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let source = "let speed = 1\nspeed = speed / 0";
+        let start = Position {
+            offset: 22,
+            line: 2,
+            column: 8,
+        };
+        let end = Position {
+            offset: 31,
+            line: 2,
+            column: 17,
+        };
+        let file: Rc<Path> = Rc::from(Path::new("robot.pl"));
+        let err = RuntimeError::DivisionByZero.at(Span::new(start, end), Some(file.clone()));
+        assert_eq!(
+            render(
+                &[Diagnostic::from_runtime_error(&err, Some(&file))],
+                source,
+                "robot.pl"
+            ),
+            "error: Division by zero\n \
+             --> robot.pl:2:9\n  \
+             |\n\
+             2 | speed = speed / 0\n  \
+             |         ^^^^^^^^^\n"
+        );
+
+        // the source of another file is not at hand, so it's only mentioned
+        let diagnostic = Diagnostic::from_runtime_error(&err, Some(Path::new("main.pl")));
+        assert_eq!(diagnostic.span, None);
+        assert_eq!(diagnostic.notes, vec!["raised in robot.pl:2:9"]);
+    }
+
+    #[test]
+    fn test_json() {
+        let source = "const speed";
+        let diagnostic = parse_error(source);
+        assert_eq!(
+            json(&[diagnostic], "robots/\"a\".pl"),
+            concat!(
+                r#"[{"severity":"error","message":"constant `speed` has no value","#,
+                r#""file":"robots/\"a\".pl","#,
+                r#""span":{"start":{"offset":6,"line":1,"column":6},"end":{"offset":11,"line":1,"column":11}},"#,
+                r#""label":"needs a value","expected":[],"found":null,"#,
+                r#""notes":["give it a value like `const speed = 0`, or use `let` if it changes later"]}]"#
+            )
+        );
+        assert_eq!(json_string("a\\b\n\t\u{1}"), r#""a\\b\n\t\u0001""#);
+        assert_eq!(json(&[], "robot.pl"), "[]");
+    }
+}
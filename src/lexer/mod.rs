@@ -4,13 +4,18 @@ use self::token::{
     Character, Decimal, Identifier, Integer, LexerError, StringLiteral, Token, TokenKind,
 };
 
+mod span;
 pub mod token;
 
+pub use span::{Position, Span};
+
 pub struct Lexer {
     pub tokens: Vec<Box<dyn Token>>,
     position: usize,
     line: usize,
     column: usize,
+    offset: usize,   // byte offset of `position`
+    start: Position, // where the token being read starts
     source: String,
 }
 
@@ -20,11 +25,14 @@ impl Lexer {
             position: 0,
             line: 1,
             column: 0,
+            offset: 0,
+            start: Position::default(),
             tokens: vec![],
             source,
         }
     }
 
+    #[allow(clippy::clone_on_copy, clippy::needless_return)]
     fn next_char(&mut self) -> Result<Option<char>, LexerError> {
         if self.source.chars().nth(self.position).is_none() {
             bail!(LexerError::UnexpectedEOF)
@@ -32,6 +40,7 @@ impl Lexer {
         let last_position = self.position.clone();
         self.position += 1;
         if let Some(ch) = self.source.chars().nth(last_position) {
+            self.offset += ch.len_utf8();
             if ch != '\n' {
                 self.column += 1;
             } else {
@@ -73,6 +82,7 @@ impl Lexer {
         Ok(chars)
     }
 
+    #[allow(clippy::is_digit_ascii_radix)]
     fn tokenize_ident(&mut self) -> Result<Identifier, LexerError> {
         // identifiers can't start with a number
         match self.peek() {
@@ -91,6 +101,7 @@ impl Lexer {
         Ok(tok)
     }
 
+    #[allow(clippy::is_digit_ascii_radix, clippy::needless_return)]
     fn tokenize_number(&mut self) -> Result<Box<dyn Token>, LexerError> {
         // number should start with a digit
         match self.peek() {
@@ -169,10 +180,13 @@ impl Lexer {
         Ok(Box::new(token))
     }
 
+    // `add_position` is the length of a token that wasn't read char by char yet,
+    // the token's span goes from `start` to wherever the lexer ends up
     fn append_token(&mut self, mut token: Box<dyn Token>, add_position: Option<usize>) {
         let token_kind = token.kind();
         if let Some(add_position) = add_position {
             self.position += add_position;
+            self.offset += add_position;
             token.set_line(self.line);
             token.set_column(self.column);
             match token_kind {
@@ -181,19 +195,35 @@ impl Lexer {
                     self.column = 0;
                 }
                 _ => {
-                    self.column += 1;
+                    self.column += add_position;
                 }
             }
         }
+        token.set_span(Span::new(self.start, self.here()));
         self.tokens.push(token);
     }
 
+    fn here(&self) -> Position {
+        Position {
+            offset: self.offset,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// The part of the source that was being read when the lexer stopped.
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.here())
+    }
+
     fn is_end(&self) -> bool {
-        self.position >= self.source.len()
+        self.offset >= self.source.len()
     }
 
+    #[allow(clippy::is_digit_ascii_radix)]
     pub fn tokenize(&mut self) -> Result<(), LexerError> {
         while !self.is_end() {
+            self.start = self.here();
             let ch = self.peek();
 
             if let Some(ch) = ch {
@@ -257,6 +287,8 @@ impl Lexer {
                                 Box::new(Character::from(TokenKind::IsEquals)),
                                 Some(2),
                             )
+                        } else if self.peek_ahead().is_some_and(|next_char| next_char == '>') {
+                            self.append_token(Box::new(Character::from(TokenKind::Arrow)), Some(2))
                         } else {
                             self.append_token(Box::new(Character::from(TokenKind::Equals)), Some(1))
                         }
@@ -287,6 +319,9 @@ impl Lexer {
                         Some(1),
                     ),
                     ':' => self.append_token(Box::new(Character::from(TokenKind::Colon)), Some(1)),
+                    ';' => {
+                        self.append_token(Box::new(Character::from(TokenKind::Semicolon)), Some(1))
+                    }
                     '.' => self.append_token(Box::new(Character::from(TokenKind::Point)), Some(1)),
                     ',' => self.append_token(Box::new(Character::from(TokenKind::Comma)), Some(1)),
                     '%' => {
@@ -310,13 +345,35 @@ impl Lexer {
                         }
                     }
                     '<' => {
-                        self.append_token(Box::new(Character::from(TokenKind::LessThan)), Some(1))
+                        if self.peek_ahead().is_some_and(|nc| nc == '=') {
+                            self.append_token(
+                                Box::new(Character::from(TokenKind::LessThanOrEqual)),
+                                Some(2),
+                            )
+                        } else {
+                            self.append_token(
+                                Box::new(Character::from(TokenKind::LessThan)),
+                                Some(1),
+                            )
+                        }
+                    }
+                    '>' => {
+                        if self.peek_ahead().is_some_and(|nc| nc == '=') {
+                            self.append_token(
+                                Box::new(Character::from(TokenKind::GreaterThanOrEqual)),
+                                Some(2),
+                            )
+                        } else {
+                            self.append_token(
+                                Box::new(Character::from(TokenKind::GreaterThan)),
+                                Some(1),
+                            )
+                        }
                     }
-                    '>' => self
-                        .append_token(Box::new(Character::from(TokenKind::GreaterThan)), Some(1)),
                     ' ' | '\r' => {
                         // ignore whitespaces
                         self.position += 1;
+                        self.offset += 1;
                         if ch.to_string().chars().count() > 0 {
                             self.column += 1;
                         }
@@ -343,6 +400,13 @@ impl Lexer {
                                     self.append_token(Box::new(token), None);
                                     continue;
                                 }
+                                "def" => {
+                                    let mut token = Character::from(TokenKind::Def);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
                                 "true" => {
                                     let mut token = Character::from(TokenKind::True);
                                     token.set_line(identifier.line());
@@ -392,6 +456,20 @@ impl Lexer {
                                     self.append_token(Box::new(token), None);
                                     continue;
                                 }
+                                "not" => {
+                                    let mut token = Character::from(TokenKind::NotKeyword);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
+                                "is" => {
+                                    let mut token = Character::from(TokenKind::Is);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
                                 "for" => {
                                     let mut token = Character::from(TokenKind::For);
                                     token.set_line(identifier.line());
@@ -399,6 +477,27 @@ impl Lexer {
                                     self.append_token(Box::new(token), None);
                                     continue;
                                 }
+                                "while" => {
+                                    let mut token = Character::from(TokenKind::While);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
+                                "break" => {
+                                    let mut token = Character::from(TokenKind::Break);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
+                                "continue" => {
+                                    let mut token = Character::from(TokenKind::Continue);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
                                 "in" => {
                                     let mut token = Character::from(TokenKind::In);
                                     token.set_line(identifier.line());
@@ -448,6 +547,13 @@ impl Lexer {
                                     self.append_token(Box::new(token), None);
                                     continue;
                                 }
+                                "as" => {
+                                    let mut token = Character::from(TokenKind::As);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
                                 "static" => {
                                     let mut token = Character::from(TokenKind::Static);
                                     token.set_line(identifier.line());
@@ -455,6 +561,13 @@ impl Lexer {
                                     self.append_token(Box::new(token), None);
                                     continue;
                                 }
+                                "new" => {
+                                    let mut token = Character::from(TokenKind::New);
+                                    token.set_line(identifier.line());
+                                    token.set_column(identifier.column());
+                                    self.append_token(Box::new(token), None);
+                                    continue;
+                                }
                                 _ => {}
                             };
 
@@ -470,6 +583,7 @@ impl Lexer {
             }
         }
 
+        self.start = self.here();
         let mut eof_token = Character::from(TokenKind::EOF);
 
         eof_token.set_line(self.line);
@@ -485,6 +599,7 @@ impl Lexer {
 mod tests {
     use super::*;
 
+    #[allow(clippy::needless_return)]
     fn tokenize_string(source: String) -> Vec<Box<dyn Token>> {
         let mut lexer = Lexer::new(source.clone());
         if lexer.tokenize().is_err() {
@@ -495,6 +610,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_plus() {
         let tokens = tokenize_string("+".to_string());
         assert_eq!(tokens.len(), 2);
@@ -507,6 +623,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_minus() {
         let tokens = tokenize_string("-".to_string());
         assert_eq!(tokens.len(), 2);
@@ -519,6 +636,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_increment() {
         let tokens = tokenize_string("++".to_string());
         assert_eq!(tokens.len(), 2);
@@ -531,6 +649,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_decrement() {
         let tokens = tokenize_string("--".to_string());
         assert_eq!(tokens.len(), 2);
@@ -543,6 +662,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_open_paren() {
         let tokens = tokenize_string("(".to_string());
         assert_eq!(tokens.len(), 2);
@@ -555,6 +675,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_close_paren() {
         let tokens = tokenize_string(")".to_string());
         assert_eq!(tokens.len(), 2);
@@ -567,6 +688,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_open_bracket() {
         let tokens = tokenize_string("[".to_string());
         assert_eq!(tokens.len(), 2);
@@ -579,6 +701,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_close_bracket() {
         let tokens = tokenize_string("]".to_string());
         assert_eq!(tokens.len(), 2);
@@ -591,6 +714,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_open_brace() {
         let tokens = tokenize_string("{".to_string());
         assert_eq!(tokens.len(), 2);
@@ -603,6 +727,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_close_brace() {
         let tokens = tokenize_string("}".to_string());
         assert_eq!(tokens.len(), 2);
@@ -615,6 +740,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_colon() {
         let tokens = tokenize_string(":".to_string());
         assert_eq!(tokens.len(), 2);
@@ -627,6 +753,19 @@ mod tests {
     }
 
     #[test]
+    fn test_semicolon() {
+        let tokens = tokenize_string(";".to_string());
+        assert_eq!(tokens.len(), 2);
+        assert!(tokens
+            .first()
+            .is_some_and(|token| token.kind() == TokenKind::Semicolon));
+        assert!(tokens
+            .get(1)
+            .is_some_and(|token| token.kind() == TokenKind::EOF));
+    }
+
+    #[test]
+    #[allow(clippy::get_first)]
     fn test_comma() {
         let tokens = tokenize_string(",".to_string());
         assert_eq!(tokens.len(), 2);
@@ -639,6 +778,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_point() {
         let tokens = tokenize_string(".".to_string());
         assert_eq!(tokens.len(), 2);
@@ -651,6 +791,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_multiply() {
         let tokens = tokenize_string("*".to_string());
         assert_eq!(tokens.len(), 2);
@@ -663,6 +804,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_divide() {
         let tokens = tokenize_string("/".to_string());
         assert_eq!(tokens.len(), 2);
@@ -675,6 +817,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_modulo() {
         let tokens = tokenize_string("%".to_string());
         assert_eq!(tokens.len(), 2);
@@ -687,6 +830,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_not() {
         let tokens = tokenize_string("!".to_string());
         assert_eq!(tokens.len(), 2);
@@ -699,6 +843,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_greater_than() {
         let tokens = tokenize_string(">".to_string());
         assert_eq!(tokens.len(), 2);
@@ -711,6 +856,48 @@ mod tests {
     }
 
     #[test]
+    fn test_arrow() {
+        let tokens = tokenize_string("fn(x) => x == 1".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::Fn,
+            TokenKind::OpenParen,
+            TokenKind::Identifier,
+            TokenKind::CloseParen,
+            TokenKind::Arrow,
+            TokenKind::Identifier,
+            TokenKind::IsEquals,
+            TokenKind::Integer,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let tokens = tokenize_string("0 <= x < 10 >= y > 1".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::Integer,
+            TokenKind::LessThanOrEqual,
+            TokenKind::Identifier,
+            TokenKind::LessThan,
+            TokenKind::Integer,
+            TokenKind::GreaterThanOrEqual,
+            TokenKind::Identifier,
+            TokenKind::GreaterThan,
+            TokenKind::Integer,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
+    #[test]
+    #[allow(clippy::get_first)]
     fn test_less_than() {
         let tokens = tokenize_string("<".to_string());
         assert_eq!(tokens.len(), 2);
@@ -723,6 +910,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_equals() {
         let tokens = tokenize_string("=".to_string());
         assert_eq!(tokens.len(), 2);
@@ -735,6 +923,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_is_equals() {
         let tokens = tokenize_string("==".to_string());
         assert_eq!(tokens.len(), 2);
@@ -747,6 +936,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_not_equals() {
         let tokens = tokenize_string("!=".to_string());
         assert_eq!(tokens.len(), 2);
@@ -759,6 +949,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_newline() {
         let tokens = tokenize_string("\n".to_string());
         assert_eq!(tokens.len(), 2);
@@ -794,6 +985,28 @@ mod tests {
     }
 
     #[test]
+    fn test_keyword_def() {
+        let tokens = tokenize_string("def mul(a, b) { }".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::Def,
+            TokenKind::Identifier,
+            TokenKind::OpenParen,
+            TokenKind::Identifier,
+            TokenKind::Comma,
+            TokenKind::Identifier,
+            TokenKind::CloseParen,
+            TokenKind::OpenCurlyBrace,
+            TokenKind::CloseCurlyBrace,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
+    #[test]
+    #[allow(clippy::get_first)]
     fn test_keyword_true() {
         let tokens = tokenize_string("true".to_string());
         assert_eq!(tokens.len(), 2);
@@ -806,6 +1019,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_keyword_false() {
         let tokens = tokenize_string("false".to_string());
         assert_eq!(tokens.len(), 2);
@@ -818,6 +1032,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_keyword_null() {
         let tokens = tokenize_string("null".to_string());
         assert_eq!(tokens.len(), 2);
@@ -830,6 +1045,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_keyword_import() {
         let tokens = tokenize_string("import".to_string());
         assert_eq!(tokens.len(), 2);
@@ -841,6 +1057,67 @@ mod tests {
             .is_some_and(|token| token.kind() == TokenKind::EOF));
     }
 
+    #[test]
+    fn test_import_forms() {
+        let tokens = tokenize_string("import WheelsAPI.*\nimport utils.{a, b as c}".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::Import,
+            TokenKind::Identifier,
+            TokenKind::Point,
+            TokenKind::Multiply,
+            TokenKind::Newline,
+            TokenKind::Import,
+            TokenKind::Identifier,
+            TokenKind::Point,
+            TokenKind::OpenCurlyBrace,
+            TokenKind::Identifier,
+            TokenKind::Comma,
+            TokenKind::Identifier,
+            TokenKind::As,
+            TokenKind::Identifier,
+            TokenKind::CloseCurlyBrace,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_keyword_new() {
+        let tokens = tokenize_string("new Person(\"John\")".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::New,
+            TokenKind::Identifier,
+            TokenKind::OpenParen,
+            TokenKind::StringLiteral,
+            TokenKind::CloseParen,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_keyword_not_is() {
+        let tokens = tokenize_string("not x is not null".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::NotKeyword,
+            TokenKind::Identifier,
+            TokenKind::Is,
+            TokenKind::NotKeyword,
+            TokenKind::Null,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
     #[test]
     fn test_if_statement() {
         let tokens: Vec<Box<dyn Token>> = tokenize_string(
@@ -902,6 +1179,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_while_loop() {
+        let tokens = tokenize_string("while true { break\ncontinue }".to_string());
+        let expected: Vec<TokenKind> = vec![
+            TokenKind::While,
+            TokenKind::True,
+            TokenKind::OpenCurlyBrace,
+            TokenKind::Break,
+            TokenKind::Newline,
+            TokenKind::Continue,
+            TokenKind::CloseCurlyBrace,
+            TokenKind::EOF,
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind(), *expected.get(i).unwrap())
+        }
+    }
+
     #[test]
     fn test_variable() {
         let tokens: Vec<Box<dyn Token>> = tokenize_string("let x = 5\nconst PI = 3.14".to_string());
@@ -922,4 +1218,37 @@ mod tests {
             assert_eq!(token.kind(), *expected.get(i).unwrap())
         }
     }
+
+    #[test]
+    fn test_spans() {
+        let tokens = tokenize_string("x == \"é\"\n  y".to_string());
+        let spans: Vec<(TokenKind, usize, usize, usize, usize)> = tokens
+            .iter()
+            .map(|token| {
+                let span = token.span();
+                let start = (span.start.line, span.start.column);
+                (
+                    token.kind(),
+                    span.start.offset,
+                    span.end.offset,
+                    start.0,
+                    start.1,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (TokenKind::Identifier, 0, 1, 1, 0),
+                (TokenKind::IsEquals, 2, 4, 1, 2),
+                (TokenKind::StringLiteral, 5, 9, 1, 5), // `é` takes two bytes
+                (TokenKind::Newline, 9, 10, 1, 8),
+                (TokenKind::Identifier, 12, 13, 2, 2),
+                (TokenKind::EOF, 13, 13, 2, 3),
+            ]
+        );
+        // the end is right after the token
+        let end = tokens[1].span().end;
+        assert_eq!((end.line, end.column), (1, 4));
+    }
 }
@@ -1,10 +1,11 @@
-use super::{Token, TokenKind};
+use super::{Span, Token, TokenKind};
 
 #[derive(Clone)]
 pub struct Character {
     pub kind: TokenKind,
     pub line: usize,
     pub column: usize,
+    pub span: Span,
 }
 
 impl Token for Character {
@@ -28,6 +29,14 @@ impl Token for Character {
         self.column = value;
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
     fn into_any(self: Box<Self>) -> Box<dyn std::any::Any> {
         self
     }
@@ -48,6 +57,7 @@ impl Default for Character {
             kind: TokenKind::EOF,
             line: usize::default(),
             column: usize::default(),
+            span: Span::default(),
         }
     }
 }
@@ -1,10 +1,11 @@
-use super::{Token, TokenKind};
+use super::{Span, Token, TokenKind};
 
 #[derive(Clone)]
 pub struct Decimal {
     pub kind: TokenKind,
     pub line: usize,
     pub column: usize,
+    pub span: Span,
     pub value: f64,
 }
 
@@ -29,6 +30,14 @@ impl Token for Decimal {
         self.column = value;
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
     fn into_any(self: Box<Self>) -> Box<dyn std::any::Any> {
         self
     }
@@ -56,6 +65,7 @@ impl Default for Decimal {
             kind: TokenKind::Decimal,
             line: usize::default(),
             column: usize::default(),
+            span: Span::default(),
             value: f64::default(),
         }
     }
@@ -31,6 +31,8 @@ pub use identifier::*;
 pub use integer::*;
 pub use string_literal::*;
 
+pub use super::span::Span;
+
 pub trait Token: DynClone {
     fn kind(&self) -> TokenKind;
     fn line(&self) -> usize;
@@ -39,6 +41,9 @@ pub trait Token: DynClone {
     fn set_line(&mut self, value: usize);
     fn set_column(&mut self, value: usize);
 
+    fn span(&self) -> Span;
+    fn set_span(&mut self, span: Span);
+
     fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 
@@ -94,6 +99,7 @@ pub enum TokenKind {
     OpenCurlyBrace,
     CloseCurlyBrace,
     Colon,
+    Semicolon,
     Comma,
     Point,
     Multiply,
@@ -102,7 +108,10 @@ pub enum TokenKind {
     Not,
     GreaterThan,
     LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
     Equals,
+    Arrow, // `=>`
     IsEquals,
     NotEquals,
     Addition,
@@ -114,6 +123,7 @@ pub enum TokenKind {
     Newline,
     // Keywords
     Fn,
+    Def,
     True,
     False,
     Return,
@@ -121,15 +131,93 @@ pub enum TokenKind {
     Else,
     And,
     Or,
+    NotKeyword, // `not`, binds looser than `!`
+    Is,
     For,
+    While,
+    Break,
+    Continue,
     In,
     Let,
     Const,
     Class,
     From,
     Static,
+    New,
     Import,
+    As,
     Null,
     // Another required tokens for parser
     EOF, // signified the end of file.
 }
+
+// how a token is called in error messages
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TokenKind::Identifier => "identifier",
+            TokenKind::Integer => "integer",
+            TokenKind::Decimal => "decimal",
+            TokenKind::StringLiteral => "string",
+            TokenKind::Plus => "`+`",
+            TokenKind::Minus => "`-`",
+            TokenKind::Increment => "`++`",
+            TokenKind::Decrement => "`--`",
+            TokenKind::OpenParen => "`(`",
+            TokenKind::CloseParen => "`)`",
+            TokenKind::OpenSquareBracket => "`[`",
+            TokenKind::CloseSquareBracket => "`]`",
+            TokenKind::OpenCurlyBrace => "`{`",
+            TokenKind::CloseCurlyBrace => "`}`",
+            TokenKind::Colon => "`:`",
+            TokenKind::Semicolon => "`;`",
+            TokenKind::Comma => "`,`",
+            TokenKind::Point => "`.`",
+            TokenKind::Multiply => "`*`",
+            TokenKind::Divide => "`/`",
+            TokenKind::Modulo => "`%`",
+            TokenKind::Not => "`!`",
+            TokenKind::GreaterThan => "`>`",
+            TokenKind::LessThan => "`<`",
+            TokenKind::GreaterThanOrEqual => "`>=`",
+            TokenKind::LessThanOrEqual => "`<=`",
+            TokenKind::Equals => "`=`",
+            TokenKind::Arrow => "`=>`",
+            TokenKind::IsEquals => "`==`",
+            TokenKind::NotEquals => "`!=`",
+            TokenKind::Addition => "`+=`",
+            TokenKind::Subtraction => "`-=`",
+            TokenKind::Multiplication => "`*=`",
+            TokenKind::Division => "`/=`",
+            TokenKind::Modulation => "`%=`",
+            TokenKind::Newline => "new line",
+            TokenKind::Fn => "`fn`",
+            TokenKind::Def => "`def`",
+            TokenKind::True => "`true`",
+            TokenKind::False => "`false`",
+            TokenKind::Return => "`return`",
+            TokenKind::If => "`if`",
+            TokenKind::Else => "`else`",
+            TokenKind::And => "`and`",
+            TokenKind::Or => "`or`",
+            TokenKind::NotKeyword => "`not`",
+            TokenKind::Is => "`is`",
+            TokenKind::For => "`for`",
+            TokenKind::While => "`while`",
+            TokenKind::Break => "`break`",
+            TokenKind::Continue => "`continue`",
+            TokenKind::In => "`in`",
+            TokenKind::Let => "`let`",
+            TokenKind::Const => "`const`",
+            TokenKind::Class => "`class`",
+            TokenKind::From => "`from`",
+            TokenKind::Static => "`static`",
+            TokenKind::New => "`new`",
+            TokenKind::Import => "`import`",
+            TokenKind::As => "`as`",
+            TokenKind::Null => "`null`",
+            TokenKind::EOF => "end of file",
+        };
+        write!(f, "{}", name)
+    }
+}
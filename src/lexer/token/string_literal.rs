@@ -1,10 +1,11 @@
-use super::{Token, TokenKind};
+use super::{Span, Token, TokenKind};
 
 #[derive(Clone)]
 pub struct StringLiteral {
     pub kind: TokenKind,
     pub line: usize,
     pub column: usize,
+    pub span: Span,
     pub value: String,
 }
 
@@ -29,6 +30,14 @@ impl Token for StringLiteral {
         self.column = value;
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
     fn into_any(self: Box<Self>) -> Box<dyn std::any::Any> {
         self
     }
@@ -56,6 +65,7 @@ impl Default for StringLiteral {
             kind: TokenKind::StringLiteral,
             line: usize::default(),
             column: usize::default(),
+            span: Span::default(),
             value: String::default(),
         }
     }
@@ -0,0 +1,7 @@
+pub mod diagnostics;
+pub mod lexer;
+mod macros;
+pub mod parser;
+pub mod runtime;
+pub mod script;
+pub mod vm;
@@ -1,46 +1,85 @@
-use std::{cmp::Ordering, env, fs};
+use std::{env, fs, path::Path, process};
 
-use lexer::Lexer;
-
-use crate::{
-    lexer::token::{Token, TokenKind},
+use pl::{
+    diagnostics::{self, Diagnostic},
+    lexer::Lexer,
     parser::Parser,
+    runtime::Interpreter,
+    vm::{disassemble, Vm},
 };
 
-// use crate::lexer::token::TokenKind;
-
-mod lexer;
-mod macros;
-mod parser;
-
 fn read_file(file_path: String) -> String {
-    let contents = fs::read_to_string(file_path).expect("Should have been able to read the file");
+    fs::read_to_string(file_path).expect("Should have been able to read the file")
+}
 
-    contents
+/// Prints the diagnostics, as JSON on stdout with --json, and stops.
+fn report(diagnostics: &[Diagnostic], source: &str, filename: &str, as_json: bool) -> ! {
+    if as_json {
+        println!("{}", diagnostics::json(diagnostics, filename));
+    } else {
+        eprint!("{}", diagnostics::render(diagnostics, source, filename));
+    }
+    process::exit(1)
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let filename = match args.len().cmp(&1) {
-        Ordering::Equal | Ordering::Less => "test/test-assignment-expression.pl",
-        Ordering::Greater => args.get(1).unwrap(),
-    };
+    // --vm runs the script on the bytecode vm, --disassemble prints its bytecode
+    let use_vm = args.iter().any(|arg| arg == "--vm");
+    let print_bytecode = args.iter().any(|arg| arg == "--disassemble");
+    // --json prints errors as JSON for editors and other tools
+    let as_json = args.iter().any(|arg| arg == "--json");
+    let filename = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or("test/test-assignment-expression.pl");
 
     let source = read_file(filename.to_string());
+    let fail = |diagnostic: Diagnostic| -> ! { report(&[diagnostic], &source, filename, as_json) };
     let mut lexer = Lexer::new(source.to_string());
     match lexer.tokenize() {
-        Err(err) => panic!("Error: {}", err),
+        Err(err) => fail(Diagnostic::from_lexer_error(&err, lexer.span())),
         Ok(_) => {
-            let with_no_newline: Vec<Box<dyn Token>> = lexer
-                .tokens
-                .into_iter()
-                .filter(|tok| !matches!(tok.kind(), TokenKind::Newline))
-                .collect();
-            let mut parser = Parser::new(with_no_newline);
-            match parser.produce_ast() {
-                Ok(program) => println!("ast: {:#?}", program),
-                Err(err) => panic!("Error while parsing: {}", err),
+            let mut parser = Parser::new(lexer.tokens);
+            // every syntax error is shown at once, not just the first one
+            let (program, errors) = parser.produce_partial_ast();
+            if !errors.is_empty() {
+                let diagnostics: Vec<Diagnostic> = errors
+                    .iter()
+                    .map(|err| Diagnostic::from_parse_error(err, &source))
+                    .collect();
+                report(&diagnostics, &source, filename, as_json)
+            }
+
+            if use_vm || print_bytecode {
+                let mut vm = Vm::new();
+                let script = match vm.compile_file(&program, Path::new(filename)) {
+                    Ok(script) => script,
+                    Err(err) => fail(Diagnostic::error(err.to_string())),
+                };
+                if print_bytecode {
+                    print!("{}", disassemble(&script));
+                }
+                if use_vm {
+                    match vm.run(script) {
+                        Ok(value) => println!("result: {}", value),
+                        Err(err) => fail(Diagnostic::from_runtime_error(
+                            &err,
+                            Some(Path::new(filename)),
+                        )),
+                    }
+                }
+            } else {
+                let mut interpreter = Interpreter::new();
+                match interpreter.interpret_file(&program, Path::new(filename)) {
+                    Ok(value) => println!("result: {}", value),
+                    Err(err) => fail(Diagnostic::from_runtime_error(
+                        &err,
+                        Some(Path::new(filename)),
+                    )),
+                }
             }
         }
     }
@@ -1,5 +1,8 @@
 use crate::{
-    lexer::token::{Decimal, Identifier, Integer, StringLiteral, Token, TokenKind},
+    lexer::{
+        token::{Decimal, Identifier, Integer, StringLiteral, Token, TokenKind},
+        Position, Span,
+    },
     macros::bail,
 };
 
@@ -7,33 +10,199 @@ use std::any::Any;
 
 use super::{
     error::ParseError,
-    nodes::{AssignmentOperator, BinaryOperator, LogicalOperator, Node, UnaryOperator},
+    nodes::{
+        AssignmentOperator, BinaryOperator, ImportKind, LogicalOperator, Node, NodeKind,
+        UnaryOperator,
+    },
 };
 
+/// Keywords that may start a function or method declaration.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Dialect {
+    #[default]
+    Any, // both `fn` and `def`
+    Fn,
+    Def,
+}
+
+impl Dialect {
+    fn keywords(&self) -> &'static [TokenKind] {
+        match self {
+            Dialect::Any => &[TokenKind::Fn, TokenKind::Def],
+            Dialect::Fn => &[TokenKind::Fn],
+            Dialect::Def => &[TokenKind::Def],
+        }
+    }
+
+    fn allows(&self, keyword: TokenKind) -> bool {
+        self.keywords().contains(&keyword)
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Box<dyn Token>>,
     position: usize,
+    dialect: Dialect,
+    loop_depth: usize, // loops around the current statement, `break` needs at least one
+    previous_end: Position, // end of the last eaten token, where the node being parsed ends
+    expected: Vec<TokenKind>, // tokens that would have been accepted instead of the current one
+    errors: Vec<ParseError>, // errors found so far, parsing goes on after them
+    block_depth: usize, // blocks around the current statement
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Box<dyn Token>>) -> Self {
         Self {
-            tokens,
+            tokens: significant_newlines(tokens),
             position: 0,
+            dialect: Dialect::default(),
+            loop_depth: 0,
+            previous_end: Position::default(),
+            expected: vec![],
+            errors: vec![],
+            block_depth: 0,
         }
     }
 
+    /// Restricts which keywords declare functions, every keyword is accepted by default.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// The program, or the first error in it.
     pub fn produce_ast(&mut self) -> Result<Node, ParseError> {
-        let mut program = Node::Program(vec![]);
+        let (program, mut errors) = self.produce_partial_ast();
+        match errors.is_empty() {
+            true => Ok(program),
+            false => Err(errors.remove(0)),
+        }
+    }
+
+    /// Parses the whole program even if it has errors, the statements that couldn't be
+    /// parsed are left out of it. The errors come in the order they are in the source.
+    pub fn produce_partial_ast(&mut self) -> (Node, Vec<ParseError>) {
+        let start = self
+            .tokens
+            .first()
+            .map(|token| token.span().start)
+            .unwrap_or_default();
+        let mut statements = vec![];
+
+        loop {
+            self.skip_separators();
+            if !self.not_eof() {
+                break;
+            }
+            if let Some(statement) = self.recover(|parser| parser.terminated(Self::statement)) {
+                statements.push(Box::new(statement));
+            }
+        }
+
+        let program = self.node(start, NodeKind::Program(statements));
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    // keeps an error, a place that already has one doesn't get a second
+    fn report(&mut self, err: ParseError) {
+        let last = self.errors.last().and_then(|last| last.span());
+        if last.is_none() || last != err.span() {
+            self.errors.push(err);
+        }
+    }
 
-        while self.not_eof() {
-            let stmt = self.statement()?;
-            if let Node::Program(ref mut stmts) = program {
-                stmts.push(Box::new(stmt));
+    // parses a statement with `parse`, if it fails the error is kept and the tokens up to
+    // the start of the next statement are skipped
+    fn recover(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Node, ParseError>,
+    ) -> Option<Node> {
+        let position = self.position;
+        match parse(self) {
+            Ok(node) => Some(node),
+            Err(err) => {
+                self.report(err);
+                self.expected.clear();
+                if self.position == position && self.not_eof() {
+                    self.position += 1;
+                }
+                self.synchronize();
+                None
             }
         }
+    }
 
-        Ok(program)
+    // skips to a keyword that starts a statement, the `}` closing the current block
+    // or the first token of the next line
+    fn synchronize(&mut self) {
+        while let Some(token) = self.tokens.get(self.position) {
+            let starts_line = self.position > 0
+                && self.tokens[self.position - 1].span().end.line < token.span().start.line;
+            match token.kind() {
+                TokenKind::EOF => return,
+                TokenKind::CloseCurlyBrace if self.block_depth > 0 => return,
+                TokenKind::Newline | TokenKind::Semicolon => {
+                    self.position += 1;
+                    return;
+                }
+                TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::Fn
+                | TokenKind::Def
+                | TokenKind::If
+                | TokenKind::For
+                | TokenKind::While
+                | TokenKind::Return
+                | TokenKind::Import
+                | TokenKind::Class => return,
+                _ if starts_line => return,
+                _ => self.position += 1,
+            }
+        }
+    }
+
+    // a statement parsed with `parse` and what ends it: a new line, `;`, the `}` closing
+    // the block or the end of the file
+    fn terminated(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Node, ParseError>,
+    ) -> Result<Node, ParseError> {
+        let statement = parse(self)?;
+        for separator in [TokenKind::Newline, TokenKind::Semicolon] {
+            if self.check(separator)? {
+                self.eat(separator)?;
+                return Ok(statement);
+            }
+        }
+        let end = match self.block_depth {
+            0 => TokenKind::EOF,
+            _ => TokenKind::CloseCurlyBrace,
+        };
+        if !self.check(end)? {
+            bail!(self.unexpected(&[]))
+        }
+        Ok(statement)
+    }
+
+    // empty statements, like blank lines or `;;`
+    fn skip_separators(&mut self) {
+        while self
+            .tokens
+            .get(self.position)
+            .is_some_and(|token| matches!(token.kind(), TokenKind::Newline | TokenKind::Semicolon))
+        {
+            self.position += 1;
+        }
+    }
+
+    // where the current token, and so the node parsed next, starts
+    fn start(&self) -> Result<Position, ParseError> {
+        Ok(self.get_current_token()?.span().start)
+    }
+
+    // a node from `start` up to the last eaten token
+    fn node(&self, start: Position, kind: NodeKind) -> Node {
+        Node::new(kind, Span::new(start, self.previous_end))
     }
 
     fn not_eof(&self) -> bool {
@@ -41,6 +210,7 @@ impl Parser {
         current_token.is_some_and(|token| !matches!(token.kind(), TokenKind::EOF))
     }
 
+    #[allow(clippy::borrowed_box)]
     fn get_current_token(&self) -> Result<&Box<dyn Token>, ParseError> {
         if let Some(token) = self.tokens.get(self.position) {
             Ok(token)
@@ -49,13 +219,14 @@ impl Parser {
         }
     }
 
+    #[allow(clippy::borrowed_box)]
     fn peek(&self) -> Option<&Box<dyn Token>> {
         self.tokens.get(self.position + 1)
     }
 
+    #[allow(clippy::borrowed_box)]
     fn eat(&mut self, kind: TokenKind) -> Result<&Box<dyn Token>, ParseError> {
         let token = self.tokens.get(self.position);
-        self.position += 1;
         if token.is_none() {
             bail!(ParseError::UnexpectedEOF)
         }
@@ -63,85 +234,170 @@ impl Parser {
         let token = token.unwrap();
         let token_data = token;
         let token_kind = token_data.kind();
-        let line = token_data.line();
-        let column = token_data.column();
 
         if token_kind == kind {
+            self.position += 1;
+            self.previous_end = token.span().end;
+            self.expected.clear();
             Ok(token)
         } else {
-            bail!(ParseError::UnexpectedToken(token_kind, line, column))
+            let mut expected = std::mem::take(&mut self.expected);
+            if !expected.contains(&kind) {
+                expected.push(kind);
+            }
+            bail!(ParseError::UnexpectedToken(
+                token_kind,
+                expected,
+                token_data.span()
+            ))
         }
     }
 
+    // an error at the current token, which isn't any of the expected tokens
+    fn unexpected(&mut self, kinds: &[TokenKind]) -> ParseError {
+        let mut expected = std::mem::take(&mut self.expected);
+        for kind in kinds {
+            if !expected.contains(kind) {
+                expected.push(*kind);
+            }
+        }
+        match self.get_current_token() {
+            Ok(token) => ParseError::UnexpectedToken(token.kind(), expected, token.span()),
+            Err(err) => err,
+        }
+    }
+
+    // whether the current token is `kind`, if it isn't `kind` is one of the tokens
+    // an error at this token tells the player about
+    fn check(&mut self, kind: TokenKind) -> Result<bool, ParseError> {
+        let found = self.get_current_token()?.kind() == kind;
+        if !found && !self.expected.contains(&kind) {
+            self.expected.push(kind);
+        }
+        Ok(found)
+    }
+
     fn statement(&mut self) -> Result<Node, ParseError> {
         match self.get_current_token()?.kind() {
             TokenKind::Let | TokenKind::Const => self.variable_declaration(),
-            TokenKind::Fn => self.function_declaration(),
+            TokenKind::Fn | TokenKind::Def if !self.is_function_expression() => {
+                self.function_declaration()
+            }
             TokenKind::If => self.if_statement(),
             TokenKind::For => self.for_statement(),
+            TokenKind::While => self.while_statement(),
+            TokenKind::Break | TokenKind::Continue => self.loop_control_statement(),
             TokenKind::Return => self.return_statement(),
             TokenKind::Import => self.import_statement(),
             TokenKind::Class => self.class_declaration(),
+            TokenKind::OpenCurlyBrace if !self.is_object_start() => self.block_statement(),
             _ => self.expression(),
         }
     }
 
+    // tells an object literal apart from a block when a statement starts with `{`,
+    // objects are `{}`, `{key: ...}` or `{[key]: ...}`
+    fn is_object_start(&self) -> bool {
+        let kind = |offset: usize| {
+            self.tokens
+                .get(self.position + offset)
+                .map(|token| token.kind())
+        };
+
+        match kind(1) {
+            Some(TokenKind::CloseCurlyBrace) => true,
+            Some(TokenKind::StringLiteral | TokenKind::Identifier) => {
+                kind(2) == Some(TokenKind::Colon)
+            }
+            Some(TokenKind::OpenSquareBracket) => {
+                let mut depth = 0;
+                let mut offset = 1;
+                while let Some(token) = kind(offset) {
+                    match token {
+                        TokenKind::OpenSquareBracket => depth += 1,
+                        TokenKind::CloseSquareBracket => depth -= 1,
+                        TokenKind::EOF => return false,
+                        _ => {}
+                    }
+                    offset += 1;
+                    if depth == 0 {
+                        return kind(offset) == Some(TokenKind::Colon);
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
     fn class_declaration(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         self.eat(TokenKind::Class)?;
 
         let class_name = self.identifier()?;
 
-        let super_class: Option<Box<Node>> = match self.get_current_token()?.kind() {
-            TokenKind::From => {
+        let super_class: Option<Box<Node>> = match self.check(TokenKind::From)? {
+            true => {
                 self.eat(TokenKind::From)?;
                 Some(Box::new(self.identifier()?))
             }
-            _ => None,
+            false => None,
         };
 
         self.eat(TokenKind::OpenCurlyBrace)?;
 
         let mut body: Vec<Box<Node>> = vec![];
 
-        while self.get_current_token()?.kind() != TokenKind::CloseCurlyBrace {
-            let statement = self.class_statement()?;
-            body.push(Box::new(statement));
+        self.block_depth += 1;
+        loop {
+            self.skip_separators();
+            if !self.not_eof() || self.check(TokenKind::CloseCurlyBrace)? {
+                break;
+            }
+            if let Some(statement) = self.recover(|parser| parser.terminated(Self::class_statement))
+            {
+                body.push(Box::new(statement));
+            }
         }
+        self.block_depth -= 1;
 
         self.eat(TokenKind::CloseCurlyBrace)?;
 
-        Ok(Node::ClassDeclaration(
-            Box::new(class_name),
-            super_class,
-            body,
+        Ok(self.node(
+            start,
+            NodeKind::ClassDeclaration(Box::new(class_name), super_class, body),
         ))
     }
 
+    #[allow(clippy::needless_question_mark, clippy::needless_return)]
     fn class_statement(&mut self) -> Result<Node, ParseError> {
         match self.get_current_token()?.kind() {
             TokenKind::Identifier => return Ok(self.class_property_definition()?),
-            TokenKind::Fn => return Ok(self.class_method_definition()?),
+            TokenKind::Fn | TokenKind::Def => return Ok(self.class_method_definition()?),
             TokenKind::Static => match self.peek() {
                 Some(token) => match token.kind() {
                     TokenKind::Identifier => return Ok(self.class_property_definition()?),
-                    TokenKind::Fn => return Ok(self.class_method_definition()?),
+                    TokenKind::Fn | TokenKind::Def => return Ok(self.class_method_definition()?),
                     kind => bail!(ParseError::UnexpectedToken(
                         kind,
-                        token.line(),
-                        token.column()
+                        vec![TokenKind::Identifier, TokenKind::Fn, TokenKind::Def],
+                        token.span()
                     )),
                 },
                 None => bail!(ParseError::UnexpectedEOF),
             },
-            kind => bail!(ParseError::UnexpectedToken(
-                kind,
-                self.get_current_token()?.line(),
-                self.get_current_token()?.column()
-            )),
+            _ => bail!(self.unexpected(&[
+                TokenKind::Identifier,
+                TokenKind::Fn,
+                TokenKind::Def,
+                TokenKind::Static
+            ])),
         }
     }
 
+    #[allow(clippy::match_like_matches_macro)]
     fn class_property_definition(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         let is_static = match self.get_current_token()?.kind() {
             TokenKind::Static => true,
             _ => false,
@@ -152,14 +408,15 @@ impl Parser {
         let id = self.identifier()?;
         self.eat(TokenKind::Equals)?;
         let value = self.expression()?;
-        Ok(Node::PropertyDefinition(
-            Box::new(id),
-            Box::new(value),
-            is_static,
+        Ok(self.node(
+            start,
+            NodeKind::PropertyDefinition(Box::new(id), Box::new(value), is_static),
         ))
     }
 
+    #[allow(clippy::match_like_matches_macro)]
     fn class_method_definition(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         let is_static = match self.get_current_token()?.kind() {
             TokenKind::Static => true,
             _ => false,
@@ -167,57 +424,173 @@ impl Parser {
         if is_static {
             self.eat(TokenKind::Static)?;
         }
-        self.eat(TokenKind::Fn)?;
+        self.function_keyword()?;
         let id = self.identifier()?;
-        let args = self.arguments()?;
-        let block = self.block_statement()?;
-        Ok(Node::MethodDefinition(
-            Box::new(id),
-            args.into_iter().map(|x| Box::new(x)).collect(),
-            Box::new(block),
-            is_static,
+        let params = self.parameters()?;
+        let block = self.function_body()?;
+        Ok(self.node(
+            start,
+            NodeKind::MethodDefinition(
+                Box::new(id),
+                params.into_iter().map(Box::new).collect(),
+                Box::new(block),
+                is_static,
+            ),
         ))
     }
 
     fn return_statement(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         self.eat(TokenKind::Return)?;
 
-        let value = self.expression()?;
+        // a bare `return` is all there is to the statement
+        let value = match self.get_current_token()?.kind() {
+            TokenKind::Newline
+            | TokenKind::Semicolon
+            | TokenKind::CloseCurlyBrace
+            | TokenKind::EOF => None,
+            _ => Some(Box::new(self.expression()?)),
+        };
 
-        Ok(Node::ReturnStatement(Box::new(value)))
+        Ok(self.node(start, NodeKind::ReturnStatement(value)))
     }
 
     fn function_declaration(&mut self) -> Result<Node, ParseError> {
-        self.eat(TokenKind::Fn)?;
+        let start = self.start()?;
+        self.function_keyword()?;
 
         let id = Box::new(self.identifier()?);
 
-        let args = self.arguments()?;
+        let params = self.parameters()?;
+
+        let body = Box::new(self.function_body()?);
+
+        Ok(self.node(
+            start,
+            NodeKind::FunctionDeclaration(id, params.into_iter().map(Box::new).collect(), body),
+        ))
+    }
+
+    // `(a, b = 1, *rest)`: parameters after one with a default value need a default
+    // as well and the rest parameter, collecting the extra arguments, comes last
+    fn parameters(&mut self) -> Result<Vec<Node>, ParseError> {
+        self.eat(TokenKind::OpenParen)?;
+
+        let mut params: Vec<Node> = vec![];
+        let mut names: Vec<String> = vec![];
+        let mut has_default = false;
+        while !self.check(TokenKind::CloseParen)? {
+            let start = self.start()?;
+            let is_rest = self.check(TokenKind::Multiply)?;
+            if is_rest {
+                self.eat(TokenKind::Multiply)?;
+            }
+            let span = self.get_current_token()?.span();
+            let name = self.name()?;
+            if names.contains(&name) {
+                self.report(ParseError::DuplicateParameter(name.clone(), span));
+            }
+
+            let default = if !is_rest && self.check(TokenKind::Equals)? {
+                self.eat(TokenKind::Equals)?;
+                has_default = true;
+                Some(Box::new(self.expression()?))
+            } else {
+                if has_default && !is_rest {
+                    self.report(ParseError::MissingDefault(name.clone(), span));
+                }
+                None
+            };
+            names.push(name.clone());
+            params.push(self.node(start, NodeKind::Parameter(name, default, is_rest)));
+
+            // a trailing comma is fine, anything after the rest parameter is not
+            if !self.check(TokenKind::Comma)? {
+                break;
+            }
+            self.eat(TokenKind::Comma)?;
+            if is_rest {
+                break;
+            }
+        }
+
+        self.eat(TokenKind::CloseParen)?;
+        Ok(params)
+    }
+
+    // an anonymous function has its parameters right after the keyword
+    fn is_function_expression(&self) -> bool {
+        self.peek()
+            .is_some_and(|token| token.kind() == TokenKind::OpenParen)
+    }
 
-        let body = Box::new(self.block_statement()?);
+    // `fn(x) { ... }`, or `fn(x) => x * 2` which returns the expression after the arrow
+    fn function_expression(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        self.function_keyword()?;
 
-        Ok(Node::FunctionDeclaration(
-            id,
-            args.into_iter().map(|x| Box::new(x)).collect(),
-            body,
+        let params = self.parameters()?;
+
+        let body = if self.check(TokenKind::Arrow)? {
+            self.eat(TokenKind::Arrow)?;
+            let value_start = self.start()?;
+            let value = self.expression()?;
+            let statement = self.node(
+                value_start,
+                NodeKind::ReturnStatement(Some(Box::new(value))),
+            );
+            self.node(
+                value_start,
+                NodeKind::BlockStatement(vec![Box::new(statement)]),
+            )
+        } else {
+            self.function_body()?
+        };
+
+        Ok(self.node(
+            start,
+            NodeKind::FunctionExpression(
+                params.into_iter().map(Box::new).collect(),
+                Box::new(body),
+            ),
         ))
     }
 
+    // `fn` or `def`, as far as the dialect allows it
+    fn function_keyword(&mut self) -> Result<(), ParseError> {
+        let kind = self.get_current_token()?.kind();
+        if !self.dialect.allows(kind) {
+            bail!(self.unexpected(self.dialect.keywords()))
+        }
+        self.eat(kind)?;
+        Ok(())
+    }
+
     fn block_statement(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         self.eat(TokenKind::OpenCurlyBrace)?;
 
         let mut statements: Vec<Box<Node>> = vec![];
 
-        while self.get_current_token()?.kind() != TokenKind::CloseCurlyBrace {
-            statements.push(Box::new(self.statement()?));
+        self.block_depth += 1;
+        loop {
+            self.skip_separators();
+            if !self.not_eof() || self.check(TokenKind::CloseCurlyBrace)? {
+                break;
+            }
+            if let Some(statement) = self.recover(|parser| parser.terminated(Self::statement)) {
+                statements.push(Box::new(statement));
+            }
         }
+        self.block_depth -= 1;
 
         self.eat(TokenKind::CloseCurlyBrace)?;
 
-        Ok(Node::BlockStatement(statements))
+        Ok(self.node(start, NodeKind::BlockStatement(statements)))
     }
 
     fn if_statement(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         self.eat(TokenKind::If)?;
 
         let condition = self.expression()?;
@@ -226,10 +599,10 @@ impl Parser {
 
         let mut alternate: Option<Box<Node>> = None;
 
-        if self.get_current_token()?.kind() == TokenKind::Else {
+        if self.check(TokenKind::Else)? {
             self.eat(TokenKind::Else)?;
 
-            if self.get_current_token()?.kind() == TokenKind::If {
+            if self.check(TokenKind::If)? {
                 alternate = Some(Box::new(self.if_statement()?));
             } else {
                 let block = self.block_statement()?;
@@ -237,14 +610,15 @@ impl Parser {
             }
         }
 
-        Ok(Node::IfStatement(
-            Box::new(condition),
-            Box::new(consequent),
-            alternate,
+        Ok(self.node(
+            start,
+            NodeKind::IfStatement(Box::new(condition), Box::new(consequent), alternate),
         ))
     }
 
+    #[allow(clippy::clone_on_copy, clippy::needless_bool)]
     fn variable_declaration(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         let is_constant = if matches!(self.get_current_token()?.kind(), TokenKind::Const) {
             true
         } else {
@@ -257,42 +631,44 @@ impl Parser {
         }
         let identifier_token = self.eat(TokenKind::Identifier)?;
         let kind = identifier_token.kind().clone();
-        let line = identifier_token.line().clone();
-        let column = identifier_token.column().clone();
+        let span = identifier_token.span();
         let identifier_token = dyn_clone::clone_box(&**identifier_token);
         let identifier = identifier_token.into_any().downcast::<Identifier>();
         if identifier.is_err() {
-            bail!(ParseError::UnexpectedToken(kind, line, column))
+            bail!(ParseError::UnexpectedToken(
+                kind,
+                vec![TokenKind::Identifier],
+                span
+            ))
         }
         let identifier = identifier.unwrap();
         // check if we have some value to assign
-        if self.get_current_token()?.kind() == TokenKind::Equals {
+        if self.check(TokenKind::Equals)? {
             self.eat(TokenKind::Equals)?;
             let value = self.expression()?;
-            Ok(Node::VariableDeclaration(
-                identifier.value(),
-                Some(Box::new(value)),
-                is_constant,
+            Ok(self.node(
+                start,
+                NodeKind::VariableDeclaration(
+                    identifier.value(),
+                    Some(Box::new(value)),
+                    is_constant,
+                ),
             ))
         } else {
             // check if variable was a constant
             if is_constant {
                 // constant cannot be declarated without value
-                bail!(ParseError::ConstantNotInitialized(
-                    identifier.value(),
-                    line,
-                    column
-                ))
-            }
-            Ok(Node::VariableDeclaration(
-                identifier.value(),
-                None,
-                is_constant,
+                self.report(ParseError::ConstantNotInitialized(identifier.value(), span));
+            }
+            Ok(self.node(
+                start,
+                NodeKind::VariableDeclaration(identifier.value(), None, is_constant),
             ))
         }
     }
 
     fn for_statement(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         self.eat(TokenKind::For)?;
 
         let left = self.expression()?;
@@ -302,319 +678,431 @@ impl Parser {
 
         let right = self.expression()?;
 
-        let body = self.block_statement()?;
+        let body = self.loop_body()?;
 
-        Ok(Node::ForInStatement(
-            Box::new(left),
-            Box::new(right),
-            Box::new(body),
+        Ok(self.node(
+            start,
+            NodeKind::ForInStatement(Box::new(left), Box::new(right), Box::new(body)),
         ))
     }
 
+    fn while_statement(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        self.eat(TokenKind::While)?;
+        let condition = self.expression()?;
+        let body = self.loop_body()?;
+        Ok(self.node(
+            start,
+            NodeKind::WhileStatement(Box::new(condition), Box::new(body)),
+        ))
+    }
+
+    fn loop_body(&mut self) -> Result<Node, ParseError> {
+        self.loop_depth += 1;
+        let body = self.block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn loop_control_statement(&mut self) -> Result<Node, ParseError> {
+        let token = self.get_current_token()?;
+        let (kind, span) = (token.kind(), token.span());
+        if self.loop_depth == 0 {
+            self.report(ParseError::OutsideOfLoop(kind, span));
+        }
+        let start = self.start()?;
+        self.eat(kind)?;
+        match kind {
+            TokenKind::Break => Ok(self.node(start, NodeKind::BreakStatement())),
+            _ => Ok(self.node(start, NodeKind::ContinueStatement())),
+        }
+    }
+
+    // bodies of functions are never inside of a loop, even if the function is
+    fn function_body(&mut self) -> Result<Node, ParseError> {
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.block_statement();
+        self.loop_depth = loop_depth;
+        body
+    }
+
     fn import_statement(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
         self.eat(TokenKind::Import)?;
 
-        let entity = self.member_expression()?;
+        // module path, optionally followed by `.*` or `.{names}`
+        let path_start = self.start()?;
+        let mut path = self.identifier()?;
+        let mut kind = None;
+        while self.check(TokenKind::Point)? {
+            self.eat(TokenKind::Point)?;
+            match self.get_current_token()?.kind() {
+                TokenKind::Multiply => {
+                    self.eat(TokenKind::Multiply)?;
+                    kind = Some(ImportKind::Wildcard);
+                    break;
+                }
+                TokenKind::OpenCurlyBrace => {
+                    kind = Some(ImportKind::Names(self.import_names()?));
+                    break;
+                }
+                _ => {
+                    let property = self.identifier()?;
+                    let member =
+                        NodeKind::MemberExpression(Box::new(path), Box::new(property), false);
+                    path = self.node(path_start, member);
+                }
+            }
+        }
+
+        let kind = match kind {
+            Some(kind) => kind,
+            None => ImportKind::Module(self.import_alias()?),
+        };
+
+        Ok(self.node(start, NodeKind::ImportStatement(Box::new(path), kind)))
+    }
+
+    // { name, name as alias, ... }
+    fn import_names(&mut self) -> Result<Vec<(String, Option<String>)>, ParseError> {
+        self.eat(TokenKind::OpenCurlyBrace)?;
+
+        let mut names = vec![(self.name()?, self.import_alias()?)];
+        while self.check(TokenKind::Comma)? {
+            self.eat(TokenKind::Comma)?;
+            // trailing comma
+            if self.check(TokenKind::CloseCurlyBrace)? {
+                break;
+            }
+            names.push((self.name()?, self.import_alias()?));
+        }
+
+        self.eat(TokenKind::CloseCurlyBrace)?;
+
+        Ok(names)
+    }
 
-        Ok(Node::ImportStatement(Box::new(entity)))
+    fn import_alias(&mut self) -> Result<Option<String>, ParseError> {
+        if !self.check(TokenKind::As)? {
+            return Ok(None);
+        }
+        self.eat(TokenKind::As)?;
+
+        Ok(Some(self.name()?))
     }
 
     fn expression(&mut self) -> Result<Node, ParseError> {
         self.assignment_expression()
     }
 
+    fn assignment_operator(kind: TokenKind) -> Option<AssignmentOperator> {
+        match kind {
+            TokenKind::Equals => Some(AssignmentOperator::Equals),
+            TokenKind::Addition => Some(AssignmentOperator::Addition),
+            TokenKind::Subtraction => Some(AssignmentOperator::Subtraction),
+            TokenKind::Multiplication => Some(AssignmentOperator::Multiplication),
+            TokenKind::Division => Some(AssignmentOperator::Division),
+            TokenKind::Modulation => Some(AssignmentOperator::Modulation),
+            _ => None,
+        }
+    }
+
+    // from the loosest to the tightest binding level:
+    // assignment < or < and < not < equality < relational < additive
+    // < multiplicative < unary < call/member < primary
     fn assignment_expression(&mut self) -> Result<Node, ParseError> {
-        let left = self.logical_expression()?;
+        let start = self.start()?;
+        let left = self.or_expression()?;
 
         let kind = self.get_current_token()?.kind();
-        let line = self.get_current_token()?.line();
-        let column = self.get_current_token()?.column();
-
-        if kind == TokenKind::Equals
-            || kind == TokenKind::Addition
-            || kind == TokenKind::Subtraction
-            || kind == TokenKind::Multiplication
-            || kind == TokenKind::Division
-            || kind == TokenKind::Modulation
-        {
-            println!("it's assignment expression!");
-            self.eat(kind)?;
-            let operator = match kind {
-                TokenKind::Equals => AssignmentOperator::Equals,
-                TokenKind::Addition => AssignmentOperator::Addition,
-                TokenKind::Subtraction => AssignmentOperator::Subtraction,
-                TokenKind::Multiplication => AssignmentOperator::Multiplication,
-                TokenKind::Division => AssignmentOperator::Division,
-                TokenKind::Modulation => AssignmentOperator::Modulation,
-                _ => bail!(ParseError::UnexpectedToken(kind, line, column)),
-            };
-            let value = self.expression()?;
-            return Ok(Node::AssignmentExpression(
-                Box::new(left),
-                operator,
-                Box::new(value),
-            ));
+        let Some(operator) = Self::assignment_operator(kind) else {
+            return Ok(left);
+        };
+
+        if !matches!(
+            left.kind,
+            NodeKind::Identifier(_) | NodeKind::MemberExpression(..)
+        ) {
+            bail!(ParseError::InvalidAssignmentTarget(left.span))
         }
+        self.eat(kind)?;
+        // right associative, `a = b = c` is `a = (b = c)`
+        let value = self.assignment_expression()?;
 
-        Ok(left)
+        Ok(self.node(
+            start,
+            NodeKind::AssignmentExpression(Box::new(left), operator, Box::new(value)),
+        ))
     }
 
-    fn logical_expression(&mut self) -> Result<Node, ParseError> {
-        let mut result = self.condition_expression()?;
+    fn or_expression(&mut self) -> Result<Node, ParseError> {
+        self.logical_expression(TokenKind::Or, LogicalOperator::Or, Self::and_expression)
+    }
 
-        // check if it logical expressions, e.g. we have && as current token
-        while self.get_current_token()?.kind() == TokenKind::And
-            || self.get_current_token()?.kind() == TokenKind::Or
-        {
-            let token = self.eat(self.get_current_token()?.kind())?;
+    fn and_expression(&mut self) -> Result<Node, ParseError> {
+        self.logical_expression(TokenKind::And, LogicalOperator::And, Self::not_expression)
+    }
 
-            match token.kind() {
-                TokenKind::And => {
-                    let right = self.expression()?;
-                    result = Node::LogicalExpression(
-                        Box::new(result),
-                        LogicalOperator::And,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::Or => {
-                    let right = self.expression()?;
-                    result = Node::LogicalExpression(
-                        Box::new(result),
-                        LogicalOperator::Or,
-                        Box::new(right),
-                    );
-                }
-                _ => {}
-            }
+    fn logical_expression(
+        &mut self,
+        kind: TokenKind,
+        operator: LogicalOperator,
+        operand: fn(&mut Self) -> Result<Node, ParseError>,
+    ) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        let mut result = operand(self)?;
+
+        while self.get_current_token()?.kind() == kind {
+            self.eat(kind)?;
+            let right = operand(self)?;
+            let logical = NodeKind::LogicalExpression(Box::new(result), operator, Box::new(right));
+            result = self.node(start, logical);
         }
 
         Ok(result)
     }
 
-    fn condition_expression(&mut self) -> Result<Node, ParseError> {
-        let mut result = self.additive_expression()?;
+    // `not` binds looser than comparisons: `not a == b` is `not (a == b)`
+    fn not_expression(&mut self) -> Result<Node, ParseError> {
+        if self.get_current_token()?.kind() != TokenKind::NotKeyword {
+            return self.equality_expression();
+        }
 
-        while self.get_current_token()?.kind() == TokenKind::LessThan
-            || self.get_current_token()?.kind() == TokenKind::GreaterThan
-            || self.get_current_token()?.kind() == TokenKind::IsEquals
-            || self.get_current_token()?.kind() == TokenKind::NotEquals
-        {
-            let token = self.eat(self.get_current_token()?.kind())?;
+        let start = self.start()?;
+        self.eat(TokenKind::NotKeyword)?;
+        let operand = self.not_expression()?;
 
-            match token.kind() {
-                TokenKind::LessThan => {
-                    let right = self.additive_expression()?;
-                    result = Node::BinaryExpression(
-                        Box::new(result),
-                        BinaryOperator::LessThan,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::GreaterThan => {
-                    let right = self.additive_expression()?;
-                    result = Node::BinaryExpression(
-                        Box::new(result),
-                        BinaryOperator::GreaterThan,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::IsEquals => {
-                    let right = self.additive_expression()?;
-                    result = Node::BinaryExpression(
-                        Box::new(result),
-                        BinaryOperator::IsEquals,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::NotEquals => {
-                    let right = self.additive_expression()?;
-                    result = Node::BinaryExpression(
-                        Box::new(result),
-                        BinaryOperator::NotEquals,
-                        Box::new(right),
-                    );
-                }
-                _ => {}
-            }
+        Ok(self.node(
+            start,
+            NodeKind::UnaryExpression(Box::new(operand), UnaryOperator::Negation),
+        ))
+    }
+
+    fn equality_operator(kind: TokenKind) -> Option<BinaryOperator> {
+        match kind {
+            TokenKind::IsEquals => Some(BinaryOperator::IsEquals),
+            TokenKind::NotEquals => Some(BinaryOperator::NotEquals),
+            TokenKind::Is => Some(BinaryOperator::Is),
+            _ => None,
         }
+    }
 
-        Ok(result)
+    fn relational_operator(kind: TokenKind) -> Option<BinaryOperator> {
+        match kind {
+            TokenKind::LessThan => Some(BinaryOperator::LessThan),
+            TokenKind::GreaterThan => Some(BinaryOperator::GreaterThan),
+            TokenKind::LessThanOrEqual => Some(BinaryOperator::LessThanOrEqual),
+            TokenKind::GreaterThanOrEqual => Some(BinaryOperator::GreaterThanOrEqual),
+            _ => None,
+        }
     }
 
-    fn unary_expression(&mut self) -> Result<Node, ParseError> {
-        let token = self.get_current_token()?;
+    fn equality_expression(&mut self) -> Result<Node, ParseError> {
+        self.comparison_expression(Self::equality_operator, Self::relational_expression)
+    }
 
-        let operator = match token.kind() {
-            TokenKind::Increment => {
-                self.eat(TokenKind::Increment)?;
-                UnaryOperator::Increment
-            }
-            TokenKind::Decrement => {
-                self.eat(TokenKind::Decrement)?;
-                UnaryOperator::Decrement
-            }
-            TokenKind::Plus => {
-                self.eat(TokenKind::Plus)?;
-                UnaryOperator::Plus
-            }
-            TokenKind::Minus => {
-                self.eat(TokenKind::Minus)?;
-                UnaryOperator::Minus
-            }
-            TokenKind::Not => {
-                self.eat(TokenKind::Not)?;
-                UnaryOperator::Negation
+    fn relational_expression(&mut self) -> Result<Node, ParseError> {
+        self.comparison_expression(Self::relational_operator, Self::additive_expression)
+    }
+
+    // comparisons of one level chain like in python:
+    // `a < b < c` is `a < b and b < c` with `b` evaluated once
+    fn comparison_expression(
+        &mut self,
+        operators: fn(TokenKind) -> Option<BinaryOperator>,
+        operand: fn(&mut Self) -> Result<Node, ParseError>,
+    ) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        let first = operand(self)?;
+        let mut comparisons = Vec::new();
+
+        while let Some(mut operator) = operators(self.get_current_token()?.kind()) {
+            self.eat(self.get_current_token()?.kind())?;
+            if operator == BinaryOperator::Is
+                && self.get_current_token()?.kind() == TokenKind::NotKeyword
+            {
+                self.eat(TokenKind::NotKeyword)?;
+                operator = BinaryOperator::IsNot;
             }
-            kind => bail!(ParseError::UnexpectedToken(
-                kind,
-                token.line(),
-                token.column()
-            )),
-        };
+            comparisons.push((operator, Box::new(operand(self)?)));
+        }
 
-        let node = self.primary_expression()?;
+        if comparisons.len() > 1 {
+            let chain = NodeKind::ComparisonExpression(Box::new(first), comparisons);
+            return Ok(self.node(start, chain));
+        }
 
-        Ok(Node::UnaryExpression(Box::new(node), operator))
+        Ok(match comparisons.pop() {
+            Some((operator, right)) => self.node(
+                start,
+                NodeKind::BinaryExpression(Box::new(first), operator, right),
+            ),
+            None => first,
+        })
     }
 
-    fn identifier(&mut self) -> Result<Node, ParseError> {
-        let identifier = self.eat(TokenKind::Identifier)?;
-        let identifier_clone = dyn_clone::clone_box(&**identifier);
-        let token: Box<dyn Any> = identifier_clone.into_any();
+    fn additive_operator(kind: TokenKind) -> Option<BinaryOperator> {
+        match kind {
+            TokenKind::Plus => Some(BinaryOperator::Plus),
+            TokenKind::Minus => Some(BinaryOperator::Minus),
+            _ => None,
+        }
+    }
 
-        match token.downcast_ref::<Identifier>() {
-            Some(identifier) => return Ok(Node::Identifier(identifier.value())),
-            None => bail!(ParseError::UnexpectedToken(
-                identifier.kind(),
-                identifier.line(),
-                identifier.column()
-            )),
+    fn multiplicative_operator(kind: TokenKind) -> Option<BinaryOperator> {
+        match kind {
+            TokenKind::Multiply => Some(BinaryOperator::Multiply),
+            TokenKind::Divide => Some(BinaryOperator::Divide),
+            TokenKind::Modulo => Some(BinaryOperator::Modulo),
+            _ => None,
         }
     }
 
     fn additive_expression(&mut self) -> Result<Node, ParseError> {
-        let mut result = self.multiplicative_expression()?;
+        self.binary_expression(Self::additive_operator, Self::multiplicative_expression)
+    }
 
-        let mut token = self.get_current_token()?;
-        let mut token_kind = token.kind().clone();
+    fn multiplicative_expression(&mut self) -> Result<Node, ParseError> {
+        self.binary_expression(Self::multiplicative_operator, Self::unary_expression)
+    }
 
-        while token_kind == TokenKind::Plus || token_kind == TokenKind::Minus {
-            let line = token.line().clone();
-            let column = token.column().clone();
+    // one left associative level, `a - b - c` is `(a - b) - c`
+    fn binary_expression(
+        &mut self,
+        operators: fn(TokenKind) -> Option<BinaryOperator>,
+        operand: fn(&mut Self) -> Result<Node, ParseError>,
+    ) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        let mut left = operand(self)?;
+
+        while let Some(operator) = operators(self.get_current_token()?.kind()) {
+            self.eat(self.get_current_token()?.kind())?;
+            let right = operand(self)?;
+            let binary = NodeKind::BinaryExpression(Box::new(left), operator, Box::new(right));
+            left = self.node(start, binary);
+        }
 
-            match token_kind {
-                TokenKind::Plus => {
-                    self.eat(TokenKind::Plus)?;
-                    let right = self.multiplicative_expression()?;
+        Ok(left)
+    }
 
-                    result = Node::BinaryExpression(
-                        Box::new(result),
-                        BinaryOperator::Plus,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::Minus => {
-                    self.eat(TokenKind::Minus)?;
-                    let right = self.multiplicative_expression()?;
-
-                    result = Node::BinaryExpression(
-                        Box::new(result),
-                        BinaryOperator::Minus,
-                        Box::new(right),
-                    );
-                }
-                _ => bail!(ParseError::UnexpectedToken(token_kind, line, column)),
-            }
-            token = self.get_current_token()?;
-            token_kind = token.kind().clone();
+    fn unary_operator(kind: TokenKind) -> Option<UnaryOperator> {
+        match kind {
+            TokenKind::Increment => Some(UnaryOperator::Increment),
+            TokenKind::Decrement => Some(UnaryOperator::Decrement),
+            TokenKind::Plus => Some(UnaryOperator::Plus),
+            TokenKind::Minus => Some(UnaryOperator::Minus),
+            TokenKind::Not => Some(UnaryOperator::Negation),
+            _ => None,
         }
-
-        Ok(result)
     }
 
-    fn multiplicative_expression(&mut self) -> Result<Node, ParseError> {
-        let mut left = self.call_member_expression()?;
+    // prefix operators bind looser than member access and calls: `-a.b` is `-(a.b)`
+    fn unary_expression(&mut self) -> Result<Node, ParseError> {
+        let kind = self.get_current_token()?.kind();
+        let Some(operator) = Self::unary_operator(kind) else {
+            return self.postfix_expression();
+        };
 
-        let mut current_token = self.get_current_token()?;
-        let mut token_kind = current_token.kind().clone();
+        let start = self.start()?;
+        self.eat(kind)?;
+        let operand = self.unary_expression()?;
 
-        while token_kind == TokenKind::Multiply
-            || token_kind == TokenKind::Divide
-            || token_kind == TokenKind::Modulo
-        {
-            let line = current_token.line().clone();
-            let column = current_token.column().clone();
+        Ok(self.node(
+            start,
+            NodeKind::UnaryExpression(Box::new(operand), operator),
+        ))
+    }
 
-            match token_kind {
-                TokenKind::Multiply => {
-                    self.eat(TokenKind::Multiply)?;
-                    let right = self.primary_expression()?;
+    // `i++` and `i--` change `i` the same way `++i` and `--i` do
+    fn postfix_expression(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        let operand = self.call_member_expression()?;
+        let kind = self.get_current_token()?.kind();
+        let operator = match kind {
+            TokenKind::Increment => UnaryOperator::Increment,
+            TokenKind::Decrement => UnaryOperator::Decrement,
+            _ => return Ok(operand),
+        };
+        self.eat(kind)?;
+        Ok(self.node(
+            start,
+            NodeKind::UnaryExpression(Box::new(operand), operator),
+        ))
+    }
 
-                    left = Node::BinaryExpression(
-                        Box::new(left),
-                        BinaryOperator::Multiply,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::Divide => {
-                    self.eat(TokenKind::Divide)?;
-                    let right = self.primary_expression()?;
-
-                    left = Node::BinaryExpression(
-                        Box::new(left),
-                        BinaryOperator::Divide,
-                        Box::new(right),
-                    );
-                }
-                TokenKind::Modulo => {
-                    self.eat(TokenKind::Modulo)?;
-                    let right = self.primary_expression()?;
-
-                    left = Node::BinaryExpression(
-                        Box::new(left),
-                        BinaryOperator::Modulo,
-                        Box::new(right),
-                    );
-                }
-                _ => bail!(ParseError::UnexpectedToken(token_kind, line, column)),
-            }
+    #[allow(clippy::needless_return)]
+    fn identifier(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        let identifier = self.eat(TokenKind::Identifier)?;
+        let identifier_clone = dyn_clone::clone_box(&**identifier);
+        let token: Box<dyn Any> = identifier_clone.into_any();
 
-            current_token = self.get_current_token()?;
-            token_kind = current_token.kind().clone();
+        match token.downcast_ref::<Identifier>() {
+            Some(identifier) => {
+                return Ok(self.node(start, NodeKind::Identifier(identifier.value())))
+            }
+            None => bail!(ParseError::UnexpectedToken(
+                identifier.kind(),
+                vec![TokenKind::Identifier],
+                identifier.span()
+            )),
         }
+    }
 
-        Ok(left)
+    fn name(&mut self) -> Result<String, ParseError> {
+        match self.identifier()?.kind {
+            NodeKind::Identifier(name) => Ok(name),
+            _ => unreachable!("identifier() should only produce identifiers"),
+        }
     }
 
+    // any mix of `.name`, `[index]` and `(arguments)` after a primary, e.g. `robots[0].wheel(1).x`
     fn call_member_expression(&mut self) -> Result<Node, ParseError> {
-        let member = self.member_expression()?;
+        let start = self.start()?;
+        let mut result = match self.get_current_token()?.kind() {
+            TokenKind::New => self.new_expression()?,
+            _ => self.primary_expression()?,
+        };
 
-        if self.get_current_token()?.kind() == TokenKind::OpenParen {
-            return Ok(self.call_expression(member)?);
+        loop {
+            result = match self.get_current_token()?.kind() {
+                TokenKind::Point | TokenKind::OpenSquareBracket => {
+                    self.member_access(start, result)?
+                }
+                TokenKind::OpenParen => {
+                    let args = self.arguments()?.into_iter().map(Box::new).collect();
+                    self.node(start, NodeKind::CallExpression(Box::new(result), args))
+                }
+                _ => return Ok(result),
+            };
         }
-
-        Ok(member)
     }
 
-    fn call_expression(&mut self, callee: Node) -> Result<Node, ParseError> {
-        let args = self.arguments()?;
-        let mut result = Node::CallExpression(
-            Box::new(callee),
-            args.into_iter().map(|arg| Box::new(arg)).collect(),
-        );
-
-        if self.get_current_token()?.kind() == TokenKind::OpenParen {
-            result = self.call_expression(result)?;
+    // the class of `new` can't be a call, the first parentheses hold the arguments
+    fn new_expression(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        self.eat(TokenKind::New)?;
+
+        let class_start = self.start()?;
+        let mut class = self.primary_expression()?;
+        while matches!(
+            self.get_current_token()?.kind(),
+            TokenKind::Point | TokenKind::OpenSquareBracket
+        ) {
+            class = self.member_access(class_start, class)?;
         }
 
-        Ok(result)
+        let args = self.arguments()?;
+        Ok(self.node(
+            start,
+            NodeKind::NewExpression(Box::new(class), args.into_iter().map(Box::new).collect()),
+        ))
     }
 
     fn arguments(&mut self) -> Result<Vec<Node>, ParseError> {
         self.eat(TokenKind::OpenParen)?;
 
-        let args = if self.get_current_token()?.kind() != TokenKind::CloseParen {
+        let args = if !self.check(TokenKind::CloseParen)? {
             self.arguments_list()?
         } else {
             vec![]
@@ -626,51 +1114,117 @@ impl Parser {
     }
 
     fn arguments_list(&mut self) -> Result<Vec<Node>, ParseError> {
-        let mut args: Vec<Node> = vec![self.expression()?];
+        let mut args: Vec<Node> = vec![self.argument()?];
 
-        while self.not_eof() && self.get_current_token()?.kind() == TokenKind::Comma {
+        while self.not_eof() && self.check(TokenKind::Comma)? {
             self.eat(TokenKind::Comma)?;
-            args.push(self.expression()?);
+            let arg = self.argument()?;
+            let follows_named = args
+                .last()
+                .is_some_and(|last| matches!(last.kind, NodeKind::NamedArgument(..)));
+            if follows_named && !matches!(arg.kind, NodeKind::NamedArgument(..)) {
+                self.report(ParseError::PositionalAfterNamed(arg.span));
+            }
+            args.push(arg);
         }
 
         Ok(args)
     }
 
-    fn member_expression(&mut self) -> Result<Node, ParseError> {
-        let mut object = self.primary_expression()?;
+    // `name=value` passes the value to the parameter with that name
+    fn argument(&mut self) -> Result<Node, ParseError> {
+        let is_named = self.get_current_token()?.kind() == TokenKind::Identifier
+            && self
+                .peek()
+                .is_some_and(|token| token.kind() == TokenKind::Equals);
+        if !is_named {
+            return self.expression();
+        }
 
-        while self.get_current_token()?.kind() == TokenKind::Point
-            || self.get_current_token()?.kind() == TokenKind::OpenSquareBracket
-        {
-            let operator: &Box<dyn Token> = self.eat(self.get_current_token()?.kind())?;
+        let start = self.start()?;
+        let name = self.name()?;
+        self.eat(TokenKind::Equals)?;
+        let value = self.expression()?;
+        Ok(self.node(start, NodeKind::NamedArgument(name, Box::new(value))))
+    }
 
-            let computed = match operator.kind() {
-                TokenKind::Point => false,
-                TokenKind::OpenSquareBracket => true,
-                _ => false,
-            };
+    // `.name` or `[index]` after `object`, which starts at `start`
+    fn member_access(&mut self, start: Position, object: Node) -> Result<Node, ParseError> {
+        let computed =
+            self.eat(self.get_current_token()?.kind())?.kind() == TokenKind::OpenSquareBracket;
+
+        let property = match computed {
+            true => {
+                let node = self.expression()?;
+                self.eat(TokenKind::CloseSquareBracket)?;
+                node
+            }
+            false => self.identifier()?,
+        };
 
-            let property = match computed {
-                true => {
-                    let node = self.expression()?;
+        Ok(self.node(
+            start,
+            NodeKind::MemberExpression(Box::new(object), Box::new(property), computed),
+        ))
+    }
+
+    fn array_expression(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        self.eat(TokenKind::OpenSquareBracket)?;
+
+        let mut items = vec![];
+        while !self.check(TokenKind::CloseSquareBracket)? {
+            items.push(Box::new(self.expression()?));
+
+            // a trailing comma is fine
+            if !self.check(TokenKind::Comma)? {
+                break;
+            }
+            self.eat(TokenKind::Comma)?;
+        }
+
+        self.eat(TokenKind::CloseSquareBracket)?;
+        Ok(self.node(start, NodeKind::ArrayExpression(items)))
+    }
+
+    fn object_expression(&mut self) -> Result<Node, ParseError> {
+        let start = self.start()?;
+        self.eat(TokenKind::OpenCurlyBrace)?;
+
+        let mut properties = vec![];
+        while !self.check(TokenKind::CloseCurlyBrace)? {
+            let (key, computed) = match self.get_current_token()?.kind() {
+                TokenKind::OpenSquareBracket => {
+                    self.eat(TokenKind::OpenSquareBracket)?;
+                    let key = self.expression()?;
                     self.eat(TokenKind::CloseSquareBracket)?;
-                    node
+                    (key, true)
                 }
-                false => self.identifier()?,
+                TokenKind::StringLiteral => (self.primary_expression()?, false),
+                _ => (self.identifier()?, false),
             };
+            self.eat(TokenKind::Colon)?;
+            let value = self.expression()?;
+            properties.push((Box::new(key), Box::new(value), computed));
 
-            object = Node::MemberExpression(Box::new(object), Box::new(property), computed);
+            // a trailing comma is fine
+            if !self.check(TokenKind::Comma)? {
+                break;
+            }
+            self.eat(TokenKind::Comma)?;
         }
 
-        Ok(object)
+        self.eat(TokenKind::CloseCurlyBrace)?;
+        Ok(self.node(start, NodeKind::ObjectExpression(properties)))
     }
 
+    #[allow(clippy::clone_on_copy, clippy::needless_return)]
     fn primary_expression(&mut self) -> Result<Node, ParseError> {
         let current_token = self.get_current_token()?;
 
         let token_kind = current_token.kind().clone();
-        let line = current_token.line().clone();
-        let column = current_token.column().clone();
+        let span = current_token.span();
+        let start = span.start;
 
         let curr_token_clone = dyn_clone::clone_box(&**current_token);
         let token: Box<dyn Any> = curr_token_clone.into_any();
@@ -680,55 +1234,758 @@ impl Parser {
             TokenKind::Integer => {
                 if let Some(integer) = token.downcast_ref::<Integer>() {
                     self.eat(TokenKind::Integer)?;
-                    return Ok(Node::IntegerLiteral(integer.value()));
+                    return Ok(self.node(start, NodeKind::IntegerLiteral(integer.value())));
                 }
-                bail!(ParseError::UnexpectedToken(token_kind, line, column))
+                bail!(ParseError::UnexpectedToken(token_kind, vec![], span))
             }
             TokenKind::Decimal => {
                 if let Some(decimal) = token.downcast_ref::<Decimal>() {
                     self.eat(TokenKind::Decimal)?;
-                    return Ok(Node::DecimalLiteral(decimal.value()));
+                    return Ok(self.node(start, NodeKind::DecimalLiteral(decimal.value())));
                 }
-                bail!(ParseError::UnexpectedToken(token_kind, line, column))
+                bail!(ParseError::UnexpectedToken(token_kind, vec![], span))
             }
             TokenKind::Null => {
                 self.eat(TokenKind::Null)?;
-                return Ok(Node::NullLiteral());
+                return Ok(self.node(start, NodeKind::NullLiteral()));
             }
             TokenKind::StringLiteral => {
                 if let Some(string_literal) = token.downcast_ref::<StringLiteral>() {
                     self.eat(TokenKind::StringLiteral)?;
-                    return Ok(Node::StringLiteral(string_literal.value()));
+                    return Ok(self.node(start, NodeKind::StringLiteral(string_literal.value())));
                 }
-                bail!(ParseError::UnexpectedToken(token_kind, line, column))
-            }
-            TokenKind::OpenSquareBracket => {
-                self.eat(TokenKind::OpenSquareBracket)?;
-
-                let mut items: Vec<Box<Node>> = vec![Box::new(self.expression()?)];
-
-                while self.get_current_token()?.kind() == TokenKind::Comma {
-                    self.eat(TokenKind::Comma)?;
-                    let item = Box::new(self.expression()?);
-                    items.push(item);
-                }
-
-                self.eat(TokenKind::CloseSquareBracket)?;
-
-                Ok(Node::ArrayExpression(items))
+                bail!(ParseError::UnexpectedToken(token_kind, vec![], span))
             }
+            TokenKind::OpenSquareBracket => self.array_expression(),
+            TokenKind::OpenCurlyBrace => self.object_expression(),
+            TokenKind::Fn | TokenKind::Def => self.function_expression(),
             TokenKind::True | TokenKind::False => {
                 self.eat(token_kind)?;
 
-                Ok(Node::BoolLiteral(token_kind == TokenKind::True))
+                Ok(self.node(start, NodeKind::BoolLiteral(token_kind == TokenKind::True)))
             }
             TokenKind::OpenParen => {
                 self.eat(TokenKind::OpenParen)?; // eat open paren
                 let expr = self.expression()?;
                 self.eat(TokenKind::CloseParen)?; // eat close paren
-                return Ok(expr);
+                return Ok(self.node(start, expr.kind));
+            }
+            kind => bail!(ParseError::ExpectedExpression(kind, span)),
+        }
+    }
+}
+
+// tokens after which a statement can't end, so a new line after them doesn't end it
+fn continues_after(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Multiply
+            | TokenKind::Divide
+            | TokenKind::Modulo
+            | TokenKind::Not
+            | TokenKind::NotKeyword
+            | TokenKind::GreaterThan
+            | TokenKind::LessThan
+            | TokenKind::GreaterThanOrEqual
+            | TokenKind::LessThanOrEqual
+            | TokenKind::Equals
+            | TokenKind::Arrow
+            | TokenKind::IsEquals
+            | TokenKind::NotEquals
+            | TokenKind::Addition
+            | TokenKind::Subtraction
+            | TokenKind::Multiplication
+            | TokenKind::Division
+            | TokenKind::Modulation
+            | TokenKind::And
+            | TokenKind::Or
+            | TokenKind::Is
+            | TokenKind::In
+            | TokenKind::Comma
+            | TokenKind::Point
+            | TokenKind::Colon
+            | TokenKind::Semicolon
+            | TokenKind::OpenParen
+            | TokenKind::OpenSquareBracket
+            | TokenKind::OpenCurlyBrace
+    )
+}
+
+// a new line ends a statement, except inside `(...)` and `[...]`, after a token that
+// needs something to follow it and before a `.`, `,`, `else` or `}`
+fn significant_newlines(tokens: Vec<Box<dyn Token>>) -> Vec<Box<dyn Token>> {
+    let mut brackets: Vec<TokenKind> = vec![]; // brackets around the current token
+    let mut kept: Vec<Box<dyn Token>> = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token.kind() {
+            kind @ (TokenKind::OpenParen
+            | TokenKind::OpenSquareBracket
+            | TokenKind::OpenCurlyBrace) => brackets.push(kind),
+            TokenKind::CloseParen | TokenKind::CloseSquareBracket | TokenKind::CloseCurlyBrace => {
+                brackets.pop();
             }
-            _ => self.unary_expression(),
+            TokenKind::Newline => {
+                let inside = matches!(
+                    brackets.last(),
+                    Some(TokenKind::OpenParen | TokenKind::OpenSquareBracket)
+                );
+                let after = match kept.as_slice() {
+                    [] => true,
+                    // `import a.*` ends with the star
+                    [.., point, star]
+                        if point.kind() == TokenKind::Point
+                            && star.kind() == TokenKind::Multiply =>
+                    {
+                        false
+                    }
+                    [.., last] => continues_after(last.kind()),
+                };
+                // `,` can't start a statement, it continues an object literal
+                let before = tokens.peek().is_some_and(|next| {
+                    matches!(
+                        next.kind(),
+                        TokenKind::Point
+                            | TokenKind::Comma
+                            | TokenKind::Else
+                            | TokenKind::CloseCurlyBrace
+                    )
+                });
+                if inside || after || before {
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        kept.push(token);
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn tokens(source: &str) -> Vec<Box<dyn Token>> {
+        let mut lexer = Lexer::new(source.to_string());
+        if lexer.tokenize().is_err() {
+            panic!("Failed to tokenize source: {}", source);
+        }
+        lexer.tokens
+    }
+
+    fn parse(source: &str) -> Result<Node, ParseError> {
+        Parser::new(tokens(source)).produce_ast()
+    }
+
+    // line and column where `span` starts
+    fn at(span: &Span) -> (usize, usize) {
+        (span.start.line, span.start.column)
+    }
+
+    fn parse_import(source: &str) -> (String, ImportKind) {
+        let statements = match parse(source) {
+            Ok(Node {
+                kind: NodeKind::Program(statements),
+                ..
+            }) => statements,
+            Ok(node) => panic!("Expected a program, got {:?}", node),
+            Err(err) => panic!("Failed to parse {}: {}", source, err),
+        };
+        match statements.first().map(|statement| &statement.kind) {
+            Some(NodeKind::ImportStatement(path, kind)) => (format!("{:?}", path), kind.clone()),
+            statement => panic!("Expected an import, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_import_statements() {
+        let timer = "Identifier(\"timer\")".to_string();
+        let wheels = "Identifier(\"WheelsAPI\")".to_string();
+        let utils_math = "MemberExpression(Identifier(\"utils\"), Identifier(\"math\"), false)";
+
+        assert_eq!(
+            parse_import("import timer"),
+            (timer.clone(), ImportKind::Module(None))
+        );
+        assert_eq!(
+            parse_import("import timer as clock"),
+            (timer, ImportKind::Module(Some("clock".to_string())))
+        );
+        assert_eq!(
+            parse_import("import WheelsAPI.*"),
+            (wheels.clone(), ImportKind::Wildcard)
+        );
+        assert_eq!(
+            parse_import("import WheelsAPI.{Wheels, WheelDirection as Direction,}"),
+            (
+                wheels,
+                ImportKind::Names(vec![
+                    ("Wheels".to_string(), None),
+                    ("WheelDirection".to_string(), Some("Direction".to_string()))
+                ])
+            )
+        );
+        assert_eq!(
+            parse_import("import utils.math.{sqrt}"),
+            (
+                utils_math.to_string(),
+                ImportKind::Names(vec![("sqrt".to_string(), None)])
+            )
+        );
+        assert_eq!(
+            parse_import("import utils.math"),
+            (utils_math.to_string(), ImportKind::Module(None))
+        );
+    }
+
+    #[test]
+    fn test_invalid_imports() {
+        for source in [
+            "import",
+            "import *",
+            "import a.{}",
+            "import a.{b c}",
+            "import a.* as b",
+            "import a.\"b\"",
+            "import a as",
+        ] {
+            assert!(parse(source).is_err(), "{} should not parse", source);
+        }
+    }
+
+    // debug representation of the first statement of `source`
+    fn parse_statement(source: &str) -> String {
+        match parse(source) {
+            Ok(Node {
+                kind: NodeKind::Program(statements),
+                ..
+            }) => format!("{:?}", statements[0]),
+            Ok(node) => panic!("Expected a program, got {:?}", node),
+            Err(err) => panic!("Failed to parse {}: {}", source, err),
+        }
+    }
+
+    #[test]
+    fn test_new_expression() {
+        assert_eq!(
+            parse_statement("new Person(\"John\", 24)"),
+            "NewExpression(Identifier(\"Person\"), [StringLiteral(\"John\"), IntegerLiteral(24)])"
+        );
+        assert_eq!(
+            parse_statement("new shapes.Circle()"),
+            "NewExpression(MemberExpression(Identifier(\"shapes\"), Identifier(\"Circle\"), false), [])"
+        );
+        assert_eq!(
+            parse_statement("fn default() { return new Person() }"),
+            "FunctionDeclaration(Identifier(\"default\"), [], BlockStatement([ReturnStatement(Some(NewExpression(Identifier(\"Person\"), [])))]))"
+        );
+        assert!(parse("new Person").is_err());
+        assert!(parse("new").is_err());
+    }
+
+    #[test]
+    fn test_def_keyword() {
+        assert_eq!(
+            parse_statement("def mul(a, b) {\nx = a * b\nreturn x\n}"),
+            parse_statement("fn mul(a, b) {\nx = a * b\nreturn x\n}")
+        );
+        assert_eq!(
+            parse_statement("class A { def f() { }\nstatic def g() { } }"),
+            parse_statement("class A { fn f() { }\nstatic fn g() { } }")
+        );
+    }
+
+    #[test]
+    fn test_dialect() {
+        let source = "fn f() { }\ndef g() { }";
+        assert!(Parser::new(tokens(source)).produce_ast().is_ok());
+        assert!(matches!(
+            Parser::new(tokens(source))
+                .with_dialect(Dialect::Fn)
+                .produce_ast(),
+            Err(ParseError::UnexpectedToken(TokenKind::Def, _, span)) if span.start.line == 2
+        ));
+        assert!(matches!(
+            Parser::new(tokens("class A { fn f() { } }"))
+                .with_dialect(Dialect::Def)
+                .produce_ast(),
+            Err(ParseError::UnexpectedToken(TokenKind::Fn, _, span)) if span.start.line == 1
+        ));
+    }
+
+    #[test]
+    fn test_bare_return() {
+        assert_eq!(
+            parse_statement("fn f() { return }"),
+            "FunctionDeclaration(Identifier(\"f\"), [], BlockStatement([ReturnStatement(None)]))"
+        );
+        // the value has to be on the same line as `return`
+        assert_eq!(
+            parse_statement("fn f() {\n  return\n  stop()\n}"),
+            "FunctionDeclaration(Identifier(\"f\"), [], BlockStatement([ReturnStatement(None), \
+             CallExpression(Identifier(\"stop\"), [])]))"
+        );
+        assert_eq!(
+            parse_statement("fn f() { return; }"),
+            "FunctionDeclaration(Identifier(\"f\"), [], BlockStatement([ReturnStatement(None)]))"
+        );
+    }
+
+    #[test]
+    fn test_function_expression() {
+        assert_eq!(
+            parse_statement("sensor.on_detect(fn(obj) { print(obj) })"),
+            "CallExpression(MemberExpression(Identifier(\"sensor\"), Identifier(\"on_detect\"), false), \
+             [FunctionExpression([Parameter(\"obj\", None, false)], BlockStatement([CallExpression(Identifier(\"print\"), \
+             [Identifier(\"obj\")])]))])"
+        );
+        // the arrow returns the expression after it
+        assert_eq!(
+            parse_statement("let double = def(x) => x * 2"),
+            "VariableDeclaration(\"double\", Some(FunctionExpression([Parameter(\"x\", None, false)], \
+             BlockStatement([ReturnStatement(Some(BinaryExpression(Identifier(\"x\"), Multiply, IntegerLiteral(2))))]))), false)"
+        );
+        assert_eq!(
+            parse_statement("fn() {}()"),
+            "CallExpression(FunctionExpression([], BlockStatement([])), [])"
+        );
+        assert!(parse("fn(x) =>").is_err());
+        assert!(parse("while true { let f = fn() { break } }").is_err());
+        let tokens = tokens("let f = def() => 1");
+        assert!(Parser::new(tokens)
+            .with_dialect(Dialect::Fn)
+            .produce_ast()
+            .is_err());
+    }
+
+    #[test]
+    fn test_spans() {
+        let program = match parse("let x = (a + 10) * b\nrobot.move(x)") {
+            Ok(program) => program,
+            Err(err) => panic!("Failed to parse: {}", err),
+        };
+        let span = |node: &Node| {
+            let (start, end) = (node.span.start, node.span.end);
+            (start.offset, end.offset, start.line, start.column)
+        };
+        let NodeKind::Program(statements) = &program.kind else {
+            panic!("Expected a program, got {:?}", program)
+        };
+        assert_eq!(span(&program), (0, 34, 1, 0));
+        assert_eq!(span(&statements[0]), (0, 20, 1, 0));
+        assert_eq!(span(&statements[1]), (21, 34, 2, 0));
+
+        let NodeKind::VariableDeclaration(_, Some(value), _) = &statements[0].kind else {
+            panic!("Expected a declaration, got {:?}", statements[0])
+        };
+        let NodeKind::BinaryExpression(left, _, right) = &value.kind else {
+            panic!("Expected a binary expression, got {:?}", value)
+        };
+        // the parentheses belong to the expression they group
+        assert_eq!(span(value), (8, 20, 1, 8));
+        assert_eq!(span(left), (8, 16, 1, 8));
+        assert_eq!(span(right), (19, 20, 1, 19));
+
+        let NodeKind::CallExpression(callee, args) = &statements[1].kind else {
+            panic!("Expected a call, got {:?}", statements[1])
+        };
+        assert_eq!(span(callee), (21, 31, 2, 0));
+        assert_eq!(span(&args[0]), (32, 33, 2, 11));
+    }
+
+    #[test]
+    fn test_parameters() {
+        assert_eq!(
+            parse_statement("fn move(x, speed = 50, *rest,) {}"),
+            "FunctionDeclaration(Identifier(\"move\"), [Parameter(\"x\", None, false), \
+             Parameter(\"speed\", Some(IntegerLiteral(50)), false), Parameter(\"rest\", None, true)], \
+             BlockStatement([]))"
+        );
+        assert_eq!(
+            parse_statement("move(1, speed=30, x = 2)"),
+            "CallExpression(Identifier(\"move\"), [IntegerLiteral(1), \
+             NamedArgument(\"speed\", IntegerLiteral(30)), NamedArgument(\"x\", IntegerLiteral(2))])"
+        );
+        // `==` is still a comparison
+        assert_eq!(
+            parse_statement("f(a == 1)"),
+            "CallExpression(Identifier(\"f\"), [BinaryExpression(Identifier(\"a\"), IsEquals, IntegerLiteral(1))])"
+        );
+
+        assert!(matches!(
+            parse("fn f(1 + 2) {}"),
+            Err(ParseError::UnexpectedToken(TokenKind::Integer, expected, span))
+                if at(&span) == (1, 5)
+                    && expected == [TokenKind::CloseParen, TokenKind::Multiply, TokenKind::Identifier]
+        ));
+        assert!(matches!(
+            parse("fn f(a = 1, b) {}"),
+            Err(ParseError::MissingDefault(name, span)) if name == "b" && at(&span) == (1, 12)
+        ));
+        assert!(matches!(
+            parse("fn f(a, *a) {}"),
+            Err(ParseError::DuplicateParameter(name, span)) if name == "a" && at(&span) == (1, 9)
+        ));
+        assert!(matches!(
+            parse("f(a=1, 2)"),
+            Err(ParseError::PositionalAfterNamed(span)) if at(&span) == (1, 7)
+        ));
+        for source in [
+            "fn f(*a, b) {}",
+            "fn f(*a = []) {}",
+            "fn f(a.b) {}",
+            "fn(a + 1) => a",
+            "f(a=)",
+        ] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_object_expression() {
+        assert_eq!(
+            parse_statement("let my_obj = { \"name\": \"John\", age: 23, [key]: null, }"),
+            "VariableDeclaration(\"my_obj\", Some(ObjectExpression([\
+             (StringLiteral(\"name\"), StringLiteral(\"John\"), false), \
+             (Identifier(\"age\"), IntegerLiteral(23), false), \
+             (Identifier(\"key\"), NullLiteral, true)])), false)"
+        );
+        assert_eq!(
+            parse_statement("let o = { inner: { list: [1] } }"),
+            "VariableDeclaration(\"o\", Some(ObjectExpression([(Identifier(\"inner\"), \
+             ObjectExpression([(Identifier(\"list\"), ArrayExpression([IntegerLiteral(1)]), false)]), \
+             false)])), false)"
+        );
+        for source in ["{ a: 1 }", "{ \"a\": 1 }", "{ [a[0]]: 1 }", "{}"] {
+            assert!(
+                parse_statement(source).starts_with("ObjectExpression"),
+                "{} should be an object",
+                source
+            );
+        }
+        for source in ["{ a }", "{ let a = 1 }", "{ [a][0] }", "{ a = 1 }"] {
+            assert!(
+                parse_statement(source).starts_with("BlockStatement"),
+                "{} should be a block",
+                source
+            );
+        }
+        for source in [
+            "let o = { a 1 }",
+            "let o = { 1: 2 }",
+            "let o = { a: 1 b: 2 }",
+        ] {
+            assert!(parse(source).is_err(), "{} should not parse", source);
+        }
+    }
+
+    #[test]
+    fn test_while_statement() {
+        assert_eq!(
+            parse_statement("while x < 3 { x += 1\nif x == 2 { continue }\nbreak }"),
+            "WhileStatement(BinaryExpression(Identifier(\"x\"), LessThan, IntegerLiteral(3)), \
+             BlockStatement([AssignmentExpression(Identifier(\"x\"), Addition, IntegerLiteral(1)), \
+             IfStatement(BinaryExpression(Identifier(\"x\"), IsEquals, IntegerLiteral(2)), \
+             BlockStatement([ContinueStatement]), None), BreakStatement]))"
+        );
+        assert!(parse("for i in xs { while true { break } }").is_ok());
+    }
+
+    #[test]
+    fn test_not_and_is() {
+        assert_eq!(
+            parse_statement("not a == b"),
+            "UnaryExpression(BinaryExpression(Identifier(\"a\"), IsEquals, Identifier(\"b\")), Negation)"
+        );
+        assert_eq!(
+            parse_statement("!a == b"),
+            "BinaryExpression(UnaryExpression(Identifier(\"a\"), Negation), IsEquals, Identifier(\"b\"))"
+        );
+        assert_eq!(
+            parse_statement("not a and not not b"),
+            "LogicalExpression(UnaryExpression(Identifier(\"a\"), Negation), And, \
+             UnaryExpression(UnaryExpression(Identifier(\"b\"), Negation), Negation))"
+        );
+        assert_eq!(
+            parse_statement("x is not null or x is y"),
+            "LogicalExpression(BinaryExpression(Identifier(\"x\"), IsNot, NullLiteral), Or, \
+             BinaryExpression(Identifier(\"x\"), Is, Identifier(\"y\")))"
+        );
+        assert!(parse("x is").is_err());
+        assert!(parse("x == not y").is_err());
+    }
+
+    #[test]
+    fn test_comparison_chain() {
+        assert_eq!(
+            parse_statement("x >= 1"),
+            "BinaryExpression(Identifier(\"x\"), GreaterThanOrEqual, IntegerLiteral(1))"
+        );
+        // equality binds looser than the relational chain before it
+        assert_eq!(
+            parse_statement("0 <= x < 10 == y"),
+            "BinaryExpression(ComparisonExpression(IntegerLiteral(0), [(LessThanOrEqual, Identifier(\"x\")), \
+             (LessThan, IntegerLiteral(10))]), IsEquals, Identifier(\"y\"))"
+        );
+        assert_eq!(
+            parse_statement("a == b != c"),
+            "ComparisonExpression(Identifier(\"a\"), [(IsEquals, Identifier(\"b\")), \
+             (NotEquals, Identifier(\"c\"))])"
+        );
+        // operands still bind tighter than the comparison
+        assert_eq!(
+            parse_statement("a < b + 1 <= c"),
+            "ComparisonExpression(Identifier(\"a\"), [(LessThan, BinaryExpression(Identifier(\"b\"), \
+             Plus, IntegerLiteral(1))), (LessThanOrEqual, Identifier(\"c\"))])"
+        );
+    }
+
+    // renders an expression with every level of nesting in parentheses
+    fn grouped(node: &Node) -> String {
+        let list = |nodes: &[Box<Node>]| {
+            nodes
+                .iter()
+                .map(|node| grouped(node))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &node.kind {
+            NodeKind::Identifier(name) => name.clone(),
+            NodeKind::IntegerLiteral(value) => value.to_string(),
+            NodeKind::NullLiteral() => "null".to_string(),
+            NodeKind::BinaryExpression(left, operator, right) => {
+                format!("({} {:?} {})", grouped(left), operator, grouped(right))
+            }
+            NodeKind::ComparisonExpression(first, comparisons) => {
+                let mut result = format!("({}", grouped(first));
+                for (operator, right) in comparisons {
+                    result += &format!(" {:?} {}", operator, grouped(right));
+                }
+                result + ")"
+            }
+            NodeKind::LogicalExpression(left, operator, right) => {
+                format!("({} {:?} {})", grouped(left), operator, grouped(right))
+            }
+            NodeKind::AssignmentExpression(left, operator, right) => {
+                format!("({} {:?} {})", grouped(left), operator, grouped(right))
+            }
+            NodeKind::UnaryExpression(target, operator) => {
+                format!("({:?} {})", operator, grouped(target))
+            }
+            NodeKind::MemberExpression(object, property, false) => {
+                format!("{}.{}", grouped(object), grouped(property))
+            }
+            NodeKind::MemberExpression(object, property, true) => {
+                format!("{}[{}]", grouped(object), grouped(property))
+            }
+            NodeKind::CallExpression(callee, args) => {
+                format!("{}({})", grouped(callee), list(args))
+            }
+            NodeKind::NewExpression(class, args) => {
+                format!("new {}({})", grouped(class), list(args))
+            }
+            NodeKind::ArrayExpression(items) => format!("[{}]", list(items)),
+            node => panic!("Unexpected node in expression: {:?}", node),
+        }
+    }
+
+    #[test]
+    fn test_newlines() {
+        let cases: [(&str, &[&str]); 7] = [
+            ("a = b\n(c)", &["(a Equals b)", "c"]),
+            ("a = b +\nc", &["(a Equals (b Plus c))"]),
+            ("f(a,\nb)\n[a,\nb]", &["f(a, b)", "[a, b]"]),
+            ("a\n.b\n.c()", &["a.b.c()"]),
+            ("a; b;; c;", &["a", "b", "c"]),
+            ("\n\na\n\n", &["a"]),
+            ("i++\ni--", &["(Increment i)", "(Decrement i)"]),
+        ];
+        for (source, expected) in cases {
+            let statements = match parse(source) {
+                Ok(Node {
+                    kind: NodeKind::Program(statements),
+                    ..
+                }) => statements,
+                result => panic!("Failed to parse {:?}: {:?}", source, result),
+            };
+            let statements: Vec<String> = statements.iter().map(|node| grouped(node)).collect();
+            assert_eq!(statements, expected, "source: {:?}", source);
+        }
+
+        for source in [
+            "if a {\n  b\n}\nelse {\n  c\n}",
+            "let f = fn(x) {\n  let y = x\n  y\n}\nf(1)",
+            "let o = {\n  \"a\": 1,\n  \"b\": [\n    2\n  ]\n}",
+            "let o = { a: 1\n, b: 2\n}",
+            "class A {\n  x = 1\n  fn f() { return x }\n}",
+            "import a.*\nlet b = 1",
+        ] {
+            assert!(parse(source).is_ok(), "source: {:?}", source);
+        }
+
+        // two statements on a line need a `;` between them
+        assert!(matches!(
+            parse("a b"),
+            Err(ParseError::UnexpectedToken(TokenKind::Identifier, expected, _))
+                if expected.contains(&TokenKind::Newline) && expected.contains(&TokenKind::Semicolon)
+        ));
+        assert!(matches!(
+            parse("if a { b c }"),
+            Err(ParseError::UnexpectedToken(TokenKind::Identifier, expected, _))
+                if expected.contains(&TokenKind::CloseCurlyBrace)
+        ));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let cases = [
+            ("a = b = c", "(a Equals (b Equals c))"),
+            ("a += b or c", "(a Addition (b Or c))"),
+            ("a or b and c", "(a Or (b And c))"),
+            ("a and b or c", "((a And b) Or c)"),
+            ("a or b or c", "((a Or b) Or c)"),
+            ("not a and b", "((Negation a) And b)"),
+            ("not a == b", "(Negation (a IsEquals b))"),
+            ("a == b < c", "(a IsEquals (b LessThan c))"),
+            ("a < b == c < d", "((a LessThan b) IsEquals (c LessThan d))"),
+            ("a is not b == c", "(a IsNot b IsEquals c)"),
+            ("a < b + c", "(a LessThan (b Plus c))"),
+            ("a - b - c", "((a Minus b) Minus c)"),
+            ("a + b * c", "(a Plus (b Multiply c))"),
+            ("a * b + c", "((a Multiply b) Plus c)"),
+            ("a / b / c", "((a Divide b) Divide c)"),
+            ("a % b * c", "((a Modulo b) Multiply c)"),
+            ("a * b.c", "(a Multiply b.c)"),
+            ("a * f(x)", "(a Multiply f(x))"),
+            ("a * -b", "(a Multiply (Minus b))"),
+            ("-a * b", "((Minus a) Multiply b)"),
+            ("-foo.bar", "(Minus foo.bar)"),
+            ("!f(x)[0]", "(Negation f(x)[0])"),
+            ("- -a", "(Minus (Minus a))"),
+            ("!a == b", "((Negation a) IsEquals b)"),
+            ("++a.b", "(Increment a.b)"),
+            ("(a + b) * c", "((a Plus b) Multiply c)"),
+            ("Wheel(1).x", "Wheel(1).x"),
+            ("f(a)(b).c[d](e)", "f(a)(b).c[d](e)"),
+            ("new shapes.Circle(1).r", "new shapes.Circle(1).r"),
+            ("(a).b", "a.b"),
+            ("[]", "[]"),
+            ("[a, b + c,]", "[a, (b Plus c)]"),
+            ("[][0]", "[][0]"),
+        ];
+
+        for (source, expected) in cases {
+            let statements = match parse(source) {
+                Ok(Node {
+                    kind: NodeKind::Program(statements),
+                    ..
+                }) => statements,
+                result => panic!("Failed to parse {:?}: {:?}", source, result),
+            };
+            assert_eq!(grouped(&statements[0]), expected, "source: {}", source);
+        }
+
+        // only variables and members can be assigned to: source, columns of the target
+        let invalid = [
+            ("a and b = c", (0, 7)),
+            ("a + b = c", (0, 5)),
+            ("f() = 1", (0, 3)),
+            ("x.y and z += 1", (0, 9)),
+        ];
+        for (source, columns) in invalid {
+            match parse(source) {
+                Err(ParseError::InvalidAssignmentTarget(span)) => assert_eq!(
+                    (span.start.column, span.end.column),
+                    columns,
+                    "source: {}",
+                    source
+                ),
+                result => panic!("{:?} should not parse: {:?}", source, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_incomplete_expressions() {
+        for source in [
+            "a +", "a * * b", "-", "not", "a and", "f(a,", "[a b]", "a.", "(a",
+        ] {
+            assert!(parse(source).is_err(), "source: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_loop_control_outside_loop() {
+        assert!(matches!(
+            parse("break"),
+            Err(ParseError::OutsideOfLoop(TokenKind::Break, span)) if span.start.line == 1
+        ));
+        assert!(matches!(
+            parse("if true {\ncontinue\n}"),
+            Err(ParseError::OutsideOfLoop(TokenKind::Continue, span)) if span.start.line == 2
+        ));
+        // a function body doesn't belong to the loop around its declaration
+        assert!(matches!(
+            parse("while true { fn f() { break } }"),
+            Err(ParseError::OutsideOfLoop(TokenKind::Break, span)) if span.start.line == 1
+        ));
+        assert!(matches!(
+            parse("for i in xs { class A { fn f() { continue } } }"),
+            Err(ParseError::OutsideOfLoop(TokenKind::Continue, span)) if span.start.line == 1
+        ));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let source =
+            "let a = 1\nlet b = = 2\nmove(a\nfn f(x, x) {\n  let c = )\n  turn(c)\n}\nlet d = 4";
+        let (program, errors) = Parser::new(tokens(source)).produce_partial_ast();
+        let lines: Vec<usize> = errors
+            .iter()
+            .map(|err| err.span().unwrap().start.line)
+            .collect();
+        assert_eq!(lines, vec![2, 4, 4, 5], "{:?}", errors);
+        assert!(
+            matches!(&errors[1], ParseError::UnexpectedToken(TokenKind::Fn, expected, _)
+            if expected.contains(&TokenKind::CloseParen))
+        );
+        assert!(matches!(&errors[2], ParseError::DuplicateParameter(name, _) if name == "x"));
+
+        // the statements around the errors are still there
+        let NodeKind::Program(statements) = &program.kind else {
+            panic!("Expected a program, got {:?}", program)
+        };
+        let kinds: Vec<String> = statements
+            .iter()
+            .map(|statement| {
+                format!("{:?}", statement)
+                    .split('(')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "VariableDeclaration",
+                "FunctionDeclaration",
+                "VariableDeclaration"
+            ]
+        );
+        let NodeKind::FunctionDeclaration(_, _, body) = &statements[1].kind else {
+            panic!("Expected a function, got {:?}", statements[1])
+        };
+        assert!(matches!(&body.kind, NodeKind::BlockStatement(body) if body.len() == 1));
+
+        // a stray `}` and an unfinished block don't stop the parser
+        for source in ["}\nlet a = 1", "let a = 1\n}", "fn f() {\nlet a ="] {
+            let (_, errors) = Parser::new(tokens(source)).produce_partial_ast();
+            assert_eq!(errors.len(), 1, "source: {}, errors: {:?}", source, errors);
         }
     }
 }
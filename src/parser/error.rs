@@ -1,34 +1,102 @@
-use crate::lexer::token::TokenKind;
+use crate::lexer::{token::TokenKind, Span};
 
 #[derive(Debug)]
 pub enum ParseError {
-    UnexpectedToken(TokenKind, usize, usize), // token_kind, line column
-    ConstantNotInitialized(String, usize, usize), // variable_name, line, column
+    UnexpectedToken(TokenKind, Vec<TokenKind>, Span), // token_kind, expected instead, span
+    ExpectedExpression(TokenKind, Span),              // token_kind, span
+    ConstantNotInitialized(String, Span),             // variable_name, span
+    OutsideOfLoop(TokenKind, Span),                   // `break` or `continue`, span
+    MissingDefault(String, Span),                     // parameter_name, span
+    DuplicateParameter(String, Span),                 // parameter_name, span
+    PositionalAfterNamed(Span),                       // span of the argument
+    InvalidAssignmentTarget(Span),                    // span of the target
     UnexpectedEOF,
 }
 
+impl ParseError {
+    /// Where the error is in the source, the end of the file has no place of its own.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::UnexpectedToken(_, _, span)
+            | ParseError::ExpectedExpression(_, span)
+            | ParseError::ConstantNotInitialized(_, span)
+            | ParseError::OutsideOfLoop(_, span)
+            | ParseError::MissingDefault(_, span)
+            | ParseError::DuplicateParameter(_, span)
+            | ParseError::PositionalAfterNamed(span)
+            | ParseError::InvalidAssignmentTarget(span) => Some(*span),
+            ParseError::UnexpectedEOF => None,
+        }
+    }
+}
+
+/// `a`, `a or b`, `one of a, b or c`
+pub fn one_of<T: std::fmt::Display>(items: &[T]) -> String {
+    let names: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+    match names.as_slice() {
+        [] => String::new(),
+        [name] => name.clone(),
+        [names @ .., last] if names.len() == 1 => format!("{} or {}", names[0], last),
+        [names @ .., last] => format!("one of {} or {}", names.join(", "), last),
+    }
+}
+
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseError::UnexpectedToken(kind, line, column) => {
-                // println!("{:?} {}:{}", kind, line, column);
-                // TODO: add support for showing token value
+            ParseError::UnexpectedToken(kind, expected, span) => {
+                write!(f, "Unexpected token: '{:?}' at position {}", kind, span)?;
+                if !expected.is_empty() {
+                    write!(f, ", expected {}", one_of(expected))?;
+                }
+                Ok(())
+            }
+            ParseError::ExpectedExpression(kind, span) => {
+                write!(
+                    f,
+                    "Expected an expression but found '{:?}' at position {}",
+                    kind, span
+                )
+            }
+            ParseError::OutsideOfLoop(kind, span) => {
+                write!(
+                    f,
+                    "'{:?}' is only allowed inside of a loop at {}",
+                    kind, span
+                )
+            }
+            ParseError::MissingDefault(name, span) => {
+                write!(
+                    f,
+                    "Parameter '{}' follows a parameter with a default value, so it needs one too at {}",
+                    name, span
+                )
+            }
+            ParseError::DuplicateParameter(name, span) => {
                 write!(
                     f,
-                    "Unexpected token: '{:?}' at position {}:{}",
-                    kind, line, column
+                    "Parameter '{}' is declared more than once at {}",
+                    name, span
                 )
             }
+            ParseError::PositionalAfterNamed(span) => {
+                write!(f, "Positional argument after a named one at {}", span)
+            }
+            ParseError::InvalidAssignmentTarget(span) => {
+                write!(f, "Invalid assignment target at {}", span)
+            }
             ParseError::UnexpectedEOF => {
                 write!(f, "Unexpected end of file")
             }
-            ParseError::ConstantNotInitialized(variable_name, line, column) => {
+            ParseError::ConstantNotInitialized(variable_name, span) => {
                 write!(
                     f,
-                    "The constant {} must be initialized at {}:{}",
-                    variable_name, line, column
+                    "The constant {} must be initialized at {}",
+                    variable_name, span
                 )
             }
         }
     }
 }
+
+impl std::error::Error for ParseError {}
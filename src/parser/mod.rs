@@ -2,4 +2,8 @@ mod ast;
 mod error;
 mod nodes;
 
-pub use ast::Parser;
+pub use ast::{Dialect, Parser};
+pub use error::{one_of, ParseError};
+pub use nodes::{
+    AssignmentOperator, BinaryOperator, ImportKind, LogicalOperator, Node, NodeKind, UnaryOperator,
+};
@@ -1,4 +1,8 @@
-#[derive(Debug)]
+use std::fmt;
+
+use crate::lexer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BinaryOperator {
     Plus,
     Minus,
@@ -7,17 +11,21 @@ pub enum BinaryOperator {
     Modulo,
     LessThan,
     GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
     IsEquals,
     NotEquals,
+    Is,
+    IsNot,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LogicalOperator {
     And,
     Or,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnaryOperator {
     Increment,
     Decrement,
@@ -26,7 +34,7 @@ pub enum UnaryOperator {
     Negation,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AssignmentOperator {
     Equals,
     Addition,
@@ -36,20 +44,51 @@ pub enum AssignmentOperator {
     Modulation,
 }
 
-#[derive(Debug)]
-pub enum Node {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportKind {
+    Module(Option<String>),               // alias
+    Wildcard,                             // every export of the module
+    Names(Vec<(String, Option<String>)>), // name, alias
+}
+
+/// A node of the syntax tree together with the part of the source it was parsed from.
+#[derive(Clone)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+// the tree is printed without its spans, they'd bury the structure
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NodeKind {
     // statements
-    Program(Vec<Box<Node>>),                                        // body[]
-    VariableDeclaration(String, Option<Box<Node>>, bool),           // var_name, value, is_constant
-    BlockStatement(Vec<Box<Node>>),                                 // body[]
+    Program(Vec<Box<Node>>),                                   // body[]
+    VariableDeclaration(String, Option<Box<Node>>, bool),      // var_name, value, is_constant
+    BlockStatement(Vec<Box<Node>>),                            // body[]
     FunctionDeclaration(Box<Node>, Vec<Box<Node>>, Box<Node>), // identifier, params, block_statement(body)
     IfStatement(Box<Node>, Box<Node>, Option<Box<Node>>), // condition, body (consequent), alternate
     ForInStatement(Box<Node>, Box<Node>, Box<Node>),      // left, right, body[] (block_statement)
-    ReturnStatement(Box<Node>),                           // value
-    ImportStatement(Box<Node>),                           // import entity
+    WhileStatement(Box<Node>, Box<Node>),                 // condition, body (block_statement)
+    BreakStatement(),
+    ContinueStatement(),
+    ReturnStatement(Option<Box<Node>>), // value, none for a bare `return`
+    ImportStatement(Box<Node>, ImportKind), // module path, imported names
     ClassDeclaration(Box<Node>, Option<Box<Node>>, Vec<Box<Node>>), // id, super_class, body
-    PropertyDefinition(Box<Node>, Box<Node>, bool),       // id, value, is_static
+    PropertyDefinition(Box<Node>, Box<Node>, bool), // id, value, is_static
     MethodDefinition(Box<Node>, Vec<Box<Node>>, Box<Node>, bool), // key, params, body, is_static
+    Parameter(String, Option<Box<Node>>, bool), // name, default value, is_rest (`*name`)
 
     // literals
     IntegerLiteral(usize), // value
@@ -61,10 +100,15 @@ pub enum Node {
 
     // expressions
     BinaryExpression(Box<Node>, BinaryOperator, Box<Node>), // left, operator, right
+    ComparisonExpression(Box<Node>, Vec<(BinaryOperator, Box<Node>)>), // first, chained comparisons
     ArrayExpression(Vec<Box<Node>>),                        // array_items
+    ObjectExpression(Vec<(Box<Node>, Box<Node>, bool)>),    // properties: key, value, computed
     LogicalExpression(Box<Node>, LogicalOperator, Box<Node>), // left, operator, right
     UnaryExpression(Box<Node>, UnaryOperator),              // unary target node, unary operator
     MemberExpression(Box<Node>, Box<Node>, bool),           // object, property, computed
     CallExpression(Box<Node>, Vec<Box<Node>>),              // callee, arguments
+    NamedArgument(String, Box<Node>),                       // name, value (`name=value` in a call)
+    FunctionExpression(Vec<Box<Node>>, Box<Node>),          // params, body (block_statement)
+    NewExpression(Box<Node>, Vec<Box<Node>>),               // class, arguments
     AssignmentExpression(Box<Node>, AssignmentOperator, Box<Node>), // assigne, operator, value
 }
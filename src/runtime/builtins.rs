@@ -0,0 +1,115 @@
+use crate::macros::bail;
+
+use super::{
+    convert::FromArgs,
+    environment::Environment,
+    error::RuntimeError,
+    module::{Module, ModuleResolver},
+    value::Value,
+};
+
+/// Functions available in the global scope of every script.
+pub fn globals() -> Vec<(&'static str, Value)> {
+    vec![
+        ("print", Value::native("print", None, print)),
+        ("range", Value::native("range", None, range)),
+        ("len", Value::native("len", Some(1), len)),
+    ]
+}
+
+/// Scope holding the builtins, global scopes of scripts and modules are created on top of it.
+pub fn scope() -> Environment {
+    let mut scope = Environment::new();
+    for (name, value) in globals() {
+        scope
+            .declare(name, value, true)
+            .expect("builtins should be declared into an empty scope");
+    }
+    scope
+}
+
+pub fn resolver() -> ModuleResolver {
+    let mut resolver = ModuleResolver::new();
+    for module in modules() {
+        resolver.register(module);
+    }
+    resolver
+}
+
+/// Native modules every script can import.
+pub fn modules() -> Vec<Module> {
+    vec![Module::new(
+        "math",
+        vec![
+            ("PI".to_string(), Value::Decimal(std::f64::consts::PI)),
+            ("sqrt".to_string(), Value::native("sqrt", Some(1), sqrt)),
+            ("floor".to_string(), Value::native("floor", Some(1), floor)),
+            ("abs".to_string(), Value::native("abs", Some(1), abs)),
+        ],
+    )]
+}
+
+fn print(args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let line: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    println!("{}", line.join(" "));
+    Ok(Value::Null)
+}
+
+// range(end) or range(start, end)
+fn range(args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (start, end) = match args.as_slice() {
+        [Value::Integer(end)] => (0, *end),
+        [Value::Integer(start), Value::Integer(end)] => (*start, *end),
+        [_] | [_, _] => bail!(RuntimeError::InvalidOperand(
+            "range".to_string(),
+            args.iter()
+                .map(|arg| arg.type_name())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )),
+        _ => bail!(RuntimeError::ArityMismatch(
+            "range".to_string(),
+            2,
+            args.len()
+        )),
+    };
+
+    Ok(Value::list((start..end).map(Value::Integer).collect()))
+}
+
+fn len(args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let length = match &args[0] {
+        Value::String(value) => value.chars().count(),
+        Value::List(items) => items.borrow().len(),
+        Value::Object(object) => object.borrow().entries().len(),
+        value => bail!(RuntimeError::InvalidOperand(
+            "len".to_string(),
+            value.type_name()
+        )),
+    };
+
+    Ok(Value::Integer(length as i64))
+}
+
+fn sqrt(args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (value,) = <(f64,)>::from_args("sqrt", args)?;
+    Ok(Value::Decimal(value.sqrt()))
+}
+
+fn floor(args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (value,) = <(f64,)>::from_args("floor", args)?;
+    Ok(Value::Integer(value.floor() as i64))
+}
+
+fn abs(args: Vec<Value>) -> Result<Value, RuntimeError> {
+    match args[0] {
+        Value::Integer(value) => match value.checked_abs() {
+            Some(value) => Ok(Value::Integer(value)),
+            None => bail!(RuntimeError::IntegerOverflow),
+        },
+        _ => {
+            let (value,) = <(f64,)>::from_args("abs", args)?;
+            Ok(Value::Decimal(value.abs()))
+        }
+    }
+}
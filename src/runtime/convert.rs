@@ -0,0 +1,257 @@
+use crate::macros::bail;
+
+use super::{error::RuntimeError, value::Value};
+
+/// Conversion of a script value into a Rust value, used for arguments of host functions.
+pub trait FromValue: Sized {
+    /// Name of the expected script type, shown when the conversion fails.
+    fn expected_type() -> String;
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+/// Conversion of a Rust value into a script value, used for results of host functions.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// Conversion of a whole argument list, implemented for tuples of `FromValue` types.
+pub trait FromArgs: Sized {
+    fn arity() -> usize;
+    fn from_args(function_name: &str, args: Vec<Value>) -> Result<Self, RuntimeError>;
+}
+
+/// Converts `value`, failing with `RuntimeError::WrongType` for `target` if it has the wrong type.
+pub fn convert<T: FromValue>(target: &str, value: &Value) -> Result<T, RuntimeError> {
+    match T::from_value(value) {
+        Some(value) => Ok(value),
+        None => bail!(RuntimeError::WrongType(
+            target.to_string(),
+            T::expected_type(),
+            value.type_name()
+        )),
+    }
+}
+
+impl FromValue for Value {
+    fn expected_type() -> String {
+        "any".to_string()
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn expected_type() -> String {
+        "int".to_string()
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn expected_type() -> String {
+        "float".to_string()
+    }
+
+    // integers are promoted the same way arithmetic promotes them
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(*value as f64),
+            Value::Decimal(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn expected_type() -> String {
+        "bool".to_string()
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    fn expected_type() -> String {
+        "string".to_string()
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn expected_type() -> String {
+        format!("{} or null", T::expected_type())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn expected_type() -> String {
+        format!("list of {}", T::expected_type())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(items) => items.borrow().iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Decimal(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => value.into_value(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::list(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+macro_rules! impl_from_args {
+    ($count:expr $(, $arg:ident)*) => {
+        impl<$($arg: FromValue),*> FromArgs for ($($arg,)*) {
+            fn arity() -> usize {
+                $count
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn from_args(function_name: &str, args: Vec<Value>) -> Result<Self, RuntimeError> {
+                if args.len() != $count {
+                    bail!(RuntimeError::ArityMismatch(
+                        function_name.to_string(),
+                        $count,
+                        args.len()
+                    ))
+                }
+                let mut args = args.iter().enumerate();
+                Ok(($({
+                    let (i, arg) = args.next().expect("argument count was checked");
+                    convert::<$arg>(
+                        &format!("Argument {} of '{}'", i + 1, function_name),
+                        arg,
+                    )?
+                },)*))
+            }
+        }
+    };
+}
+
+impl_from_args!(0);
+impl_from_args!(1, A);
+impl_from_args!(2, A, B);
+impl_from_args!(3, A, B, C);
+impl_from_args!(4, A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_args() {
+        let args = vec![Value::Integer(3), Value::String("left".to_string())];
+        let (speed, side) = <(f64, String)>::from_args("move", args).unwrap();
+        assert_eq!(speed, 3.0);
+        assert_eq!(side, "left");
+
+        let args = vec![Value::String("fast".to_string())];
+        match <(i64,)>::from_args("move", args) {
+            Err(RuntimeError::WrongType(target, expected, got)) => {
+                assert_eq!(target, "Argument 1 of 'move'");
+                assert_eq!(expected, "int");
+                assert_eq!(got, "string");
+            }
+            _ => panic!("expected a type error"),
+        }
+
+        assert!(matches!(
+            <(i64, i64)>::from_args("move", vec![Value::Integer(1)]),
+            Err(RuntimeError::ArityMismatch(_, 2, 1))
+        ));
+        assert_eq!(<()>::arity(), 0);
+    }
+
+    #[test]
+    fn test_containers() {
+        let list = Value::list(vec![Value::Integer(1), Value::Decimal(2.5)]);
+        assert_eq!(Vec::<f64>::from_value(&list), Some(vec![1.0, 2.5]));
+        assert_eq!(Vec::<i64>::from_value(&list), None);
+        assert_eq!(Option::<i64>::from_value(&Value::Null), Some(None));
+        assert_eq!(
+            vec![1i64, 2].into_value(),
+            Value::list(vec![Value::Integer(1), Value::Integer(2)])
+        );
+        assert_eq!(None::<i64>.into_value(), Value::Null);
+    }
+}
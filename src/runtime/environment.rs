@@ -0,0 +1,105 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::macros::bail;
+
+use super::{
+    error::RuntimeError,
+    host::{HostClass, NativeClass},
+    value::Value,
+};
+
+#[derive(Default)]
+pub struct Environment {
+    parent: Option<Rc<RefCell<Environment>>>,
+    variables: HashMap<String, Value>,
+    constants: HashSet<String>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_parent(parent: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Default::default()
+        }
+    }
+
+    pub fn declare(
+        &mut self,
+        name: &str,
+        value: Value,
+        is_constant: bool,
+    ) -> Result<(), RuntimeError> {
+        // variables can be re-declared in the same scope, constants can't
+        if self.constants.contains(name) {
+            bail!(RuntimeError::ConstantReassignment(name.to_string()))
+        }
+        if is_constant {
+            self.constants.insert(name.to_string());
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &str) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.variables.get(name) {
+            return Ok(value.clone());
+        }
+        match self.parent {
+            Some(ref parent) => parent.borrow().lookup(name),
+            None => bail!(RuntimeError::UndefinedVariable(name.to_string())),
+        }
+    }
+
+    /// Assigns to the closest scope that declares `name`.
+    /// Returns `false` if no scope in the chain declares it.
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<bool, RuntimeError> {
+        if self.variables.contains_key(name) {
+            if self.constants.contains(name) {
+                bail!(RuntimeError::ConstantReassignment(name.to_string()))
+            }
+            self.variables.insert(name.to_string(), value);
+            return Ok(true);
+        }
+        match self.parent {
+            Some(ref parent) => parent.borrow_mut().assign(name, value),
+            None => Ok(false),
+        }
+    }
+
+    /// Names declared in this scope and all of its parents.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.variables.keys().cloned().collect();
+        if let Some(ref parent) = self.parent {
+            names.extend(parent.borrow().names());
+        }
+        names
+    }
+
+    /// Variables declared directly in this scope.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Makes the constructor of `T` available to scripts under `T::NAME`.
+    ///
+    /// Classes without a constructor are not declared, the returned class can
+    /// still be used to hand instances created by the host to scripts.
+    pub fn register<T: NativeClass>(&mut self) -> Result<Rc<HostClass>, RuntimeError> {
+        let class = HostClass::of::<T>();
+        if let Some(constructor) = class.constructor() {
+            self.declare(T::NAME, constructor, true)?;
+        }
+        Ok(class)
+    }
+}
@@ -0,0 +1,181 @@
+use std::{path::Path, rc::Rc};
+
+use crate::lexer::Span;
+
+/// Where in the source code a runtime error happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub span: Span,
+    pub file: Option<Rc<Path>>, // None for code that didn't come from a file
+}
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    UndefinedVariable(String),            // variable_name
+    ConstantReassignment(String),         // constant_name
+    TypeMismatch(String, String, String), // operator, left_type, right_type
+    InvalidOperand(String, String),       // operator, operand_type
+    NotCallable(String),                  // type_name
+    NotConstructible(String),             // type_name
+    ArityMismatch(String, usize, usize),  // function_name, expected, got
+    InvalidSignature(String),             // function_name
+    UndefinedProperty(String, String),    // type_name, property
+    ReadOnlyProperty(String, String),     // type_name, property
+    WrongType(String, String, String),    // target, expected_type, got_type
+    InvalidArgument(String, String),      // function_name, message
+    IndexOutOfBounds(i64, usize),         // index, length
+    NotIterable(String),                  // type_name
+    TooLarge(String, usize),              // operator, maximum length
+    IntegerOverflow,
+    DivisionByZero,
+    InvalidAssignmentTarget,
+    ReturnOutsideFunction,
+    OutsideOfLoop,
+    StackOverflow,
+    ModuleNotFound(String),        // module_path
+    ImportCycle(String),           // chain of files
+    InvalidModule(String, String), // file, message
+    InvalidImport,
+    MissingSuper,
+    AlreadySuspended,
+    NotSuspended,
+    CannotWait,
+    Unsupported(String),                  // feature description
+    Located(Box<RuntimeError>, Location), // error, the innermost code that raised it
+}
+
+impl RuntimeError {
+    /// Attaches the location of the code that failed, unless a more precise one is attached already.
+    pub fn at(self, span: Span, file: Option<Rc<Path>>) -> Self {
+        match self {
+            RuntimeError::Located(..) => self,
+            err => RuntimeError::Located(Box::new(err), Location { span, file }),
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            RuntimeError::Located(_, location) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error itself, without the location the interpreter or the vm attached to it.
+    pub fn without_location(self) -> Self {
+        match self {
+            RuntimeError::Located(err, _) => *err,
+            err => err,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::UndefinedVariable(name) => {
+                write!(f, "Variable '{}' is not defined", name)
+            }
+            RuntimeError::ConstantReassignment(name) => {
+                write!(f, "Cannot reassign constant '{}'", name)
+            }
+            RuntimeError::TypeMismatch(operator, left, right) => {
+                write!(
+                    f,
+                    "Unsupported operand types for '{}': '{}' and '{}'",
+                    operator, left, right
+                )
+            }
+            RuntimeError::InvalidOperand(operator, operand) => {
+                write!(
+                    f,
+                    "Unsupported operand type for '{}': '{}'",
+                    operator, operand
+                )
+            }
+            RuntimeError::NotCallable(type_name) => {
+                write!(f, "Value of type '{}' is not callable", type_name)
+            }
+            RuntimeError::NotConstructible(type_name) => {
+                write!(
+                    f,
+                    "Value of type '{}' can't be created with 'new'",
+                    type_name
+                )
+            }
+            RuntimeError::ArityMismatch(name, expected, got) => {
+                write!(
+                    f,
+                    "Function '{}' expects {} argument(s), but {} were given",
+                    name, expected, got
+                )
+            }
+            RuntimeError::InvalidSignature(name) => {
+                write!(f, "Function '{}' has an invalid parameter list", name)
+            }
+            RuntimeError::UndefinedProperty(type_name, property) => {
+                write!(f, "'{}' has no property '{}'", type_name, property)
+            }
+            RuntimeError::ReadOnlyProperty(type_name, property) => {
+                write!(f, "Property '{}' of '{}' is read-only", property, type_name)
+            }
+            RuntimeError::WrongType(target, expected, got) => {
+                write!(f, "{} should be '{}', got '{}'", target, expected, got)
+            }
+            RuntimeError::InvalidArgument(name, message) => {
+                write!(f, "Invalid argument for '{}': {}", name, message)
+            }
+            RuntimeError::IndexOutOfBounds(index, length) => {
+                write!(f, "Index {} is out of bounds for length {}", index, length)
+            }
+            RuntimeError::NotIterable(type_name) => {
+                write!(f, "Value of type '{}' is not iterable", type_name)
+            }
+            RuntimeError::TooLarge(operator, limit) => write!(
+                f,
+                "Result of '{}' would be longer than {} items",
+                operator, limit
+            ),
+            RuntimeError::IntegerOverflow => write!(f, "Integer overflow"),
+            RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::InvalidAssignmentTarget => write!(f, "Invalid assignment target"),
+            RuntimeError::ReturnOutsideFunction => {
+                write!(f, "'return' is only allowed inside of a function")
+            }
+            RuntimeError::OutsideOfLoop => {
+                write!(
+                    f,
+                    "'break' and 'continue' are only allowed inside of a loop"
+                )
+            }
+            RuntimeError::StackOverflow => write!(f, "Stack overflow"),
+            RuntimeError::ModuleNotFound(path) => write!(f, "Module '{}' was not found", path),
+            RuntimeError::ImportCycle(chain) => write!(f, "Import cycle: {}", chain),
+            RuntimeError::InvalidModule(file, message) => {
+                write!(f, "Failed to load module '{}': {}", file, message)
+            }
+            RuntimeError::InvalidImport => write!(f, "Only module paths can be imported"),
+            RuntimeError::MissingSuper => write!(
+                f,
+                "'super' is only available in methods of a class that inherits from another"
+            ),
+            RuntimeError::AlreadySuspended => {
+                write!(
+                    f,
+                    "Another execution is suspended and has to be resumed first"
+                )
+            }
+            RuntimeError::NotSuspended => write!(f, "There is no suspended execution to resume"),
+            RuntimeError::CannotWait => {
+                write!(
+                    f,
+                    "Waiting is only possible in the vm, in executions started with a budget"
+                )
+            }
+            RuntimeError::Unsupported(feature) => write!(f, "{} is not supported yet", feature),
+            // the location is shown next to the message by `Diagnostic`
+            RuntimeError::Located(err, _) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
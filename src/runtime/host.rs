@@ -0,0 +1,354 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, marker::PhantomData, rc::Rc};
+
+use crate::macros::bail;
+
+use super::{
+    convert::{convert, FromArgs, FromValue, IntoValue},
+    error::RuntimeError,
+    value::Value,
+};
+
+type Constructor = dyn Fn(Vec<Value>) -> Result<Box<dyn Any>, RuntimeError>;
+type Getter = dyn Fn(&dyn Any) -> Value;
+// setters and methods get the whole object, so they borrow it only after the
+// arguments are converted, converting may need to look at the object too
+type Setter = dyn Fn(&RefCell<HostObject>, &Value) -> Result<(), RuntimeError>;
+type Method = dyn Fn(&RefCell<HostObject>, Vec<Value>) -> Result<Value, RuntimeError>;
+
+/// A Rust type that scripts can use as a class, e.g. a robot tail like `Wheel`.
+///
+/// `bind` registers the constructor, properties and methods scripts can use.
+pub trait NativeClass: Sized + 'static {
+    const NAME: &'static str;
+
+    fn bind(class: &mut HostClassBuilder<Self>);
+}
+
+/// Type erased description of a `NativeClass`.
+pub struct HostClass {
+    pub name: String,
+    constructor: Option<(usize, Box<Constructor>)>, // arity, constructor
+    getters: HashMap<String, Box<Getter>>,
+    setters: HashMap<String, Box<Setter>>,
+    methods: HashMap<String, (usize, Rc<Method>)>, // arity, method
+}
+
+impl HostClass {
+    pub fn of<T: NativeClass>() -> Rc<HostClass> {
+        let mut builder = HostClassBuilder {
+            class: HostClass {
+                name: T::NAME.to_string(),
+                constructor: None,
+                getters: HashMap::new(),
+                setters: HashMap::new(),
+                methods: HashMap::new(),
+            },
+            marker: PhantomData,
+        };
+        T::bind(&mut builder);
+        Rc::new(builder.class)
+    }
+
+    /// The function scripts call to construct the class, if it has a constructor.
+    pub fn constructor(self: &Rc<Self>) -> Option<Value> {
+        let (arity, _) = self.constructor.as_ref()?;
+        let class = self.clone();
+
+        Some(Value::native(&self.name, Some(*arity), move |args| {
+            let construct = match class.constructor {
+                Some((_, ref construct)) => construct,
+                None => unreachable!("class was checked to have a constructor"),
+            };
+            Ok(Value::HostObject(Rc::new(RefCell::new(HostObject {
+                class: class.clone(),
+                data: construct(args)?,
+            }))))
+        }))
+    }
+
+    /// Wraps `data` created by the host, so it can be handed to scripts.
+    pub fn instance<T: NativeClass>(self: &Rc<Self>, data: T) -> Value {
+        Value::HostObject(Rc::new(RefCell::new(HostObject {
+            class: self.clone(),
+            data: Box::new(data),
+        })))
+    }
+}
+
+/// Collects the members of a `NativeClass`, see `NativeClass::bind`.
+pub struct HostClassBuilder<T> {
+    class: HostClass,
+    marker: PhantomData<T>,
+}
+
+impl<T: NativeClass> HostClassBuilder<T> {
+    pub fn constructor<A: FromArgs>(
+        &mut self,
+        constructor: impl Fn(A) -> Result<T, RuntimeError> + 'static,
+    ) -> &mut Self {
+        let name = self.class.name.clone();
+        self.class.constructor = Some((
+            A::arity(),
+            Box::new(move |args| {
+                let data: Box<dyn Any> = Box::new(constructor(A::from_args(&name, args)?)?);
+                Ok(data)
+            }),
+        ));
+        self
+    }
+
+    pub fn getter<R: IntoValue>(
+        &mut self,
+        name: &str,
+        getter: impl Fn(&T) -> R + 'static,
+    ) -> &mut Self {
+        self.class.getters.insert(
+            name.to_string(),
+            Box::new(move |data| getter(downcast_ref(data)).into_value()),
+        );
+        self
+    }
+
+    pub fn setter<V: FromValue>(
+        &mut self,
+        name: &str,
+        setter: impl Fn(&mut T, V) + 'static,
+    ) -> &mut Self {
+        let target = format!("Property '{}' of '{}'", name, self.class.name);
+        self.class.setters.insert(
+            name.to_string(),
+            Box::new(move |object, value| {
+                let value = convert(&target, value)?;
+                setter(downcast_mut(object.borrow_mut().data.as_mut()), value);
+                Ok(())
+            }),
+        );
+        self
+    }
+
+    pub fn method<A: FromArgs, R: IntoValue>(
+        &mut self,
+        name: &str,
+        method: impl Fn(&mut T, A) -> Result<R, RuntimeError> + 'static,
+    ) -> &mut Self {
+        let function_name = format!("{}.{}", self.class.name, name);
+        self.class.methods.insert(
+            name.to_string(),
+            (
+                A::arity(),
+                Rc::new(move |object, args| {
+                    let args = A::from_args(&function_name, args)?;
+                    let result = method(downcast_mut(object.borrow_mut().data.as_mut()), args);
+                    Ok(result?.into_value())
+                }),
+            ),
+        );
+        self
+    }
+}
+
+fn downcast_ref<T: 'static>(data: &dyn Any) -> &T {
+    data.downcast_ref()
+        .expect("host object data should match its class")
+}
+
+fn downcast_mut<T: 'static>(data: &mut dyn Any) -> &mut T {
+    data.downcast_mut()
+        .expect("host object data should match its class")
+}
+
+/// An instance of a `NativeClass` living inside of a script value.
+pub struct HostObject {
+    pub class: Rc<HostClass>,
+    data: Box<dyn Any>,
+}
+
+impl HostObject {
+    pub fn downcast_ref<T: NativeClass>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+
+    pub fn downcast_mut<T: NativeClass>(&mut self) -> Option<&mut T> {
+        self.data.downcast_mut()
+    }
+
+    // methods come back as native functions bound to the object
+    pub(super) fn get(object: &Rc<RefCell<HostObject>>, name: &str) -> Result<Value, RuntimeError> {
+        let this = object.borrow();
+        if let Some(getter) = this.class.getters.get(name) {
+            return Ok(getter(this.data.as_ref()));
+        }
+        if let Some((arity, method)) = this.class.methods.get(name) {
+            let receiver = object.clone();
+            let method = method.clone();
+            let name = format!("{}.{}", this.class.name, name);
+            return Ok(Value::native(&name, Some(*arity), move |args| {
+                method(&receiver, args)
+            }));
+        }
+
+        bail!(RuntimeError::UndefinedProperty(
+            this.class.name.clone(),
+            name.to_string()
+        ))
+    }
+
+    pub(super) fn set(
+        object: &Rc<RefCell<HostObject>>,
+        name: &str,
+        value: &Value,
+    ) -> Result<(), RuntimeError> {
+        let class = object.borrow().class.clone();
+        if let Some(setter) = class.setters.get(name) {
+            return setter(object, value);
+        }
+        if class.getters.contains_key(name) {
+            bail!(RuntimeError::ReadOnlyProperty(
+                class.name.clone(),
+                name.to_string()
+            ))
+        }
+
+        bail!(RuntimeError::UndefinedProperty(
+            class.name.clone(),
+            name.to_string()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        script::{Script, ScriptError},
+        vm::Vm,
+    };
+
+    struct Wheel {
+        port: i64,
+        speed: f64,
+    }
+
+    impl NativeClass for Wheel {
+        const NAME: &'static str = "Wheel";
+
+        fn bind(class: &mut HostClassBuilder<Self>) {
+            class
+                .constructor(|(port,): (i64,)| Ok(Wheel { port, speed: 0.0 }))
+                .getter("port", |wheel| wheel.port)
+                .getter("speed", |wheel| wheel.speed)
+                .setter("speed", |wheel, speed: f64| wheel.speed = speed)
+                .method("accelerate", |wheel, (by,): (f64,)| {
+                    wheel.speed += by;
+                    Ok(wheel.speed)
+                })
+                .method("stop", |wheel, (): ()| {
+                    wheel.speed = 0.0;
+                    Ok(())
+                });
+        }
+    }
+
+    fn load(source: &str) -> Result<Script, ScriptError> {
+        let mut vm = Vm::new();
+        vm.globals().register::<Wheel>().unwrap();
+        Script::load_into(vm, source)
+    }
+
+    fn run_err(source: &str) -> RuntimeError {
+        match load(source) {
+            Err(ScriptError::Runtime(err)) => err.without_location(),
+            Err(err) => panic!("Unexpected error: {}", err),
+            Ok(_) => panic!("Expected a runtime error in: {}", source),
+        }
+    }
+
+    #[test]
+    fn test_members() {
+        let source = "const wheel = Wheel(3)\n\
+                      wheel.speed = 10\n\
+                      wheel.speed += 2.5\n\
+                      let faster = wheel.accelerate(1)\n\
+                      let port = wheel.port";
+        let mut script = load(source).unwrap();
+        let globals = script.vm().globals();
+        assert_eq!(globals.lookup("faster").unwrap(), Value::Decimal(13.5));
+        assert_eq!(globals.lookup("port").unwrap(), Value::Integer(3));
+
+        match globals.lookup("wheel").unwrap() {
+            Value::HostObject(object) => {
+                let object = object.borrow();
+                let wheel = object.downcast_ref::<Wheel>().unwrap();
+                assert_eq!(wheel.speed, 13.5);
+            }
+            value => panic!("Expected a host object, got {}", value),
+        }
+    }
+
+    #[test]
+    fn test_host_instance() {
+        let mut vm = Vm::new();
+        let class = vm.globals().register::<Wheel>().unwrap();
+        let wheel = class.instance(Wheel {
+            port: 1,
+            speed: 5.0,
+        });
+        vm.globals().declare("left", wheel.clone(), true).unwrap();
+
+        let mut script = Script::load_into(vm, "fn update(delta) { left.stop() }").unwrap();
+        script.update(0.1).unwrap();
+        assert_eq!(wheel.get_property("speed").unwrap(), Value::Decimal(0.0));
+        assert_eq!(wheel.to_string(), "<Wheel object>");
+    }
+
+    #[test]
+    fn test_type_errors() {
+        match run_err("Wheel(\"left\")") {
+            RuntimeError::WrongType(target, expected, got) => {
+                assert_eq!(target, "Argument 1 of 'Wheel'");
+                assert_eq!(expected, "int");
+                assert_eq!(got, "string");
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+        assert!(matches!(
+            run_err("let wheel = Wheel(1)\nwheel.speed = true"),
+            RuntimeError::WrongType(..)
+        ));
+        assert!(matches!(
+            run_err("Wheel()"),
+            RuntimeError::ArityMismatch(_, 1, 0)
+        ));
+        assert!(matches!(
+            run_err("let w = Wheel(1)\nw.accelerate()"),
+            RuntimeError::ArityMismatch(..)
+        ));
+        assert!(matches!(
+            run_err("let w = Wheel(1)\nw.port = 2"),
+            RuntimeError::ReadOnlyProperty(..)
+        ));
+        assert!(matches!(
+            run_err("let w = Wheel(1)\nw.direction"),
+            RuntimeError::UndefinedProperty(..)
+        ));
+    }
+
+    #[test]
+    fn test_object_as_argument() {
+        // the error names the object's class, which must not be borrowed by the setter yet
+        match run_err("let w = Wheel(1)\nw.speed = w") {
+            RuntimeError::WrongType(_, expected, got) => {
+                assert_eq!(expected, "float");
+                assert_eq!(got, "Wheel");
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+        match run_err("let w = Wheel(1)\nw.accelerate(w)") {
+            RuntimeError::WrongType(target, _, got) => {
+                assert_eq!(target, "Argument 1 of 'Wheel.accelerate'");
+                assert_eq!(got, "Wheel");
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,1182 @@
+use std::{cell::RefCell, collections::HashMap, path::Path, rc::Rc};
+
+use crate::{
+    macros::bail,
+    parser::{
+        AssignmentOperator, BinaryOperator, ImportKind, LogicalOperator, Node, NodeKind,
+        UnaryOperator,
+    },
+    vm::timer::{self, Clock},
+};
+
+use super::{
+    builtins,
+    environment::Environment,
+    error::RuntimeError,
+    module::{import_path, Module, ModuleResolver, Resolved},
+    operators::{binary_operation, unary_operation},
+    signature::Signature,
+    value::{Class, Function, Instance, Object, Value, ANONYMOUS},
+};
+
+type Scope = Rc<RefCell<Environment>>;
+// positional arguments of a call followed by the named ones
+type Arguments = (Vec<Value>, Vec<(String, Value)>);
+
+// result of executing a statement, `return` has to unwind up to the closest function call,
+// `break` and `continue` up to the closest loop
+enum Completion {
+    Normal(Value),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+// the same limit as the vm's call frames
+const MAX_DEPTH: usize = 1024;
+// every call also takes native stack, in debug builds a lot of it, so calls stop before
+// they use more than this much of the 2 MiB a thread gets by default
+const STACK_BUDGET: usize = 1536 * 1024;
+
+// roughly where the native stack is right now
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::ptr::addr_of!(marker) as usize
+}
+
+pub struct Interpreter {
+    builtins: Scope,
+    globals: Scope,
+    modules: ModuleResolver,
+    clock: Rc<RefCell<Clock>>,
+    file: Option<Rc<Path>>, // file of the code that runs right now
+    depth: usize,           // calls of script functions that haven't returned yet
+    stack_base: usize,      // address on the native stack where the outermost call started
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let builtins = Rc::new(RefCell::new(builtins::scope()));
+        let clock = Rc::new(RefCell::new(Clock::default()));
+        let mut modules = builtins::resolver();
+        modules.register(timer::module_without_waiting(&clock));
+
+        Self {
+            globals: Rc::new(RefCell::new(Environment::with_parent(builtins.clone()))),
+            builtins,
+            modules,
+            clock,
+            file: None,
+            depth: 0,
+            stack_base: 0,
+        }
+    }
+
+    pub fn globals(&self) -> Scope {
+        self.globals.clone()
+    }
+
+    pub fn modules(&mut self) -> &mut ModuleResolver {
+        &mut self.modules
+    }
+
+    /// Simulated time in seconds, see `advance_time`.
+    pub fn time(&self) -> f64 {
+        self.clock.borrow().now().as_secs_f64()
+    }
+
+    /// Moves the simulated time forward, scripts see it in `timer.now()`.
+    pub fn advance_time(&mut self, seconds: f64) -> Result<(), RuntimeError> {
+        let delta = timer::seconds("advance_time", seconds)?;
+        self.clock.borrow_mut().advance(delta);
+        Ok(())
+    }
+
+    /// Same as `interpret`, but imports are resolved relative to `file`.
+    pub fn interpret_file(&mut self, program: &Node, file: &Path) -> Result<Value, RuntimeError> {
+        self.file = Some(Rc::from(file));
+        self.interpret(program)
+    }
+
+    /// Evaluates the whole program in the global scope and returns the value
+    /// of the last statement (statements other than expressions produce `null`).
+    pub fn interpret(&mut self, program: &Node) -> Result<Value, RuntimeError> {
+        let globals = self.globals.clone();
+
+        match &program.kind {
+            NodeKind::Program(statements) => {
+                let mut result = Value::Null;
+                for statement in statements {
+                    match self.execute(statement, &globals)? {
+                        Completion::Normal(value) => result = value,
+                        Completion::Return(_) => bail!(RuntimeError::ReturnOutsideFunction),
+                        Completion::Break | Completion::Continue => {
+                            bail!(RuntimeError::OutsideOfLoop)
+                        }
+                    }
+                }
+                Ok(result)
+            }
+            _ => self.evaluate(program, &globals),
+        }
+    }
+
+    pub fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.call_with(callee, args, vec![])
+    }
+
+    fn call_with(
+        &mut self,
+        callee: &Value,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Function(function) => self.call_function(function, None, args, named),
+            Value::BoundMethod(bound) => match &bound.method {
+                Value::Function(method) => {
+                    self.call_function(method, Some(bound.receiver.clone()), args, named)
+                }
+                _ => bail!(RuntimeError::Unsupported(
+                    "Calling a compiled method from the tree-walking interpreter".to_string()
+                )),
+            },
+            Value::NativeFunction(native) => {
+                if !named.is_empty() {
+                    bail!(RuntimeError::InvalidArgument(
+                        native.name.clone(),
+                        "doesn't take named arguments".to_string()
+                    ))
+                }
+                if let Some(arity) = native.arity {
+                    if arity != args.len() {
+                        bail!(RuntimeError::ArityMismatch(
+                            native.name.clone(),
+                            arity,
+                            args.len()
+                        ))
+                    }
+                }
+                (native.function)(args)
+            }
+            Value::Class(class) => self.instantiate(class, args, named),
+            Value::CompiledFunction(_) | Value::Closure(_) => bail!(RuntimeError::Unsupported(
+                "Calling a compiled function from the tree-walking interpreter".to_string()
+            )),
+            value => bail!(RuntimeError::NotCallable(value.type_name())),
+        }
+    }
+
+    // errors get the span of the innermost node that failed
+    fn execute(&mut self, node: &Node, env: &Scope) -> Result<Completion, RuntimeError> {
+        self.execute_node(node, env)
+            .map_err(|err| err.at(node.span, self.file.clone()))
+    }
+
+    fn evaluate(&mut self, node: &Node, env: &Scope) -> Result<Value, RuntimeError> {
+        self.evaluate_node(node, env)
+            .map_err(|err| err.at(node.span, self.file.clone()))
+    }
+
+    fn execute_node(&mut self, node: &Node, env: &Scope) -> Result<Completion, RuntimeError> {
+        match &node.kind {
+            NodeKind::VariableDeclaration(name, value, is_constant) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value, env)?,
+                    None => Value::Null,
+                };
+                env.borrow_mut().declare(name, value, *is_constant)?;
+                Ok(Completion::Normal(Value::Null))
+            }
+            NodeKind::BlockStatement(statements) => {
+                let scope = Rc::new(RefCell::new(Environment::with_parent(env.clone())));
+                for statement in statements {
+                    match self.execute(statement, &scope)? {
+                        Completion::Normal(_) => {}
+                        completion => return Ok(completion),
+                    }
+                }
+                Ok(Completion::Normal(Value::Null))
+            }
+            NodeKind::FunctionDeclaration(id, params, body) => {
+                let function = self.function(Self::identifier_name(id)?, params, body, env)?;
+                env.borrow_mut().declare(
+                    &function.name.clone(),
+                    Value::Function(function),
+                    false,
+                )?;
+                Ok(Completion::Normal(Value::Null))
+            }
+            NodeKind::IfStatement(condition, consequent, alternate) => {
+                if self.evaluate(condition, env)?.is_truthy() {
+                    self.execute(consequent, env)
+                } else if let Some(alternate) = alternate {
+                    self.execute(alternate, env)
+                } else {
+                    Ok(Completion::Normal(Value::Null))
+                }
+            }
+            NodeKind::ForInStatement(left, right, body) => {
+                let name = Self::identifier_name(left)?;
+                let iterable = self.evaluate(right, env)?;
+
+                for item in iterable.iterate()? {
+                    // every iteration gets its own scope for the loop variable
+                    let scope = Rc::new(RefCell::new(Environment::with_parent(env.clone())));
+                    scope.borrow_mut().declare(&name, item, false)?;
+                    match self.execute(body, &scope)? {
+                        Completion::Normal(_) | Completion::Continue => {}
+                        Completion::Break => break,
+                        completion => return Ok(completion),
+                    }
+                }
+
+                Ok(Completion::Normal(Value::Null))
+            }
+            NodeKind::WhileStatement(condition, body) => {
+                while self.evaluate(condition, env)?.is_truthy() {
+                    match self.execute(body, env)? {
+                        Completion::Normal(_) | Completion::Continue => {}
+                        Completion::Break => break,
+                        completion => return Ok(completion),
+                    }
+                }
+                Ok(Completion::Normal(Value::Null))
+            }
+            NodeKind::BreakStatement() => Ok(Completion::Break),
+            NodeKind::ContinueStatement() => Ok(Completion::Continue),
+            NodeKind::ReturnStatement(Some(value)) => {
+                Ok(Completion::Return(self.evaluate(value, env)?))
+            }
+            NodeKind::ReturnStatement(None) => Ok(Completion::Return(Value::Null)),
+            NodeKind::ImportStatement(path, kind) => {
+                let (path, name) = match import_path(path) {
+                    Some(import) => import,
+                    None => bail!(RuntimeError::InvalidImport),
+                };
+                let module = self.import(&path)?;
+
+                let mut env = env.borrow_mut();
+                match kind {
+                    ImportKind::Module(alias) => {
+                        let name = alias.as_ref().unwrap_or(&name);
+                        env.declare(name, Value::Module(module), false)?;
+                    }
+                    ImportKind::Wildcard => {
+                        for (name, value) in &module.exports {
+                            env.declare(name, value.clone(), false)?;
+                        }
+                    }
+                    ImportKind::Names(names) => {
+                        for (name, alias) in names {
+                            let value = module.export(name)?;
+                            env.declare(alias.as_ref().unwrap_or(name), value, false)?;
+                        }
+                    }
+                }
+                Ok(Completion::Normal(Value::Null))
+            }
+            NodeKind::ClassDeclaration(id, super_class, body) => {
+                let class = self.class(id, super_class, body, env)?;
+                env.borrow_mut()
+                    .declare(&class.name.clone(), Value::Class(class), false)?;
+                Ok(Completion::Normal(Value::Null))
+            }
+            _ => Ok(Completion::Normal(self.evaluate(node, env)?)),
+        }
+    }
+
+    fn evaluate_node(&mut self, node: &Node, env: &Scope) -> Result<Value, RuntimeError> {
+        match &node.kind {
+            NodeKind::IntegerLiteral(value) => match i64::try_from(*value) {
+                Ok(value) => Ok(Value::Integer(value)),
+                Err(_) => bail!(RuntimeError::IntegerOverflow),
+            },
+            NodeKind::DecimalLiteral(value) => Ok(Value::Decimal(*value)),
+            NodeKind::StringLiteral(value) => Ok(Value::String(value.clone())),
+            NodeKind::BoolLiteral(value) => Ok(Value::Bool(*value)),
+            NodeKind::NullLiteral() => Ok(Value::Null),
+            NodeKind::Identifier(name) => env.borrow().lookup(name),
+            NodeKind::ArrayExpression(items) => {
+                let mut values = vec![];
+                for item in items {
+                    values.push(self.evaluate(item, env)?);
+                }
+                Ok(Value::list(values))
+            }
+            NodeKind::ObjectExpression(properties) => {
+                let mut pairs = vec![];
+                for (key, value, computed) in properties {
+                    let key = match (&key.kind, computed) {
+                        (_, true) => self.evaluate(key, env)?,
+                        (NodeKind::Identifier(name) | NodeKind::StringLiteral(name), false) => {
+                            Value::String(name.clone())
+                        }
+                        _ => bail!(RuntimeError::InvalidAssignmentTarget),
+                    };
+                    pairs.push((key, self.evaluate(value, env)?));
+                }
+                Ok(Value::object(Object::from_pairs(pairs)?))
+            }
+            NodeKind::BinaryExpression(left, operator, right) => {
+                let left = self.evaluate(left, env)?;
+                let right = self.evaluate(right, env)?;
+                binary_operation(*operator, &left, &right)
+            }
+            NodeKind::ComparisonExpression(first, comparisons) => {
+                let mut left = self.evaluate(first, env)?;
+                for (operator, right) in comparisons {
+                    let right = self.evaluate(right, env)?;
+                    if !binary_operation(*operator, &left, &right)?.is_truthy() {
+                        return Ok(Value::Bool(false));
+                    }
+                    left = right;
+                }
+                Ok(Value::Bool(true))
+            }
+            NodeKind::LogicalExpression(left, operator, right) => {
+                let left = self.evaluate(left, env)?;
+                match operator {
+                    LogicalOperator::And if !left.is_truthy() => Ok(left),
+                    LogicalOperator::Or if left.is_truthy() => Ok(left),
+                    _ => self.evaluate(right, env),
+                }
+            }
+            NodeKind::UnaryExpression(target, operator) => match operator {
+                UnaryOperator::Increment => self.assign(
+                    target,
+                    Some(BinaryOperator::Plus),
+                    &Node::new(NodeKind::IntegerLiteral(1), node.span),
+                    env,
+                ),
+                UnaryOperator::Decrement => self.assign(
+                    target,
+                    Some(BinaryOperator::Minus),
+                    &Node::new(NodeKind::IntegerLiteral(1), node.span),
+                    env,
+                ),
+                operator => unary_operation(*operator, &self.evaluate(target, env)?),
+            },
+            NodeKind::MemberExpression(object, property, false) if Self::is_super(object) => {
+                Self::super_method(&Self::identifier_name(property)?, env)
+            }
+            NodeKind::MemberExpression(object, property, computed) => {
+                let object = self.evaluate(object, env)?;
+                if *computed {
+                    let index = self.evaluate(property, env)?;
+                    object.get_index(&index)
+                } else {
+                    object.get_property(&Self::identifier_name(property)?)
+                }
+            }
+            NodeKind::CallExpression(callee, args) => {
+                let callee = match Self::is_super(callee) {
+                    true => Self::super_method("init", env)?,
+                    false => self.evaluate(callee, env)?,
+                };
+                let (values, named) = self.arguments(args, env)?;
+                self.call_with(&callee, values, named)
+            }
+            NodeKind::FunctionExpression(params, body) => Ok(Value::Function(self.function(
+                ANONYMOUS.to_string(),
+                params,
+                body,
+                env,
+            )?)),
+            NodeKind::NewExpression(class, args) => {
+                let class = match self.evaluate(class, env)? {
+                    Value::Class(class) => class,
+                    value => bail!(RuntimeError::NotConstructible(value.type_name())),
+                };
+                let (values, named) = self.arguments(args, env)?;
+                self.instantiate(&class, values, named)
+            }
+            NodeKind::AssignmentExpression(target, operator, value) => {
+                let operator = match operator {
+                    AssignmentOperator::Equals => None,
+                    AssignmentOperator::Addition => Some(BinaryOperator::Plus),
+                    AssignmentOperator::Subtraction => Some(BinaryOperator::Minus),
+                    AssignmentOperator::Multiplication => Some(BinaryOperator::Multiply),
+                    AssignmentOperator::Division => Some(BinaryOperator::Divide),
+                    AssignmentOperator::Modulation => Some(BinaryOperator::Modulo),
+                };
+                self.assign(target, operator, value, env)
+            }
+            NodeKind::Program(_)
+            | NodeKind::VariableDeclaration(..)
+            | NodeKind::BlockStatement(_)
+            | NodeKind::FunctionDeclaration(..)
+            | NodeKind::IfStatement(..)
+            | NodeKind::ForInStatement(..)
+            | NodeKind::WhileStatement(..)
+            | NodeKind::BreakStatement()
+            | NodeKind::ContinueStatement()
+            | NodeKind::ReturnStatement(_)
+            | NodeKind::ImportStatement(..)
+            | NodeKind::ClassDeclaration(..)
+            | NodeKind::PropertyDefinition(..)
+            | NodeKind::MethodDefinition(..) => bail!(RuntimeError::Unsupported(
+                "Using a statement as an expression".to_string()
+            )),
+            NodeKind::Parameter(..) | NodeKind::NamedArgument(..) => {
+                bail!(RuntimeError::Unsupported(
+                    "Using a parameter or a named argument outside of a function".to_string()
+                ))
+            }
+        }
+    }
+
+    // evaluates call arguments in order, splitting the named ones out
+    fn arguments(&mut self, args: &[Box<Node>], env: &Scope) -> Result<Arguments, RuntimeError> {
+        let mut values = vec![];
+        let mut named = vec![];
+        for arg in args {
+            match &arg.kind {
+                NodeKind::NamedArgument(name, value) => {
+                    named.push((name.clone(), self.evaluate(value, env)?))
+                }
+                _ => values.push(self.evaluate(arg, env)?),
+            }
+        }
+        Ok((values, named))
+    }
+
+    // stores `value` into an identifier or a member expression, combining it
+    // with the current value first if it's a compound assignment (e.g. `+=`),
+    // the target is always evaluated before the value
+    fn assign(
+        &mut self,
+        target: &Node,
+        operator: Option<BinaryOperator>,
+        value: &Node,
+        env: &Scope,
+    ) -> Result<Value, RuntimeError> {
+        match &target.kind {
+            NodeKind::Identifier(name) => {
+                let value = match operator {
+                    Some(operator) => {
+                        let current = env.borrow().lookup(name)?;
+                        binary_operation(operator, &current, &self.evaluate(value, env)?)?
+                    }
+                    None => self.evaluate(value, env)?,
+                };
+                let assigned = env.borrow_mut().assign(name, value.clone())?;
+                if !assigned {
+                    // assigning to an unknown name declares it in the current scope
+                    env.borrow_mut().declare(name, value.clone(), false)?;
+                }
+                Ok(value)
+            }
+            NodeKind::MemberExpression(object, property, computed) => {
+                let object = self.evaluate(object, env)?;
+                if *computed {
+                    let index = self.evaluate(property, env)?;
+                    let value = match operator {
+                        Some(operator) => {
+                            let current = object.get_index(&index)?;
+                            binary_operation(operator, &current, &self.evaluate(value, env)?)?
+                        }
+                        None => self.evaluate(value, env)?,
+                    };
+                    object.set_index(&index, value.clone())?;
+                    Ok(value)
+                } else {
+                    let name = Self::identifier_name(property)?;
+                    let value = match operator {
+                        Some(operator) => {
+                            let current = object.get_property(&name)?;
+                            binary_operation(operator, &current, &self.evaluate(value, env)?)?
+                        }
+                        None => self.evaluate(value, env)?,
+                    };
+                    object.set_property(&name, value.clone())?;
+                    Ok(value)
+                }
+            }
+            _ => bail!(RuntimeError::InvalidAssignmentTarget),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        function: &Rc<Function>,
+        receiver: Option<Value>,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Result<Value, RuntimeError> {
+        let here = stack_address();
+        if self.depth == 0 {
+            self.stack_base = here;
+        }
+        if self.depth >= MAX_DEPTH || self.stack_base.abs_diff(here) > STACK_BUDGET {
+            bail!(RuntimeError::StackOverflow)
+        }
+        self.depth += 1;
+        let result = self.enter_function(function, receiver, args, named);
+        self.depth -= 1;
+        result
+    }
+
+    fn enter_function(
+        &mut self,
+        function: &Rc<Function>,
+        receiver: Option<Value>,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Result<Value, RuntimeError> {
+        let values = function.signature.bind(&function.name, args, named)?;
+
+        let scope = Rc::new(RefCell::new(Environment::with_parent(
+            function.closure.clone(),
+        )));
+        if let Some(receiver) = receiver {
+            scope.borrow_mut().declare("self", receiver, true)?;
+        }
+        // defaults are evaluated in the call's scope, so they can use earlier parameters
+        let params = function.signature.params.iter().zip(&function.defaults);
+        let rest = function.signature.rest.iter().map(|rest| (rest, &None));
+        for ((param, default), value) in params.chain(rest).zip(values) {
+            let value = match (value, default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.evaluate(default, &scope)?,
+                (None, None) => Value::Null,
+            };
+            scope.borrow_mut().declare(param, value, false)?;
+        }
+
+        let file = std::mem::replace(&mut self.file, function.file.clone());
+        let result = self.execute(&function.body, &scope);
+        self.file = file;
+
+        match result? {
+            Completion::Return(value) => Ok(value),
+            Completion::Normal(_) => Ok(Value::Null),
+            Completion::Break | Completion::Continue => bail!(RuntimeError::OutsideOfLoop),
+        }
+    }
+
+    fn import(&mut self, path: &str) -> Result<Rc<Module>, RuntimeError> {
+        let (file, program) = match self.modules.resolve(path, self.file.as_deref())? {
+            Resolved::Loaded(module) => return Ok(module),
+            Resolved::Program(file, program) => (file, program),
+        };
+
+        // every module gets its own global scope
+        let scope = Rc::new(RefCell::new(Environment::with_parent(
+            self.builtins.clone(),
+        )));
+        let previous = self.file.replace(Rc::from(file.as_path()));
+        let result = self.run_module(&program, &scope);
+        self.file = previous;
+
+        match result {
+            Ok(()) => Ok(self.modules.finish(file, scope.borrow().variables())),
+            Err(err) => {
+                self.modules.fail(&file);
+                Err(err)
+            }
+        }
+    }
+
+    fn run_module(&mut self, program: &Node, scope: &Scope) -> Result<(), RuntimeError> {
+        if let NodeKind::Program(statements) = &program.kind {
+            for statement in statements {
+                match self.execute(statement, scope)? {
+                    Completion::Normal(_) => {}
+                    Completion::Return(_) => bail!(RuntimeError::ReturnOutsideFunction),
+                    Completion::Break | Completion::Continue => {
+                        bail!(RuntimeError::OutsideOfLoop)
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn instantiate(
+        &mut self,
+        class: &Rc<Class>,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = Value::Instance(Rc::new(RefCell::new(Instance::new(class))));
+
+        match class.find_method("init") {
+            Some(Value::Function(init)) => {
+                self.call_function(&init, Some(instance.clone()), args, named)?;
+            }
+            Some(_) => bail!(RuntimeError::Unsupported(
+                "Calling a compiled method from the tree-walking interpreter".to_string()
+            )),
+            None if !named.is_empty() => bail!(RuntimeError::InvalidArgument(
+                class.name.clone(),
+                "doesn't take named arguments".to_string()
+            )),
+            None if !args.is_empty() => bail!(RuntimeError::ArityMismatch(
+                class.name.clone(),
+                0,
+                args.len()
+            )),
+            None => {}
+        }
+
+        Ok(instance)
+    }
+
+    fn function(
+        &self,
+        name: String,
+        params: &[Box<Node>],
+        body: &Node,
+        env: &Scope,
+    ) -> Result<Rc<Function>, RuntimeError> {
+        let (signature, defaults) = match Signature::from_nodes(params) {
+            Some(signature) => signature,
+            None => bail!(RuntimeError::InvalidSignature(name)),
+        };
+
+        Ok(Rc::new(Function {
+            name,
+            signature,
+            defaults,
+            body: body.clone(),
+            closure: env.clone(),
+            file: self.file.clone(),
+        }))
+    }
+
+    fn class(
+        &mut self,
+        id: &Node,
+        super_class: &Option<Box<Node>>,
+        body: &[Box<Node>],
+        env: &Scope,
+    ) -> Result<Rc<Class>, RuntimeError> {
+        let name = Self::identifier_name(id)?;
+
+        let superclass = match super_class {
+            Some(super_class) => match self.evaluate(super_class, env)? {
+                Value::Class(superclass) => Some(superclass),
+                value => bail!(RuntimeError::WrongType(
+                    format!("Superclass of '{}'", name),
+                    "class".to_string(),
+                    value.type_name()
+                )),
+            },
+            None => None,
+        };
+        // methods find the class to call through `super` in their scope
+        let method_scope = match &superclass {
+            Some(superclass) => {
+                let scope = Rc::new(RefCell::new(Environment::with_parent(env.clone())));
+                scope
+                    .borrow_mut()
+                    .declare("super", Value::Class(superclass.clone()), true)?;
+                scope
+            }
+            None => env.clone(),
+        };
+
+        let mut fields = vec![];
+        let mut methods = HashMap::new();
+        let mut statics = HashMap::new();
+
+        for member in body {
+            match &member.kind {
+                NodeKind::PropertyDefinition(property, value, is_static) => {
+                    let name = Self::identifier_name(property)?;
+                    let value = self.evaluate(value, env)?;
+                    match is_static {
+                        true => {
+                            statics.insert(name, value);
+                        }
+                        false => fields.push((name, value)),
+                    }
+                }
+                NodeKind::MethodDefinition(key, params, body, true) => {
+                    let method = self.function(Self::identifier_name(key)?, params, body, env)?;
+                    statics.insert(method.name.clone(), Value::Function(method));
+                }
+                NodeKind::MethodDefinition(key, params, body, false) => {
+                    let method =
+                        self.function(Self::identifier_name(key)?, params, body, &method_scope)?;
+                    methods.insert(method.name.clone(), Value::Function(method));
+                }
+                _ => bail!(RuntimeError::Unsupported(
+                    "Statements inside of a class body".to_string()
+                )),
+            }
+        }
+
+        Ok(Rc::new(Class {
+            name,
+            superclass,
+            fields,
+            methods,
+            statics: RefCell::new(statics),
+        }))
+    }
+
+    fn is_super(node: &Node) -> bool {
+        matches!(&node.kind, NodeKind::Identifier(name) if name == "super")
+    }
+
+    // `super.name` inside of a method, bound to its `self`
+    fn super_method(name: &str, env: &Scope) -> Result<Value, RuntimeError> {
+        let env = env.borrow();
+        match (env.lookup("super"), env.lookup("self")) {
+            (Ok(Value::Class(superclass)), Ok(receiver)) => superclass.bind_super(receiver, name),
+            _ => bail!(RuntimeError::MissingSuper),
+        }
+    }
+
+    fn identifier_name(node: &Node) -> Result<String, RuntimeError> {
+        match &node.kind {
+            NodeKind::Identifier(name) => Ok(name.clone()),
+            _ => bail!(RuntimeError::InvalidAssignmentTarget),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lexer::{Lexer, Span},
+        parser::Parser,
+    };
+
+    fn run(source: &str) -> Result<Value, RuntimeError> {
+        let mut lexer = Lexer::new(source.to_string());
+        if lexer.tokenize().is_err() {
+            panic!("Failed to tokenize source: {}", source);
+        }
+        let program = match Parser::new(lexer.tokens).produce_ast() {
+            Ok(program) => program,
+            Err(err) => panic!("Failed to parse source: {}", err),
+        };
+        Interpreter::new()
+            .interpret(&program)
+            .map_err(RuntimeError::without_location)
+    }
+
+    fn run_ok(source: &str) -> String {
+        match run(source) {
+            Ok(value) => format!("{:?}", value),
+            Err(err) => panic!("Failed to run source: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(run_ok("45"), "45");
+        assert_eq!(run_ok("403.54"), "403.54");
+        assert_eq!(run_ok("\"hello\""), "\"hello\"");
+        assert_eq!(run_ok("true"), "true");
+        assert_eq!(run_ok("null"), "null");
+        assert_eq!(run_ok("[1, \"two\", 3.0]"), "[1, \"two\", 3.0]");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(run_ok("5 + 10 - (3 * 10)"), "-15");
+        assert_eq!(run_ok("21 % 10"), "1");
+        assert_eq!(run_ok("50 / 10"), "5.0");
+        assert_eq!(run_ok("1 + 0.5"), "1.5");
+        assert_eq!(run_ok("\"a\" + \"b\""), "\"ab\"");
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(run_ok("let y = 5\ny += 56\ny -= 12\ny *= 2\ny"), "98");
+        assert_eq!(run_ok("let t\nt"), "null");
+        assert!(matches!(
+            run("const PI = 3.14\nPI = 3"),
+            Err(RuntimeError::ConstantReassignment(_))
+        ));
+        assert!(matches!(
+            run("missing + 1"),
+            Err(RuntimeError::UndefinedVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_block_scope() {
+        assert_eq!(run_ok("let a = 1\nif true { let a = 2 }\na"), "1");
+        assert_eq!(run_ok("let a = 1\nif true { a = 2 }\na"), "2");
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let source = "
+            let x = 5
+            let y
+            if x == 1 { y = 1 } else if x > 3 and x != 4 { y = 2 } else { y = 3 }
+            y
+        ";
+        assert_eq!(run_ok(source), "2");
+    }
+
+    #[test]
+    fn test_for_statement() {
+        assert_eq!(
+            run_ok("let total = 0\nfor i in range(0, 5) { total += i }\ntotal"),
+            "10"
+        );
+        assert_eq!(
+            run_ok("let s = \"\"\nfor c in [\"a\", \"b\"] { s += c }\ns"),
+            "\"ab\""
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let source = "
+            fn mul(a, b) {
+              x = a * b
+              return x
+            }
+            mul(3, 10)
+        ";
+        assert_eq!(run_ok(source), "30");
+        assert!(matches!(
+            run("fn f(a) { return a }\nf()"),
+            Err(RuntimeError::ArityMismatch(_, 1, 0))
+        ));
+        assert!(matches!(
+            run("let f = 5\nf()"),
+            Err(RuntimeError::NotCallable(_))
+        ));
+        assert!(matches!(
+            run("fn f() { return f() }\nf()"),
+            Err(RuntimeError::StackOverflow)
+        ));
+        assert!(matches!(
+            run("fn f(x = f()) { }\nf()"),
+            Err(RuntimeError::StackOverflow)
+        ));
+
+        // the parser only produces parameters, a tree built by hand may not
+        let node = |kind| Box::new(Node::new(kind, Span::default()));
+        let program = Node::new(
+            NodeKind::Program(vec![node(NodeKind::FunctionDeclaration(
+                node(NodeKind::Identifier("f".to_string())),
+                vec![node(NodeKind::Identifier("x".to_string()))],
+                node(NodeKind::BlockStatement(vec![])),
+            ))]),
+            Span::default(),
+        );
+        assert!(matches!(
+            Interpreter::new().interpret(&program).map_err(RuntimeError::without_location),
+            Err(RuntimeError::InvalidSignature(name)) if name == "f"
+        ));
+    }
+
+    #[test]
+    fn test_member_expression() {
+        assert_eq!(run_ok("let l = [1, 2, 3]\nl[1] = 5\nl[-1] + l[1]"), "8");
+        assert!(matches!(
+            run("[1][3]"),
+            Err(RuntimeError::IndexOutOfBounds(3, 1))
+        ));
+    }
+
+    #[test]
+    fn test_classes() {
+        let source = "
+            class Counter {
+              count = 0
+
+              fn init(start) {
+                self.count = start
+              }
+
+              fn increment() {
+                self.count += 1
+                return self.count
+              }
+            }
+            let counter = Counter(10)
+            counter.increment()
+            counter.increment()
+        ";
+        assert_eq!(run_ok(source), "12");
+    }
+
+    const CREATURES: &str = "
+        class Creature {
+          kind = \"unknown\"
+          legs = 0
+
+          fn init(kind) {
+            self.kind = kind
+          }
+
+          fn describe() {
+            return [self.kind, self.legs]
+          }
+        }
+
+        class Human from Creature {
+          age = 1
+          legs = 2
+
+          fn init(age) {
+            super(\"human\")
+            self.age = age
+          }
+
+          fn birthday() {
+            self.age += 1
+          }
+        }
+
+        class Person from Human {
+          name = null
+          static count = 0
+
+          fn init(name, age) {
+            super(age)
+            self.name = name
+            Person.count += 1
+          }
+
+          static fn default() {
+            return new Person(\"unknown\", 0)
+          }
+
+          fn describe() {
+            return [self.name, super.describe()]
+          }
+        }
+    ";
+
+    #[test]
+    fn test_inheritance() {
+        let source = format!(
+            "{}\nconst jeff = new Person(\"Jeff\", 24)\njeff.birthday()\nlet result = [jeff.kind, jeff.age, jeff.describe()]\nresult",
+            CREATURES
+        );
+        assert_eq!(
+            run_ok(&source),
+            "[\"human\", 25, [\"Jeff\", [\"human\", 2]]]"
+        );
+    }
+
+    #[test]
+    fn test_static_members() {
+        let source = format!(
+            "{}\nlet nobody = Person.default()\nPerson(\"Jeff\", 24)\nlet result = [nobody.name, Person.count]\nresult",
+            CREATURES
+        );
+        assert_eq!(run_ok(&source), "[\"unknown\", 2]");
+    }
+
+    #[test]
+    fn test_super_errors() {
+        assert!(matches!(
+            run("class A { fn init() { super() } }\nA()"),
+            Err(RuntimeError::MissingSuper)
+        ));
+        assert!(matches!(
+            run("class A { }\nclass B from A { fn f() { return super.g() } }\nlet b = B()\nb.f()"),
+            Err(RuntimeError::UndefinedProperty(class, method)) if class == "A" && method == "g"
+        ));
+        assert!(matches!(
+            run("class A { }\nclass B from A { fn init() { super(1) } }\nB()"),
+            Err(RuntimeError::ArityMismatch(class, 0, 1)) if class == "A"
+        ));
+        assert!(matches!(
+            run("let A = 5\nclass B from A { }"),
+            Err(RuntimeError::WrongType(..))
+        ));
+    }
+
+    #[test]
+    fn test_new_expression() {
+        assert_eq!(run_ok("class A { x = 1 }\nlet a = new A()\na.x"), "1");
+        assert!(matches!(
+            run("fn f() { }\nnew f()"),
+            Err(RuntimeError::NotConstructible(type_name)) if type_name == "function"
+        ));
+    }
+
+    #[test]
+    fn test_function_expression() {
+        let source = "
+            fn apply(f, x) {
+              return f(x)
+            }
+            let offset = 10
+            let add = fn(x) {
+              return x + offset
+            }
+            let result = [apply(add, 1), apply(fn(x) => x * 2, 4), (fn() => offset)(), add]
+            result
+        ";
+        assert_eq!(run_ok(source), "[11, 8, 10, <fn <anonymous>>]");
+    }
+
+    #[test]
+    fn test_parameters() {
+        let source = "
+            fn move(x, speed = x * 10, *rest) {
+              return [x, speed, rest]
+            }
+            let result = [move(1), move(1, 2, 3, 4), move(speed=30, x=2), move(2, speed=5)]
+            result
+        ";
+        assert_eq!(
+            run_ok(source),
+            "[[1, 10, []], [1, 2, [3, 4]], [2, 30, []], [2, 5, []]]"
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "
+            fn counter() {
+              let count = 0
+              return fn() {
+                count += 1
+                return count
+              }
+            }
+            let a = counter()
+            a()
+            let callbacks = [null, null]
+            for i in range(2) {
+              callbacks[i] = fn() => i
+            }
+            let result = [a(), counter()(), callbacks[0](), callbacks[1]()]
+            result
+        ";
+        assert_eq!(run_ok(source), "[2, 1, 0, 1]");
+    }
+
+    #[test]
+    fn test_objects() {
+        let source = "
+            let key = \"speed\"
+            let robot = { \"name\": \"R2\", [key]: 1.5, parts: { wheels: 4 }, }
+            robot.parts.wheels += 2
+            robot[\"name\"] = \"R3\"
+            robot.color = \"red\"
+            robot
+        ";
+        assert_eq!(
+            run_ok(source),
+            "{\"name\": \"R3\", \"speed\": 1.5, \"parts\": {\"wheels\": 6}, \"color\": \"red\"}"
+        );
+        assert_eq!(
+            run_ok("let keys = \"\"\nfor k in { a: 1, b: 2 } { keys += k }\nkeys"),
+            "\"ab\""
+        );
+        assert_eq!(
+            run_ok("[len({ a: 1 }), { a: [1] } == { a: [1] }]"),
+            "[1, true]"
+        );
+        assert!(matches!(
+            run("let o = { a: 1 }\no.b"),
+            Err(RuntimeError::UndefinedProperty(..))
+        ));
+        assert!(matches!(
+            run("let o = { [1]: 1 }"),
+            Err(RuntimeError::WrongType(..))
+        ));
+    }
+
+    #[test]
+    fn test_while_statement() {
+        let source = "
+            let i = 0
+            let odd = 0
+            while true {
+              i += 1
+              if i % 2 == 0 {
+                continue
+              }
+              if i > 9 {
+                break
+              }
+              odd += i
+            }
+            [i, odd]
+        ";
+        assert_eq!(run_ok(source), "[11, 25]");
+        assert_eq!(
+            run_ok("let n = 0\nfor i in range(10) { if i == 3 { break }\nn += i }\nn"),
+            "3"
+        );
+        assert_eq!(
+            run_ok("fn first(xs) { for x in xs { while true { return x } } }\nfirst([7, 8])"),
+            "7"
+        );
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        assert_eq!(
+            run_ok("let x = 5\nlet checks = [0 <= x < 10, 0 <= x < 5, 5 >= x >= 5]\nchecks"),
+            "[true, false, true]"
+        );
+        assert_eq!(run_ok("1 == 1 == 1"), "true");
+        // the middle operand runs once and a failed link skips the rest
+        let source = "
+            let calls = 0
+            fn mid() {
+              calls += 1
+              return 5
+            }
+            let a = 1 < mid() < 9
+            let b = 9 < mid() < missing
+            let result = [a, b, calls]
+            result
+        ";
+        assert_eq!(run_ok(source), "[true, false, 2]");
+    }
+
+    #[test]
+    fn test_not_and_is() {
+        let source = "
+            let xs = [1]
+            let same = xs
+            let found = null
+            let checks = [not found, not 1 == 2, xs is same, xs is [1], found is null, 0 is not null]
+            checks
+        ";
+        assert_eq!(run_ok(source), "[true, true, true, false, true, true]");
+        assert_eq!(run_ok("not true or true"), "true");
+        assert_eq!(run_ok("not (true or true)"), "false");
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut lexer = Lexer::new("import timer\ntimer.now()".to_string());
+        lexer.tokenize().unwrap();
+        let program = Parser::new(lexer.tokens).produce_ast().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.advance_time(1.5).unwrap();
+        assert_eq!(
+            interpreter.interpret(&program).unwrap(),
+            Value::Decimal(1.5)
+        );
+        assert_eq!(interpreter.time(), 1.5);
+
+        // only the vm can suspend a script until the time has come
+        assert!(matches!(
+            run("import timer\ntimer.run_and_wait(1)"),
+            Err(RuntimeError::CannotWait)
+        ));
+        assert!(matches!(
+            run("import timer\ntimer.run_and_wait(-1)"),
+            Err(RuntimeError::InvalidArgument(name, _)) if name == "run_and_wait"
+        ));
+    }
+}
@@ -0,0 +1,20 @@
+pub(crate) mod builtins;
+mod convert;
+mod environment;
+mod error;
+mod host;
+mod interpreter;
+mod module;
+mod operators;
+mod signature;
+mod value;
+
+pub use convert::{FromArgs, FromValue, IntoValue};
+pub use environment::Environment;
+pub use error::{Location, RuntimeError};
+pub use host::{HostClass, HostClassBuilder, HostObject, NativeClass};
+pub use interpreter::Interpreter;
+pub use module::{import_path, Module, ModuleResolver, Resolved};
+pub use operators::{binary_operation, unary_operation};
+pub use signature::Signature;
+pub use value::{BoundMethod, Class, Function, Instance, NativeFunction, Object, Value, ANONYMOUS};
@@ -0,0 +1,346 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    lexer::Lexer,
+    macros::bail,
+    parser::{Node, NodeKind, Parser},
+};
+
+use super::{error::RuntimeError, value::Value};
+
+const EXTENSION: &str = "pl";
+
+/// A namespace of values, either built into the game or loaded from a `.pl` file.
+pub struct Module {
+    pub name: String,
+    pub exports: HashMap<String, Value>,
+}
+
+impl Module {
+    pub fn new(name: &str, exports: Vec<(String, Value)>) -> Self {
+        Self {
+            name: name.to_string(),
+            exports: exports.into_iter().collect(),
+        }
+    }
+
+    pub fn export(&self, name: &str) -> Result<Value, RuntimeError> {
+        match self.exports.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => bail!(RuntimeError::UndefinedProperty(
+                format!("module {}", self.name),
+                name.to_string()
+            )),
+        }
+    }
+}
+
+/// What an import resolved to.
+pub enum Resolved {
+    Loaded(Rc<Module>),
+    /// A parsed user module that has to be run and handed back to `ModuleResolver::finish`.
+    Program(PathBuf, Node),
+}
+
+/// Finds modules for `import` statements.
+///
+/// Native modules are looked up by their dotted name first, anything else is a
+/// `.pl` file relative to the importing file (`import utils.math` is `utils/math.pl`).
+/// Every file is loaded only once.
+#[derive(Default)]
+pub struct ModuleResolver {
+    root: PathBuf, // where imports of scripts that don't come from a file are resolved
+    native: HashMap<String, Rc<Module>>,
+    cache: HashMap<PathBuf, Rc<Module>>,
+    loading: Vec<PathBuf>, // files that are being run right now, used to detect cycles
+}
+
+impl ModuleResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_root(&mut self, root: &Path) {
+        self.root = root.to_path_buf();
+    }
+
+    pub fn register(&mut self, module: Module) {
+        self.native.insert(module.name.clone(), Rc::new(module));
+    }
+
+    /// `path` is the dotted module path, `importer` is the file that contains the import.
+    pub fn resolve(
+        &mut self,
+        path: &str,
+        importer: Option<&Path>,
+    ) -> Result<Resolved, RuntimeError> {
+        if let Some(module) = self.native.get(path) {
+            return Ok(Resolved::Loaded(module.clone()));
+        }
+
+        let directory = match importer.and_then(Path::parent) {
+            Some(directory) => directory.to_path_buf(),
+            None => self.root.clone(),
+        };
+        let mut file = directory;
+        file.extend(path.split('.'));
+        file.set_extension(EXTENSION);
+        // the same file can be reached through different relative paths
+        let file = match fs::canonicalize(&file) {
+            Ok(file) => file,
+            Err(_) => bail!(RuntimeError::ModuleNotFound(path.to_string())),
+        };
+
+        if let Some(module) = self.cache.get(&file) {
+            return Ok(Resolved::Loaded(module.clone()));
+        }
+        if let Some(start) = self.loading.iter().position(|loading| *loading == file) {
+            let mut cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .map(|file| file.display().to_string())
+                .collect();
+            cycle.push(file.display().to_string());
+            bail!(RuntimeError::ImportCycle(cycle.join(" -> ")))
+        }
+
+        let program = Self::parse(&file)?;
+        self.loading.push(file.clone());
+        Ok(Resolved::Program(file, program))
+    }
+
+    /// Caches the exports of a module that was run after `resolve` returned it.
+    pub fn finish(&mut self, file: PathBuf, exports: Vec<(String, Value)>) -> Rc<Module> {
+        self.loading.retain(|loading| *loading != file);
+
+        let name = file
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let module = Rc::new(Module::new(&name, exports));
+        self.cache.insert(file, module.clone());
+        module
+    }
+
+    /// Forgets a module that failed to run, so importing it again reports the error again.
+    pub fn fail(&mut self, file: &Path) {
+        self.loading.retain(|loading| loading != file);
+    }
+
+    fn parse(file: &Path) -> Result<Node, RuntimeError> {
+        let invalid =
+            |message: String| RuntimeError::InvalidModule(file.display().to_string(), message);
+
+        let source = fs::read_to_string(file).map_err(|err| invalid(err.to_string()))?;
+        let mut lexer = Lexer::new(source);
+        lexer.tokenize().map_err(|err| invalid(err.to_string()))?;
+        Parser::new(lexer.tokens)
+            .produce_ast()
+            .map_err(|err| invalid(err.to_string()))
+    }
+}
+
+/// The dotted path and the name it binds for `import a.b.c`.
+pub fn import_path(node: &Node) -> Option<(String, String)> {
+    match &node.kind {
+        NodeKind::Identifier(name) => Some((name.clone(), name.clone())),
+        NodeKind::MemberExpression(object, property, false) => {
+            let (path, _) = import_path(object)?;
+            let (name, _) = import_path(property)?;
+            Some((format!("{}.{}", path, name), name))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Interpreter,
+        script::{Script, ScriptError},
+        vm::Vm,
+    };
+
+    // writes `files` into a fresh directory and returns the path of the first one
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pl-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, source) in files {
+            let file = root.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, source).unwrap();
+        }
+        root.join(files[0].0)
+    }
+
+    fn load(main: &Path) -> Result<Script, ScriptError> {
+        without_location(Script::load_file(Vm::new(), main))
+    }
+
+    // the tests match on what went wrong, not where
+    fn without_location(result: Result<Script, ScriptError>) -> Result<Script, ScriptError> {
+        result.map_err(|err| match err {
+            ScriptError::Runtime(err) => ScriptError::Runtime(err.without_location()),
+            err => err,
+        })
+    }
+
+    fn global(script: &mut Script, name: &str) -> Value {
+        script.vm().globals().lookup(name).unwrap()
+    }
+
+    #[test]
+    fn test_user_modules() {
+        let main = project(
+            "user-modules",
+            &[
+                (
+                    "main.pl",
+                    "import utils.vectors\nimport counter\nlet length = vectors.length(3, 4)\ncounter.bump()\nlet count = counter.bump()",
+                ),
+                (
+                    "utils/vectors.pl",
+                    "import math\nfn length(x, y) { return math.sqrt(x * x + y * y) }",
+                ),
+                ("counter.pl", "let count = 0\nfn bump() { count += 1\nreturn count }"),
+            ],
+        );
+
+        let mut script = load(&main).unwrap();
+        assert_eq!(global(&mut script, "length"), Value::Decimal(5.0));
+        // functions keep using the globals of the module they come from
+        assert_eq!(global(&mut script, "count"), Value::Integer(2));
+
+        let mut interpreter = Interpreter::new();
+        let program = ModuleResolver::parse(&main).unwrap();
+        interpreter.interpret_file(&program, &main).unwrap();
+        let globals = interpreter.globals();
+        assert_eq!(
+            globals.borrow().lookup("length").unwrap(),
+            Value::Decimal(5.0)
+        );
+        assert_eq!(globals.borrow().lookup("count").unwrap(), Value::Integer(2));
+    }
+
+    #[test]
+    fn test_modules_are_cached() {
+        let main = project(
+            "cached-modules",
+            &[
+                (
+                    "main.pl",
+                    "import a\nimport b\nlet same = a.shared == b.shared",
+                ),
+                ("a.pl", "import shared\nlet shared = shared.items"),
+                ("b.pl", "import shared\nlet shared = shared.items"),
+                ("shared.pl", "let items = [1, 2]"),
+            ],
+        );
+
+        let mut script = load(&main).unwrap();
+        assert_eq!(global(&mut script, "same"), Value::Bool(true));
+        let (a, b) = match (global(&mut script, "a"), global(&mut script, "b")) {
+            (Value::Module(a), Value::Module(b)) => (a, b),
+            _ => panic!("Expected modules"),
+        };
+        match (&a.exports["shared"], &b.exports["shared"]) {
+            (Value::List(left), Value::List(right)) => assert!(Rc::ptr_eq(left, right)),
+            _ => panic!("Expected lists"),
+        }
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let main = project(
+            "cyclic-modules",
+            &[
+                ("main.pl", "import a"),
+                ("a.pl", "import b"),
+                ("b.pl", "import a"),
+            ],
+        );
+        match load(&main) {
+            Err(ScriptError::Runtime(RuntimeError::ImportCycle(chain))) => {
+                assert!(chain.contains("a.pl -> "));
+                assert!(chain.ends_with("a.pl"));
+            }
+            _ => panic!("Expected an import cycle"),
+        }
+
+        let main = project("missing-modules", &[("main.pl", "import missing")]);
+        assert!(matches!(
+            load(&main),
+            Err(ScriptError::Runtime(RuntimeError::ModuleNotFound(path))) if path == "missing"
+        ));
+
+        let main = project(
+            "broken-modules",
+            &[("main.pl", "import broken"), ("broken.pl", "let = 1")],
+        );
+        assert!(matches!(
+            load(&main),
+            Err(ScriptError::Runtime(RuntimeError::InvalidModule(..)))
+        ));
+    }
+
+    #[test]
+    fn test_import_forms() {
+        let main = project(
+            "import-forms",
+            &[
+                (
+                    "main.pl",
+                    "import math as m\n\
+                     import math.{floor, sqrt as root,}\n\
+                     import shapes.*\n\
+                     let result = [m.floor(2.5), floor(3.5), root(16), area(2), SIDES]",
+                ),
+                (
+                    "shapes.pl",
+                    "const SIDES = 4\nfn area(side) { return side * side }",
+                ),
+            ],
+        );
+        let expected = "[2, 3, 4.0, 4, 4]";
+
+        let mut script = load(&main).unwrap();
+        assert_eq!(format!("{:?}", global(&mut script, "result")), expected);
+
+        let mut interpreter = Interpreter::new();
+        let program = ModuleResolver::parse(&main).unwrap();
+        interpreter.interpret_file(&program, &main).unwrap();
+        let result = interpreter.globals().borrow().lookup("result").unwrap();
+        assert_eq!(format!("{:?}", result), expected);
+
+        assert!(matches!(
+            without_location(Script::load_into(Vm::new(), "import math.{cbrt}")),
+            Err(ScriptError::Runtime(RuntimeError::UndefinedProperty(module, name)))
+                if module == "module math" && name == "cbrt"
+        ));
+        assert!(matches!(
+            Script::load_into(Vm::new(), "fn f() { import math.* }"),
+            Err(ScriptError::Compile(_))
+        ));
+    }
+
+    #[test]
+    fn test_native_modules() {
+        let mut vm = Vm::new();
+        vm.modules().register(Module::new(
+            "robot",
+            vec![("PORTS".to_string(), Value::Integer(4))],
+        ));
+        let mut script = Script::load_into(vm, "import robot\nimport math\nlet ports = robot.PORTS\nlet root = math.floor(math.sqrt(17))").unwrap();
+        assert_eq!(global(&mut script, "ports"), Value::Integer(4));
+        assert_eq!(global(&mut script, "root"), Value::Integer(4));
+        assert!(matches!(
+            without_location(Script::load_into(Vm::new(), "import math\nmath.PI = 3")),
+            Err(ScriptError::Runtime(RuntimeError::ReadOnlyProperty(..)))
+        ));
+    }
+}
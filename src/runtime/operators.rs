@@ -0,0 +1,515 @@
+use std::cmp::Ordering;
+
+use crate::{
+    macros::bail,
+    parser::{BinaryOperator, UnaryOperator},
+};
+
+use super::{error::RuntimeError, value::Value};
+
+// strings (in bytes) and lists can't be repeated into anything longer than this
+const MAX_REPEAT_LENGTH: usize = 1 << 24;
+
+pub fn binary_symbol(operator: BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Plus => "+",
+        BinaryOperator::Minus => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::LessThan => "<",
+        BinaryOperator::GreaterThan => ">",
+        BinaryOperator::LessThanOrEqual => "<=",
+        BinaryOperator::GreaterThanOrEqual => ">=",
+        BinaryOperator::IsEquals => "==",
+        BinaryOperator::NotEquals => "!=",
+        BinaryOperator::Is => "is",
+        BinaryOperator::IsNot => "is not",
+    }
+}
+
+pub fn unary_symbol(operator: UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::Increment => "++",
+        UnaryOperator::Decrement => "--",
+        UnaryOperator::Plus => "+",
+        UnaryOperator::Minus => "-",
+        UnaryOperator::Negation => "!",
+    }
+}
+
+/// Applies a binary operator following Python-like semantics:
+/// integers are promoted to floats when mixed with them, `/` always
+/// produces a float and `%` takes the sign of the divisor.
+pub fn binary_operation(
+    operator: BinaryOperator,
+    left: &Value,
+    right: &Value,
+) -> Result<Value, RuntimeError> {
+    match operator {
+        BinaryOperator::Plus => add(left, right),
+        BinaryOperator::Minus => arithmetic(operator, left, right),
+        BinaryOperator::Multiply => multiply(left, right),
+        BinaryOperator::Divide => divide(left, right),
+        BinaryOperator::Modulo => modulo(left, right),
+        BinaryOperator::LessThan => Ok(Value::Bool(matches!(
+            compare(operator, left, right)?,
+            Some(Ordering::Less)
+        ))),
+        BinaryOperator::GreaterThan => Ok(Value::Bool(matches!(
+            compare(operator, left, right)?,
+            Some(Ordering::Greater)
+        ))),
+        BinaryOperator::LessThanOrEqual => Ok(Value::Bool(matches!(
+            compare(operator, left, right)?,
+            Some(Ordering::Less | Ordering::Equal)
+        ))),
+        BinaryOperator::GreaterThanOrEqual => Ok(Value::Bool(matches!(
+            compare(operator, left, right)?,
+            Some(Ordering::Greater | Ordering::Equal)
+        ))),
+        BinaryOperator::IsEquals => Ok(Value::Bool(left.is_equal(right))),
+        BinaryOperator::NotEquals => Ok(Value::Bool(!left.is_equal(right))),
+        BinaryOperator::Is => Ok(Value::Bool(left.is_same(right))),
+        BinaryOperator::IsNot => Ok(Value::Bool(!left.is_same(right))),
+    }
+}
+
+/// Applies a unary operator that doesn't modify its operand,
+/// `++` and `--` are assignments and have to be handled by the caller.
+pub fn unary_operation(operator: UnaryOperator, operand: &Value) -> Result<Value, RuntimeError> {
+    match (operator, operand) {
+        (UnaryOperator::Negation, value) => Ok(Value::Bool(!value.is_truthy())),
+        (UnaryOperator::Plus, Value::Integer(_) | Value::Decimal(_)) => Ok(operand.clone()),
+        (UnaryOperator::Minus, Value::Integer(value)) => match value.checked_neg() {
+            Some(value) => Ok(Value::Integer(value)),
+            None => bail!(RuntimeError::IntegerOverflow),
+        },
+        (UnaryOperator::Minus, Value::Decimal(value)) => Ok(Value::Decimal(-value)),
+        (operator, value) => bail!(RuntimeError::InvalidOperand(
+            unary_symbol(operator).to_string(),
+            value.type_name()
+        )),
+    }
+}
+
+fn type_mismatch(operator: BinaryOperator, left: &Value, right: &Value) -> RuntimeError {
+    RuntimeError::TypeMismatch(
+        binary_symbol(operator).to_string(),
+        left.type_name(),
+        right.type_name(),
+    )
+}
+
+fn checked(result: Option<i64>) -> Result<Value, RuntimeError> {
+    match result {
+        Some(value) => Ok(Value::Integer(value)),
+        None => bail!(RuntimeError::IntegerOverflow),
+    }
+}
+
+fn as_decimal(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(value) => Some(*value as f64),
+        Value::Decimal(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn add(left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+    match (left, right) {
+        (Value::String(l), Value::String(r)) => Ok(Value::String(format!("{}{}", l, r))),
+        (Value::List(l), Value::List(r)) => {
+            let mut items = l.borrow().clone();
+            items.extend(r.borrow().iter().cloned());
+            Ok(Value::list(items))
+        }
+        _ => arithmetic(BinaryOperator::Plus, left, right),
+    }
+}
+
+fn multiply(left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+    match (left, right) {
+        (Value::String(value), Value::Integer(times))
+        | (Value::Integer(times), Value::String(value)) => {
+            match repeated_length(value.len(), *times)? {
+                0 => Ok(Value::String(String::new())),
+                _ => Ok(Value::String(value.repeat(*times as usize))),
+            }
+        }
+        (Value::List(items), Value::Integer(times))
+        | (Value::Integer(times), Value::List(items)) => {
+            let items = items.borrow();
+            let length = repeated_length(items.len(), *times)?;
+            Ok(Value::list(
+                items.iter().cloned().cycle().take(length).collect(),
+            ))
+        }
+        _ => arithmetic(BinaryOperator::Multiply, left, right),
+    }
+}
+
+// length of something `length` long repeated `times` times, negative counts repeat nothing
+fn repeated_length(length: usize, times: i64) -> Result<usize, RuntimeError> {
+    let times = usize::try_from(times.max(0)).unwrap_or(usize::MAX);
+    match length.checked_mul(times) {
+        Some(total) if total <= MAX_REPEAT_LENGTH => Ok(total),
+        Some(_) => bail!(RuntimeError::TooLarge("*".to_string(), MAX_REPEAT_LENGTH)),
+        None => bail!(RuntimeError::IntegerOverflow),
+    }
+}
+
+// `+`, `-` and `*` on numbers
+fn arithmetic(
+    operator: BinaryOperator,
+    left: &Value,
+    right: &Value,
+) -> Result<Value, RuntimeError> {
+    if let (Value::Integer(l), Value::Integer(r)) = (left, right) {
+        return match operator {
+            BinaryOperator::Plus => checked(l.checked_add(*r)),
+            BinaryOperator::Minus => checked(l.checked_sub(*r)),
+            _ => checked(l.checked_mul(*r)),
+        };
+    }
+
+    match (as_decimal(left), as_decimal(right)) {
+        (Some(l), Some(r)) => Ok(Value::Decimal(match operator {
+            BinaryOperator::Plus => l + r,
+            BinaryOperator::Minus => l - r,
+            _ => l * r,
+        })),
+        _ => Err(type_mismatch(operator, left, right)),
+    }
+}
+
+fn divide(left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+    match (as_decimal(left), as_decimal(right)) {
+        (Some(_), Some(0.0)) => bail!(RuntimeError::DivisionByZero),
+        (Some(l), Some(r)) => Ok(Value::Decimal(l / r)),
+        _ => Err(type_mismatch(BinaryOperator::Divide, left, right)),
+    }
+}
+
+fn modulo(left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+    if let (Value::Integer(l), Value::Integer(r)) = (left, right) {
+        if *r == 0 {
+            bail!(RuntimeError::DivisionByZero)
+        }
+        let remainder = match l.checked_rem(*r) {
+            Some(remainder) => remainder,
+            None => bail!(RuntimeError::IntegerOverflow),
+        };
+        // the result takes the sign of the divisor, e.g. -7 % 3 == 2
+        if remainder != 0 && (remainder < 0) != (*r < 0) {
+            return Ok(Value::Integer(remainder + r));
+        }
+        return Ok(Value::Integer(remainder));
+    }
+
+    match (as_decimal(left), as_decimal(right)) {
+        (Some(_), Some(0.0)) => bail!(RuntimeError::DivisionByZero),
+        (Some(l), Some(r)) => {
+            let remainder = l % r;
+            if remainder != 0.0 && (remainder < 0.0) != (r < 0.0) {
+                return Ok(Value::Decimal(remainder + r));
+            }
+            Ok(Value::Decimal(remainder))
+        }
+        _ => Err(type_mismatch(BinaryOperator::Modulo, left, right)),
+    }
+}
+
+// ordering of numbers, strings and lists (compared item by item)
+// `None` when the values are unordered, e.g. one of them is NaN
+fn compare(
+    operator: BinaryOperator,
+    left: &Value,
+    right: &Value,
+) -> Result<Option<Ordering>, RuntimeError> {
+    match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => Ok(Some(l.cmp(r))),
+        (Value::String(l), Value::String(r)) => Ok(Some(l.cmp(r))),
+        (Value::List(l), Value::List(r)) => {
+            let (l, r) = (l.borrow(), r.borrow());
+            for (l, r) in l.iter().zip(r.iter()) {
+                match compare(operator, l, r)? {
+                    Some(Ordering::Equal) => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(Some(l.len().cmp(&r.len())))
+        }
+        _ => match (as_decimal(left), as_decimal(right)) {
+            (Some(l), Some(r)) => Ok(l.partial_cmp(&r)),
+            _ => Err(type_mismatch(operator, left, right)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn test_binary_operations() {
+        let cases: Vec<(Value, BinaryOperator, Value, Value)> = vec![
+            (
+                Value::Integer(2),
+                BinaryOperator::Plus,
+                Value::Integer(3),
+                Value::Integer(5),
+            ),
+            (
+                Value::Integer(2),
+                BinaryOperator::Plus,
+                Value::Decimal(0.5),
+                Value::Decimal(2.5),
+            ),
+            (
+                Value::Decimal(2.0),
+                BinaryOperator::Minus,
+                Value::Integer(3),
+                Value::Decimal(-1.0),
+            ),
+            (
+                Value::Integer(4),
+                BinaryOperator::Multiply,
+                Value::Integer(3),
+                Value::Integer(12),
+            ),
+            (
+                Value::Integer(7),
+                BinaryOperator::Divide,
+                Value::Integer(2),
+                Value::Decimal(3.5),
+            ),
+            (
+                Value::Integer(-7),
+                BinaryOperator::Modulo,
+                Value::Integer(3),
+                Value::Integer(2),
+            ),
+            (
+                Value::Integer(7),
+                BinaryOperator::Modulo,
+                Value::Integer(-3),
+                Value::Integer(-2),
+            ),
+            (
+                Value::Decimal(5.5),
+                BinaryOperator::Modulo,
+                Value::Integer(2),
+                Value::Decimal(1.5),
+            ),
+            (
+                string("ab"),
+                BinaryOperator::Plus,
+                string("cd"),
+                string("abcd"),
+            ),
+            (
+                string("ab"),
+                BinaryOperator::Multiply,
+                Value::Integer(3),
+                string("ababab"),
+            ),
+            (
+                Value::list(vec![Value::Integer(1)]),
+                BinaryOperator::Plus,
+                Value::list(vec![Value::Integer(2)]),
+                Value::list(vec![Value::Integer(1), Value::Integer(2)]),
+            ),
+            (
+                Value::list(vec![Value::Integer(0)]),
+                BinaryOperator::Multiply,
+                Value::Integer(2),
+                Value::list(vec![Value::Integer(0), Value::Integer(0)]),
+            ),
+            (
+                Value::Integer(1),
+                BinaryOperator::LessThan,
+                Value::Decimal(1.5),
+                Value::Bool(true),
+            ),
+            (
+                string("b"),
+                BinaryOperator::GreaterThan,
+                string("a"),
+                Value::Bool(true),
+            ),
+            (
+                Value::Integer(1),
+                BinaryOperator::IsEquals,
+                Value::Decimal(1.0),
+                Value::Bool(true),
+            ),
+            (
+                Value::Integer(1),
+                BinaryOperator::IsEquals,
+                string("1"),
+                Value::Bool(false),
+            ),
+            (
+                Value::Null,
+                BinaryOperator::IsEquals,
+                Value::Null,
+                Value::Bool(true),
+            ),
+            (
+                Value::list(vec![Value::Integer(1), string("a")]),
+                BinaryOperator::IsEquals,
+                Value::list(vec![Value::Integer(1), string("a")]),
+                Value::Bool(true),
+            ),
+            (
+                Value::list(vec![Value::Integer(1)]),
+                BinaryOperator::NotEquals,
+                Value::list(vec![Value::Integer(2)]),
+                Value::Bool(true),
+            ),
+            (
+                Value::list(vec![Value::Integer(1), Value::Integer(2)]),
+                BinaryOperator::LessThan,
+                Value::list(vec![Value::Integer(1), Value::Integer(3)]),
+                Value::Bool(true),
+            ),
+            (
+                Value::Integer(2),
+                BinaryOperator::LessThanOrEqual,
+                Value::Decimal(2.0),
+                Value::Bool(true),
+            ),
+            (
+                string("a"),
+                BinaryOperator::GreaterThanOrEqual,
+                string("b"),
+                Value::Bool(false),
+            ),
+            (
+                Value::Decimal(f64::NAN),
+                BinaryOperator::LessThanOrEqual,
+                Value::Decimal(f64::NAN),
+                Value::Bool(false),
+            ),
+            (
+                Value::list(vec![Value::Integer(1)]),
+                BinaryOperator::GreaterThanOrEqual,
+                Value::list(vec![Value::Integer(1)]),
+                Value::Bool(true),
+            ),
+            (
+                Value::Null,
+                BinaryOperator::Is,
+                Value::Null,
+                Value::Bool(true),
+            ),
+            (
+                Value::Integer(0),
+                BinaryOperator::IsNot,
+                Value::Null,
+                Value::Bool(true),
+            ),
+            (
+                Value::Integer(1),
+                BinaryOperator::Is,
+                Value::Decimal(1.0),
+                Value::Bool(false),
+            ),
+            (
+                Value::list(vec![]),
+                BinaryOperator::Is,
+                Value::list(vec![]),
+                Value::Bool(false),
+            ),
+        ];
+
+        for (left, operator, right, expected) in cases {
+            match binary_operation(operator, &left, &right) {
+                Ok(value) => assert_eq!(
+                    value,
+                    expected,
+                    "{:?} {} {:?}",
+                    left,
+                    binary_symbol(operator),
+                    right
+                ),
+                Err(err) => panic!(
+                    "{:?} {} {:?}: {}",
+                    left,
+                    binary_symbol(operator),
+                    right,
+                    err
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_errors() {
+        assert!(matches!(
+            binary_operation(
+                BinaryOperator::Divide,
+                &Value::Integer(1),
+                &Value::Integer(0)
+            ),
+            Err(RuntimeError::DivisionByZero)
+        ));
+        assert!(matches!(
+            binary_operation(
+                BinaryOperator::Divide,
+                &Value::Decimal(1.0),
+                &Value::Decimal(0.0)
+            ),
+            Err(RuntimeError::DivisionByZero)
+        ));
+        assert!(matches!(
+            binary_operation(
+                BinaryOperator::Modulo,
+                &Value::Integer(1),
+                &Value::Integer(0)
+            ),
+            Err(RuntimeError::DivisionByZero)
+        ));
+        assert!(matches!(
+            binary_operation(
+                BinaryOperator::Plus,
+                &Value::Integer(i64::MAX),
+                &Value::Integer(1)
+            ),
+            Err(RuntimeError::IntegerOverflow)
+        ));
+        assert!(matches!(
+            binary_operation(BinaryOperator::Plus, &string("a"), &Value::Integer(1)),
+            Err(RuntimeError::TypeMismatch(..))
+        ));
+        assert!(matches!(
+            binary_operation(BinaryOperator::LessThan, &Value::Null, &Value::Integer(1)),
+            Err(RuntimeError::TypeMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn test_unary_operations() {
+        assert_eq!(
+            unary_operation(UnaryOperator::Minus, &Value::Integer(5)).unwrap(),
+            Value::Integer(-5)
+        );
+        assert_eq!(
+            unary_operation(UnaryOperator::Minus, &Value::Decimal(0.5)).unwrap(),
+            Value::Decimal(-0.5)
+        );
+        assert_eq!(
+            unary_operation(UnaryOperator::Negation, &Value::list(vec![])).unwrap(),
+            Value::Bool(true)
+        );
+        assert!(matches!(
+            unary_operation(UnaryOperator::Minus, &string("a")),
+            Err(RuntimeError::InvalidOperand(..))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    macros::bail,
+    parser::{Node, NodeKind},
+};
+
+use super::{error::RuntimeError, value::Value};
+
+/// Parameters of a script function and how call arguments are matched to them.
+#[derive(Debug, Clone, Default)]
+pub struct Signature {
+    pub params: Vec<String>, // named parameters, the rest parameter isn't one of them
+    pub required: usize,     // leading parameters without a default value
+    pub rest: Option<String>, // `*rest` collects the extra positional arguments into a list
+}
+
+impl Signature {
+    /// Reads the `NodeKind::Parameter`s of a function together with their default values,
+    /// `None` if one of them is something else.
+    pub fn from_nodes(params: &[Box<Node>]) -> Option<(Signature, Vec<Option<Node>>)> {
+        let mut signature = Signature::default();
+        let mut defaults = vec![];
+        for param in params {
+            match &param.kind {
+                NodeKind::Parameter(name, _, true) => signature.rest = Some(name.clone()),
+                NodeKind::Parameter(name, default, false) => {
+                    if default.is_none() {
+                        signature.required += 1;
+                    }
+                    signature.params.push(name.clone());
+                    defaults.push(default.as_deref().cloned());
+                }
+                _ => return None,
+            }
+        }
+        Some((signature, defaults))
+    }
+
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Whether `count` positional arguments are enough and not too many.
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.required && (self.rest.is_some() || count <= self.params.len())
+    }
+
+    /// Matches the arguments of a call to the parameters. There is one value for
+    /// every parameter, `None` when it takes its default value, followed by the
+    /// list for the rest parameter if there is one.
+    pub fn bind(
+        &self,
+        function_name: &str,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Result<Vec<Option<Value>>, RuntimeError> {
+        let count = args.len();
+        if named.is_empty() && !self.accepts(count) {
+            let expected = match count < self.required {
+                true => self.required,
+                false => self.params.len(),
+            };
+            bail!(RuntimeError::ArityMismatch(
+                function_name.to_string(),
+                expected,
+                count
+            ))
+        }
+
+        let mut values: Vec<Option<Value>> = vec![None; self.params.len()];
+        let mut args = args.into_iter();
+        for (value, arg) in values.iter_mut().zip(args.by_ref()) {
+            *value = Some(arg);
+        }
+        let extra: Vec<Value> = args.collect();
+        if !extra.is_empty() && self.rest.is_none() {
+            bail!(RuntimeError::ArityMismatch(
+                function_name.to_string(),
+                self.params.len(),
+                count
+            ))
+        }
+
+        for (name, value) in named {
+            let index = match self.params.iter().position(|param| *param == name) {
+                Some(index) => index,
+                None => bail!(RuntimeError::InvalidArgument(
+                    function_name.to_string(),
+                    format!("there is no parameter named '{}'", name)
+                )),
+            };
+            if values[index].is_some() {
+                bail!(RuntimeError::InvalidArgument(
+                    function_name.to_string(),
+                    format!("'{}' got more than one value", name)
+                ))
+            }
+            values[index] = Some(value);
+        }
+
+        let missing = self.params[..self.required]
+            .iter()
+            .zip(&values)
+            .find(|(_, value)| value.is_none());
+        if let Some((name, _)) = missing {
+            bail!(RuntimeError::InvalidArgument(
+                function_name.to_string(),
+                format!("missing a value for '{}'", name)
+            ))
+        }
+
+        if self.rest.is_some() {
+            values.push(Some(Value::list(extra)));
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature() -> Signature {
+        Signature {
+            params: vec!["x".to_string(), "speed".to_string()],
+            required: 1,
+            rest: Some("rest".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_bind() {
+        let values = signature()
+            .bind("move", vec![Value::Integer(1)], vec![])
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![Some(Value::Integer(1)), None, Some(Value::list(vec![]))]
+        );
+
+        let args = vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)];
+        let values = signature().bind("move", args, vec![]).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Some(Value::Integer(1)),
+                Some(Value::Integer(2)),
+                Some(Value::list(vec![Value::Integer(3)]))
+            ]
+        );
+
+        let named = vec![("x".to_string(), Value::Integer(5))];
+        let values = signature().bind("move", vec![], named).unwrap();
+        assert_eq!(values[0], Some(Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_bind_errors() {
+        assert!(matches!(
+            signature().bind("move", vec![], vec![]),
+            Err(RuntimeError::ArityMismatch(_, 1, 0))
+        ));
+        let fixed = Signature {
+            rest: None,
+            ..signature()
+        };
+        assert!(matches!(
+            fixed.bind("move", vec![Value::Null; 3], vec![]),
+            Err(RuntimeError::ArityMismatch(_, 2, 3))
+        ));
+
+        let named = |name: &str| vec![(name.to_string(), Value::Null)];
+        for (args, named) in [
+            (vec![], named("speed")),
+            (vec![Value::Null], named("x")),
+            (vec![Value::Null], named("rest")),
+        ] {
+            assert!(matches!(
+                signature().bind("move", args, named),
+                Err(RuntimeError::InvalidArgument(..))
+            ));
+        }
+    }
+}
@@ -0,0 +1,602 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{
+    macros::bail,
+    parser::Node,
+    vm::{Closure, CompiledFunction},
+};
+
+use super::{
+    environment::Environment, error::RuntimeError, host::HostObject, module::Module,
+    signature::Signature,
+};
+
+#[derive(Clone)]
+pub enum Value {
+    Integer(i64),
+    Decimal(f64),
+    String(String),
+    Bool(bool),
+    Null,
+    List(Rc<RefCell<Vec<Value>>>),
+    Object(Rc<RefCell<Object>>),
+    Function(Rc<Function>),
+    NativeFunction(Rc<NativeFunction>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    HostObject(Rc<RefCell<HostObject>>),
+    Module(Rc<Module>),
+}
+
+/// Entries of an object literal, in the order they were inserted.
+#[derive(Default)]
+pub struct Object {
+    entries: Vec<(String, Value)>,
+}
+
+impl Object {
+    /// Builds an object out of evaluated key/value pairs, keys have to be strings.
+    pub fn from_pairs(pairs: Vec<(Value, Value)>) -> Result<Self, RuntimeError> {
+        let mut object = Self::default();
+        for (key, value) in pairs {
+            match key {
+                Value::String(key) => object.insert(key, value),
+                key => bail!(RuntimeError::WrongType(
+                    "Object key".to_string(),
+                    "string".to_string(),
+                    key.type_name()
+                )),
+            }
+        }
+        Ok(object)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn insert(&mut self, key: String, value: Value) {
+        match self.entries.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, current)) => *current = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, Value)] {
+        &self.entries
+    }
+}
+
+/// Name given to functions created by a function expression.
+pub const ANONYMOUS: &str = "<anonymous>";
+
+pub struct Function {
+    pub name: String,
+    pub signature: Signature,
+    pub defaults: Vec<Option<Node>>, // default value of every parameter
+    pub body: Node,                  // block_statement
+    pub closure: Rc<RefCell<Environment>>,
+    pub file: Option<Rc<Path>>, // imports inside of the function are relative to it
+}
+
+pub type NativeFn = dyn Fn(Vec<Value>) -> Result<Value, RuntimeError>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Option<usize>, // None means the function is variadic
+    pub function: Box<NativeFn>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value, // the instance that will be available as `self`
+    pub method: Value,
+}
+
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>, // class after `from`
+    pub fields: Vec<(String, Value)>,  // field name, default value
+    pub methods: HashMap<String, Value>,
+    pub statics: RefCell<HashMap<String, Value>>, // static fields and methods
+}
+
+impl Class {
+    /// Looks the method up in the class first and then along its `from` chain.
+    pub fn find_method(&self, name: &str) -> Option<Value> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    pub fn find_static(&self, name: &str) -> Option<Value> {
+        match self.statics.borrow().get(name) {
+            Some(member) => Some(member.clone()),
+            None => self.superclass.as_ref()?.find_static(name),
+        }
+    }
+
+    /// Resolves `super.name` (or `super(...)` for `init`) for a method of a
+    /// subclass of `self`, the result is bound to `receiver`.
+    pub fn bind_super(&self, receiver: Value, name: &str) -> Result<Value, RuntimeError> {
+        match self.find_method(name) {
+            Some(method) => Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                receiver,
+                method,
+            }))),
+            // classes without `init` can still be initialized without arguments
+            None if name == "init" => Ok(Value::native(&self.name, Some(0), |_| Ok(Value::Null))),
+            None => bail!(RuntimeError::UndefinedProperty(
+                self.name.clone(),
+                name.to_string()
+            )),
+        }
+    }
+
+    // field defaults of the whole chain, subclasses override their parents,
+    // lists and objects are copied so instances don't change each other's defaults
+    fn field_defaults(&self, fields: &mut HashMap<String, Value>, copies: &mut Copies) {
+        if let Some(superclass) = &self.superclass {
+            superclass.field_defaults(fields, copies);
+        }
+        for (name, value) in &self.fields {
+            fields.insert(name.clone(), value.deep_copy(copies));
+        }
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    /// Creates an instance with its own copy of the field defaults of the class
+    /// and all of its superclasses.
+    pub fn new(class: &Rc<Class>) -> Self {
+        let mut fields = HashMap::new();
+        class.field_defaults(&mut fields, &mut HashMap::new());
+        Self {
+            class: class.clone(),
+            fields,
+        }
+    }
+}
+
+// lists and objects that were already copied, by their address, so shared and cyclic ones
+// stay shared and cyclic in the copy
+type Copies = HashMap<usize, Value>;
+
+impl Value {
+    pub fn list(items: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn object(object: Object) -> Self {
+        Value::Object(Rc::new(RefCell::new(object)))
+    }
+
+    pub fn native(
+        name: &str,
+        arity: Option<usize>,
+        function: impl Fn(Vec<Value>) -> Result<Value, RuntimeError> + 'static,
+    ) -> Self {
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }))
+    }
+
+    // copies lists and objects all the way down, anything else is shared with `self`
+    fn deep_copy(&self, copies: &mut Copies) -> Value {
+        match self {
+            Value::List(items) => {
+                let address = Rc::as_ptr(items) as usize;
+                if let Some(copy) = copies.get(&address) {
+                    return copy.clone();
+                }
+                let copy = Value::list(vec![]);
+                copies.insert(address, copy.clone());
+                let copied: Vec<Value> = items
+                    .borrow()
+                    .iter()
+                    .map(|item| item.deep_copy(copies))
+                    .collect();
+                if let Value::List(copy) = &copy {
+                    *copy.borrow_mut() = copied;
+                }
+                copy
+            }
+            Value::Object(object) => {
+                let address = Rc::as_ptr(object) as usize;
+                if let Some(copy) = copies.get(&address) {
+                    return copy.clone();
+                }
+                let copy = Value::object(Object::default());
+                copies.insert(address, copy.clone());
+                let copied: Vec<(String, Value)> = object
+                    .borrow()
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.deep_copy(copies)))
+                    .collect();
+                if let Value::Object(copy) = &copy {
+                    copy.borrow_mut().entries = copied;
+                }
+                copy
+            }
+            value => value.clone(),
+        }
+    }
+
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Integer(_) => "int".to_string(),
+            Value::Decimal(_) => "float".to_string(),
+            Value::String(_) => "string".to_string(),
+            Value::Bool(_) => "bool".to_string(),
+            Value::Null => "null".to_string(),
+            Value::List(_) => "list".to_string(),
+            Value::Object(_) => "object".to_string(),
+            Value::Function(_)
+            | Value::NativeFunction(_)
+            | Value::CompiledFunction(_)
+            | Value::Closure(_)
+            | Value::BoundMethod(_) => "function".to_string(),
+            Value::Class(_) => "class".to_string(),
+            Value::Instance(instance) => instance.borrow().class.name.clone(),
+            Value::HostObject(object) => object.borrow().class.name.clone(),
+            Value::Module(_) => "module".to_string(),
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Integer(value) => *value != 0,
+            Value::Decimal(value) => *value != 0.0,
+            Value::String(value) => !value.is_empty(),
+            Value::Bool(value) => *value,
+            Value::Null => false,
+            Value::List(items) => !items.borrow().is_empty(),
+            Value::Object(object) => !object.borrow().entries.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// Structural equality for primitives and lists, identity for everything else.
+    pub fn is_equal(&self, other: &Value) -> bool {
+        self.is_equal_within(other, &mut HashSet::new())
+    }
+
+    // `compared` holds the addresses of list and object pairs that are being compared already,
+    // meeting one of them again means they contain themselves and are equal as far as that goes
+    fn is_equal_within(&self, other: &Value, compared: &mut HashSet<(usize, usize)>) -> bool {
+        match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) => left == right,
+            (Value::Decimal(left), Value::Decimal(right)) => left == right,
+            (Value::Integer(left), Value::Decimal(right))
+            | (Value::Decimal(right), Value::Integer(left)) => *left as f64 == *right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Null, Value::Null) => true,
+            (Value::List(left), Value::List(right)) => {
+                let pair = (Rc::as_ptr(left) as usize, Rc::as_ptr(right) as usize);
+                if Rc::ptr_eq(left, right) || !compared.insert(pair) {
+                    return true;
+                }
+                let (left, right) = (left.borrow(), right.borrow());
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| left.is_equal_within(right, compared))
+            }
+            (Value::Object(left), Value::Object(right)) => {
+                let pair = (Rc::as_ptr(left) as usize, Rc::as_ptr(right) as usize);
+                if Rc::ptr_eq(left, right) || !compared.insert(pair) {
+                    return true;
+                }
+                let (left, right) = (left.borrow(), right.borrow());
+                left.entries.len() == right.entries.len()
+                    && left.entries.iter().all(|(key, value)| {
+                        right
+                            .get(key)
+                            .is_some_and(|other| value.is_equal_within(other, compared))
+                    })
+            }
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::NativeFunction(left), Value::NativeFunction(right)) => Rc::ptr_eq(left, right),
+            (Value::CompiledFunction(left), Value::CompiledFunction(right)) => {
+                Rc::ptr_eq(left, right)
+            }
+            (Value::Closure(left), Value::Closure(right)) => Rc::ptr_eq(left, right),
+            (Value::BoundMethod(left), Value::BoundMethod(right)) => {
+                left.method.is_equal_within(&right.method, compared)
+                    && left.receiver.is_equal_within(&right.receiver, compared)
+            }
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            (Value::HostObject(left), Value::HostObject(right)) => Rc::ptr_eq(left, right),
+            (Value::Module(left), Value::Module(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+
+    /// Identity behind `is`: lists and objects have to be the same one,
+    /// primitives have to be of the same type and equal.
+    pub fn is_same(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::List(left), Value::List(right)) => Rc::ptr_eq(left, right),
+            (Value::Object(left), Value::Object(right)) => Rc::ptr_eq(left, right),
+            (Value::Integer(_), Value::Decimal(_)) | (Value::Decimal(_), Value::Integer(_)) => {
+                false
+            }
+            _ => self.is_equal(other),
+        }
+    }
+
+    pub fn iterate(&self) -> Result<Vec<Value>, RuntimeError> {
+        match self {
+            Value::List(items) => Ok(items.borrow().clone()),
+            Value::String(value) => Ok(value
+                .chars()
+                .map(|ch| Value::String(ch.to_string()))
+                .collect()),
+            // objects are iterated over their keys
+            Value::Object(object) => Ok(object
+                .borrow()
+                .entries
+                .iter()
+                .map(|(key, _)| Value::String(key.clone()))
+                .collect()),
+            value => bail!(RuntimeError::NotIterable(value.type_name())),
+        }
+    }
+
+    pub fn get_property(&self, name: &str) -> Result<Value, RuntimeError> {
+        if let Value::HostObject(object) = self {
+            return HostObject::get(object, name);
+        }
+        if let Value::Module(module) = self {
+            return module.export(name);
+        }
+        if let Value::Object(object) = self {
+            if let Some(value) = object.borrow().get(name) {
+                return Ok(value.clone());
+            }
+        }
+        if let Value::Instance(instance) = self {
+            let instance_ref = instance.borrow();
+            if let Some(value) = instance_ref.fields.get(name) {
+                return Ok(value.clone());
+            }
+            if let Some(method) = instance_ref.class.find_method(name) {
+                return Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                    receiver: self.clone(),
+                    method,
+                })));
+            }
+        }
+        if let Value::Class(class) = self {
+            if let Some(member) = class.find_static(name) {
+                return Ok(member);
+            }
+            bail!(RuntimeError::UndefinedProperty(
+                format!("class {}", class.name),
+                name.to_string()
+            ))
+        }
+
+        bail!(RuntimeError::UndefinedProperty(
+            self.type_name(),
+            name.to_string()
+        ))
+    }
+
+    pub fn set_property(&self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        match self {
+            Value::Instance(instance) => {
+                instance.borrow_mut().fields.insert(name.to_string(), value);
+                Ok(())
+            }
+            Value::HostObject(object) => HostObject::set(object, name, &value),
+            Value::Object(object) => {
+                object.borrow_mut().insert(name.to_string(), value);
+                Ok(())
+            }
+            Value::Class(class) => {
+                class.statics.borrow_mut().insert(name.to_string(), value);
+                Ok(())
+            }
+            Value::Module(module) => bail!(RuntimeError::ReadOnlyProperty(
+                format!("module {}", module.name),
+                name.to_string()
+            )),
+            object => bail!(RuntimeError::UndefinedProperty(
+                object.type_name(),
+                name.to_string()
+            )),
+        }
+    }
+
+    pub fn get_index(&self, index: &Value) -> Result<Value, RuntimeError> {
+        match self {
+            Value::List(items) => {
+                let items = items.borrow();
+                let position = normalize_index(index, items.len())?;
+                Ok(items[position].clone())
+            }
+            Value::String(value) => {
+                let chars: Vec<char> = value.chars().collect();
+                let position = normalize_index(index, chars.len())?;
+                Ok(Value::String(chars[position].to_string()))
+            }
+            Value::Object(_) => match index {
+                Value::String(key) => self.get_property(key),
+                key => bail!(RuntimeError::WrongType(
+                    "Object key".to_string(),
+                    "string".to_string(),
+                    key.type_name()
+                )),
+            },
+            object => bail!(RuntimeError::InvalidOperand(
+                "[]".to_string(),
+                object.type_name()
+            )),
+        }
+    }
+
+    pub fn set_index(&self, index: &Value, value: Value) -> Result<(), RuntimeError> {
+        match self {
+            Value::List(items) => {
+                let mut items = items.borrow_mut();
+                let position = normalize_index(index, items.len())?;
+                items[position] = value;
+                Ok(())
+            }
+            Value::Object(_) => match index {
+                Value::String(key) => self.set_property(key, value),
+                key => bail!(RuntimeError::WrongType(
+                    "Object key".to_string(),
+                    "string".to_string(),
+                    key.type_name()
+                )),
+            },
+            object => bail!(RuntimeError::InvalidOperand(
+                "[]=".to_string(),
+                object.type_name()
+            )),
+        }
+    }
+}
+
+// converts a possibly negative index into a position inside of a sequence
+fn normalize_index(index: &Value, length: usize) -> Result<usize, RuntimeError> {
+    let index = match index {
+        Value::Integer(index) => *index,
+        value => bail!(RuntimeError::InvalidOperand(
+            "[]".to_string(),
+            value.type_name()
+        )),
+    };
+    let position = if index < 0 {
+        index + length as i64
+    } else {
+        index
+    };
+    if position < 0 || position >= length as i64 {
+        bail!(RuntimeError::IndexOutOfBounds(index, length))
+    }
+    Ok(position as usize)
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_equal(other)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(f, &mut vec![])
+    }
+}
+
+impl Value {
+    // `printing` holds the addresses of the lists and objects this value is nested in,
+    // one that contains itself is shown as `[...]` or `{...}` the second time
+    fn display(&self, f: &mut fmt::Formatter<'_>, printing: &mut Vec<usize>) -> fmt::Result {
+        match self {
+            Value::List(items) => {
+                let address = Rc::as_ptr(items) as usize;
+                if printing.contains(&address) {
+                    return write!(f, "[...]");
+                }
+                printing.push(address);
+                write!(f, "[")?;
+                for (i, item) in items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match item {
+                        Value::String(value) => write!(f, "{:?}", value)?,
+                        item => item.display(f, printing)?,
+                    }
+                }
+                printing.pop();
+                write!(f, "]")
+            }
+            Value::Object(object) => {
+                let address = Rc::as_ptr(object) as usize;
+                if printing.contains(&address) {
+                    return write!(f, "{{...}}");
+                }
+                printing.push(address);
+                write!(f, "{{")?;
+                for (i, (key, value)) in object.borrow().entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match value {
+                        Value::String(value) => write!(f, "{:?}: {:?}", key, value)?,
+                        value => {
+                            write!(f, "{:?}: ", key)?;
+                            value.display(f, printing)?;
+                        }
+                    }
+                }
+                printing.pop();
+                write!(f, "}}")
+            }
+            value => value.display_flat(f),
+        }
+    }
+
+    // values that don't contain other values
+    fn display_flat(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Decimal(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Null => write!(f, "null"),
+            Value::List(_) | Value::Object(_) => self.display(f, &mut vec![]),
+            Value::Function(function) => write!(f, "<fn {}>", function.name),
+            Value::NativeFunction(function) => write!(f, "<native fn {}>", function.name),
+            Value::CompiledFunction(function) => write!(f, "<fn {}>", function.name),
+            Value::Closure(closure) => write!(f, "<fn {}>", closure.function.name),
+            Value::BoundMethod(bound) => match &bound.method {
+                Value::Function(method) => write!(f, "<method {}>", method.name),
+                Value::CompiledFunction(method) => write!(f, "<method {}>", method.name),
+                Value::Closure(method) => write!(f, "<method {}>", method.function.name),
+                method => write!(f, "<method {}>", method),
+            },
+            Value::Class(class) => write!(f, "<class {}>", class.name),
+            Value::Instance(instance) => write!(f, "<{} instance>", instance.borrow().class.name),
+            Value::HostObject(object) => write!(f, "<{} object>", object.borrow().class.name),
+            Value::Module(module) => write!(f, "<module {}>", module.name),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{:?}", value),
+            value => write!(f, "{}", value),
+        }
+    }
+}
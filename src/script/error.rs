@@ -0,0 +1,66 @@
+use crate::{
+    lexer::token::LexerError, parser::ParseError, runtime::RuntimeError, vm::CompileError,
+};
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Lexer(LexerError),
+    Parse(ParseError),
+    Compile(CompileError),
+    Runtime(RuntimeError),
+    MissingHook(String),             // hook_name
+    HookArity(String, usize, usize), // hook_name, expected, got
+    NotAFunction(String, String),    // hook_name, type_name
+    AlreadyStarted,
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Lexer(err) => write!(f, "Error while tokenizing: {}", err),
+            ScriptError::Parse(err) => write!(f, "Error while parsing: {}", err),
+            ScriptError::Compile(err) => write!(f, "Compile error: {}", err),
+            ScriptError::Runtime(err) => write!(f, "Runtime error: {}", err),
+            ScriptError::MissingHook(name) => {
+                write!(f, "The script does not define the '{}' function", name)
+            }
+            ScriptError::HookArity(name, expected, got) => write!(
+                f,
+                "'{}' should take {} parameter(s), but the script declares {}",
+                name, expected, got
+            ),
+            ScriptError::NotAFunction(name, type_name) => write!(
+                f,
+                "'{}' should be a function, but the script defines it as '{}'",
+                name, type_name
+            ),
+            ScriptError::AlreadyStarted => write!(f, "The script has already been started"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<LexerError> for ScriptError {
+    fn from(err: LexerError) -> Self {
+        ScriptError::Lexer(err)
+    }
+}
+
+impl From<ParseError> for ScriptError {
+    fn from(err: ParseError) -> Self {
+        ScriptError::Parse(err)
+    }
+}
+
+impl From<CompileError> for ScriptError {
+    fn from(err: CompileError) -> Self {
+        ScriptError::Compile(err)
+    }
+}
+
+impl From<RuntimeError> for ScriptError {
+    fn from(err: RuntimeError) -> Self {
+        ScriptError::Runtime(err)
+    }
+}
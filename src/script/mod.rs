@@ -0,0 +1,269 @@
+mod error;
+
+use std::{fs, path::Path};
+
+use crate::{
+    lexer::Lexer,
+    macros::bail,
+    parser::Parser,
+    runtime::{RuntimeError, Value},
+    vm::{Budget, Outcome, Vm},
+};
+
+pub use error::ScriptError;
+
+const START: &str = "start";
+const UPDATE: &str = "update";
+
+/// A loaded game script, this is what the engine talks to.
+///
+/// The top-level statements run once when the script is loaded, after that
+/// the engine calls `start()` once and `update(delta)` on every frame.
+pub struct Script {
+    vm: Vm,
+    started: bool,
+}
+
+impl Script {
+    pub fn load(source: &str) -> Result<Self, ScriptError> {
+        Self::load_into(Vm::new(), source)
+    }
+
+    /// Loads the script into a prepared `vm`, e.g. one with host globals defined.
+    pub fn load_into(vm: Vm, source: &str) -> Result<Self, ScriptError> {
+        Self::load_source(vm, source, None)
+    }
+
+    /// Loads a script file, its imports are resolved relative to it.
+    pub fn load_file(vm: Vm, file: &Path) -> Result<Self, ScriptError> {
+        let source = fs::read_to_string(file).map_err(|err| {
+            RuntimeError::InvalidModule(file.display().to_string(), err.to_string())
+        })?;
+        Self::load_source(vm, &source, Some(file))
+    }
+
+    fn load_source(mut vm: Vm, source: &str, file: Option<&Path>) -> Result<Self, ScriptError> {
+        let mut lexer = Lexer::new(source.to_string());
+        lexer.tokenize()?;
+        let program = Parser::new(lexer.tokens).produce_ast()?;
+
+        let script = match file {
+            Some(file) => vm.compile_file(&program, file)?,
+            None => vm.compile(&program)?,
+        };
+        vm.run(script)?;
+
+        Ok(Self { vm, started: false })
+    }
+
+    pub fn vm(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    /// Whether the script defines a function called `name`, other globals don't count.
+    pub fn has_hook(&mut self, name: &str) -> bool {
+        matches!(self.vm.globals().lookup(name), Ok(Value::Closure(_)))
+    }
+
+    /// Calls `start()`, this may be done only once. The script counts as started only
+    /// after the hook ran without an error.
+    pub fn start(&mut self) -> Result<(), ScriptError> {
+        if self.started {
+            bail!(ScriptError::AlreadyStarted)
+        }
+        self.call_hook(START, vec![])?;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Calls `update(delta)`, `delta` is the time since the last frame in seconds.
+    pub fn update(&mut self, delta: f64) -> Result<(), ScriptError> {
+        self.call_hook(UPDATE, vec![Value::Decimal(delta)])?;
+        Ok(())
+    }
+
+    /// Same as `update`, but the frame is suspended once it runs out of `budget`,
+    /// the rest of it runs on the next `resume`.
+    pub fn update_with_budget(
+        &mut self,
+        delta: f64,
+        budget: Budget,
+    ) -> Result<Outcome, ScriptError> {
+        let hook = self.hook(UPDATE, 1)?;
+        Ok(self
+            .vm
+            .call_with_budget(&hook, vec![Value::Decimal(delta)], budget)?)
+    }
+
+    pub fn resume(&mut self, budget: Budget) -> Result<Outcome, ScriptError> {
+        Ok(self.vm.resume(budget)?)
+    }
+
+    /// Runs one frame: advances the game time by `delta` seconds, then continues
+    /// the suspended frame if there is one (e.g. waiting in `timer.run_and_wait`),
+    /// otherwise calls `update(delta)`.
+    pub fn tick(&mut self, delta: f64, budget: Budget) -> Result<Outcome, ScriptError> {
+        self.vm.advance_time(delta)?;
+        match self.vm.is_suspended() {
+            true => self.resume(budget),
+            false => self.update_with_budget(delta, budget),
+        }
+    }
+
+    pub fn call_hook(&mut self, name: &str, args: Vec<Value>) -> Result<Value, ScriptError> {
+        let hook = self.hook(name, args.len())?;
+        Ok(self.vm.call(&hook, args)?)
+    }
+
+    fn hook(&mut self, name: &str, arity: usize) -> Result<Value, ScriptError> {
+        let hook = match self.vm.globals().lookup(name) {
+            Ok(hook) => hook,
+            Err(_) => bail!(ScriptError::MissingHook(name.to_string())),
+        };
+        match hook {
+            Value::Closure(ref closure) if !closure.function.signature.accepts(arity) => bail!(
+                ScriptError::HookArity(name.to_string(), arity, closure.function.arity())
+            ),
+            Value::Closure(_) => Ok(hook),
+            value => bail!(ScriptError::NotAFunction(
+                name.to_string(),
+                value.type_name()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        let source = "let ticks = 0\n\
+                      let total = 0.0\n\
+                      fn start() { ticks = 100 }\n\
+                      fn update(delta) { ticks += 1\ntotal += delta }";
+        let mut script = Script::load(source).unwrap();
+        assert!(script.has_hook("start"));
+        assert!(script.has_hook("update"));
+
+        script.start().unwrap();
+        assert!(matches!(script.start(), Err(ScriptError::AlreadyStarted)));
+        for _ in 0..3 {
+            script.update(0.5).unwrap();
+        }
+
+        let globals = script.vm().globals();
+        assert_eq!(globals.lookup("ticks").unwrap(), Value::Integer(103));
+        assert_eq!(globals.lookup("total").unwrap(), Value::Decimal(1.5));
+    }
+
+    #[test]
+    fn test_top_level_runs_once() {
+        let mut script = Script::load("let loads = 0\nloads += 1\nfn update(delta) { }").unwrap();
+        script.update(0.1).unwrap();
+        script.update(0.1).unwrap();
+        assert_eq!(
+            script.vm().globals().lookup("loads").unwrap(),
+            Value::Integer(1)
+        );
+    }
+
+    #[test]
+    fn test_hook_errors() {
+        let mut script = Script::load("fn start(x) { }\nlet update = 5").unwrap();
+        assert!(script.has_hook("start"));
+        assert!(!script.has_hook("update"));
+        assert!(matches!(
+            script.start(),
+            Err(ScriptError::HookArity(name, 0, 1)) if name == "start"
+        ));
+        assert!(matches!(
+            script.update(0.1),
+            Err(ScriptError::NotAFunction(name, _)) if name == "update"
+        ));
+
+        let mut script = Script::load("let x = 1").unwrap();
+        assert!(!script.has_hook("update"));
+        // a script without `start` never counts as started
+        for _ in 0..2 {
+            assert!(matches!(
+                script.start(),
+                Err(ScriptError::MissingHook(name)) if name == "start"
+            ));
+        }
+        assert!(matches!(
+            script.update(0.1),
+            Err(ScriptError::MissingHook(name)) if name == "update"
+        ));
+
+        let mut script = Script::load("fn update(delta) {\n  move(delta)\n}").unwrap();
+        match script.update(0.1) {
+            Err(ScriptError::Runtime(err)) => {
+                let span = err.location().expect("errors should have a location").span;
+                assert_eq!((span.start.line, span.start.column), (2, 2));
+                assert!(matches!(
+                    err.without_location(),
+                    RuntimeError::UndefinedVariable(name) if name == "move"
+                ));
+            }
+            _ => panic!("Expected a runtime error"),
+        }
+    }
+
+    #[test]
+    fn test_load_errors() {
+        assert!(matches!(
+            Script::load("let = 5"),
+            Err(ScriptError::Parse(_))
+        ));
+        match Script::load("fn f() { }\nf(1)") {
+            Err(ScriptError::Runtime(err)) => assert!(matches!(
+                err.without_location(),
+                RuntimeError::ArityMismatch(..)
+            )),
+            _ => panic!("Expected an arity mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_update_with_budget() {
+        let source = "let sum = 0\nfn update(delta) { for i in range(100) { sum += i } }";
+        let mut script = Script::load(source).unwrap();
+
+        let mut outcome = script.update_with_budget(0.1, Budget::steps(20)).unwrap();
+        assert_eq!(outcome, Outcome::BudgetExhausted);
+        while outcome == Outcome::BudgetExhausted {
+            outcome = script.resume(Budget::steps(20)).unwrap();
+        }
+        assert_eq!(
+            script.vm().globals().lookup("sum").unwrap(),
+            Value::Integer(4950)
+        );
+    }
+
+    #[test]
+    fn test_tick_with_timer() {
+        let source = "import timer\n\
+                      let shots = 0\n\
+                      fn update(delta) { shots += 1\ntimer.run_and_wait(1) }";
+        let mut waiting = Script::load(source).unwrap();
+        let mut busy = Script::load("let frames = 0\nfn update(delta) { frames += 1 }").unwrap();
+
+        // only the waiting script is held back, the frame where its timer
+        // fires finishes the previous update
+        for _ in 0..10 {
+            waiting.tick(0.25, Budget::unlimited()).unwrap();
+            busy.tick(0.25, Budget::unlimited()).unwrap();
+        }
+        assert_eq!(
+            waiting.vm().globals().lookup("shots").unwrap(),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            busy.vm().globals().lookup("frames").unwrap(),
+            Value::Integer(10)
+        );
+        assert_eq!(waiting.vm().time(), 2.5);
+    }
+}
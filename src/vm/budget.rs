@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use crate::runtime::Value;
+
+// reading the clock on every instruction is too slow, so it's read every few steps
+const CLOCK_INTERVAL: u64 = 64;
+
+/// How much work a single run of the vm may do before it gets suspended.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub steps: Option<u64>,     // executed instructions
+    pub time: Option<Duration>, // wall clock time
+}
+
+impl Budget {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(steps: u64) -> Self {
+        Self {
+            steps: Some(steps),
+            time: None,
+        }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        Self {
+            steps: None,
+            time: Some(time),
+        }
+    }
+
+    pub(super) fn start(&self) -> Fuel {
+        Fuel {
+            steps_left: self.steps,
+            deadline: self.time.map(|time| Instant::now() + time),
+            taken: 0,
+        }
+    }
+}
+
+/// Result of a budgeted run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Finished(Value),
+    BudgetExhausted, // the execution is suspended and can be resumed with `Vm::resume`
+    Waiting,         // suspended until the timer passed to `timer.run_and_wait` fires
+}
+
+pub(super) struct Fuel {
+    steps_left: Option<u64>,
+    deadline: Option<Instant>,
+    taken: u64,
+}
+
+impl Fuel {
+    // takes fuel for one instruction, returns false if there is none left
+    pub(super) fn take(&mut self) -> bool {
+        if let Some(steps) = self.steps_left.as_mut() {
+            if *steps == 0 {
+                return false;
+            }
+            *steps -= 1;
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.taken.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+                return false;
+            }
+        }
+        self.taken += 1;
+        true
+    }
+}
@@ -0,0 +1,142 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use crate::{
+    lexer::Span,
+    macros::bail,
+    parser::{BinaryOperator, UnaryOperator},
+    runtime::{Class, Environment, Signature, Value},
+};
+
+use super::error::CompileError;
+
+// Jump targets are absolute instruction offsets inside of the chunk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Constant(u16), // constant index
+    Null,
+    True,
+    False,
+    Pop,
+    Dup,                     // duplicates the top of the stack
+    Dup2,                    // duplicates the two values on top of the stack
+    DefineGlobal(u16, bool), // name constant, is_constant
+    GetGlobal(u16),          // name constant
+    SetGlobal(u16),          // name constant
+    GetLocal(u16),           // slot
+    SetLocal(u16),           // slot
+    GetUpvalue(u16),         // upvalue index of the running closure
+    SetUpvalue(u16),         // upvalue index of the running closure
+    CloseUpvalue,            // pops a captured local, its closures keep the value
+    Binary(BinaryOperator),
+    CompareOrJump(BinaryOperator, u32), // link of a chained comparison, jumps with `false` when it fails
+    Unary(UnaryOperator),               // never `++` or `--`, those are compiled into assignments
+    Jump(u32),
+    JumpIfGiven(u16, u32), // parameter slot, skips its default value when the call gave one
+    JumpIfFalse(u32),      // pops the condition
+    JumpIfFalseOrPop(u32), // keeps the condition on the stack only when jumping
+    JumpIfTrueOrPop(u32),  // keeps the condition on the stack only when jumping
+    BuildList(u16),        // item count
+    BuildObject(u16),      // property count, every property is a key followed by its value
+    GetProperty(u16),      // name constant
+    SetProperty(u16),      // name constant
+    GetIndex,
+    SetIndex,
+    Iterate,            // replaces the iterable on top of the stack with a list of its items
+    IterNext(u16, u32), // items slot (the index lives in the next slot), exit target
+    Call(u8),           // argument count
+    CallNamed(u8, u16), // argument count, constant listing the names of the trailing arguments
+    New,                // fails unless the value on top of the stack is a class, its call follows
+    Closure(u16),       // function constant
+    Import(u16),        // module path constant
+    ImportAll(u16),     // module path constant, declares every export as a global
+    Class(u16, u16, u16), // name constant, field count, method count, pops the superclass too
+    GetSuper(u16),      // method name constant, binds it to the `self` on top of the stack
+    Return,
+}
+
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub spans: Vec<Span>, // span of the node every instruction was compiled from
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.code.push(instruction);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> Result<u16, CompileError> {
+        // names are used over and over again, so they are stored only once
+        if let Value::String(ref name) = value {
+            let existing = self
+                .constants
+                .iter()
+                .position(|constant| matches!(constant, Value::String(value) if value == name));
+            if let Some(index) = existing {
+                return Ok(index as u16);
+            }
+        }
+
+        if self.constants.len() > u16::MAX as usize {
+            bail!(CompileError::TooManyConstants)
+        }
+        self.constants.push(value);
+        Ok((self.constants.len() - 1) as u16)
+    }
+
+    // points a previously emitted jump at the next instruction to be emitted
+    pub fn patch_jump(&mut self, offset: usize) {
+        let target = self.code.len() as u32;
+        self.code[offset] = match self.code[offset] {
+            Instruction::Jump(_) => Instruction::Jump(target),
+            Instruction::JumpIfFalse(_) => Instruction::JumpIfFalse(target),
+            Instruction::JumpIfFalseOrPop(_) => Instruction::JumpIfFalseOrPop(target),
+            Instruction::JumpIfTrueOrPop(_) => Instruction::JumpIfTrueOrPop(target),
+            Instruction::JumpIfGiven(slot, _) => Instruction::JumpIfGiven(slot, target),
+            Instruction::IterNext(slot, _) => Instruction::IterNext(slot, target),
+            Instruction::CompareOrJump(operator, _) => Instruction::CompareOrJump(operator, target),
+            instruction => instruction,
+        };
+    }
+}
+
+/// A function lowered into bytecode, the top-level script is compiled into one as well.
+pub struct CompiledFunction {
+    pub name: String,
+    pub signature: Signature,
+    pub chunk: Chunk,
+    pub captures: Vec<Capture>, // turned into the upvalues of its closures, in order
+    pub file: Option<Rc<Path>>, // source file, imports are resolved relative to it
+}
+
+impl CompiledFunction {
+    pub fn arity(&self) -> usize {
+        self.signature.arity()
+    }
+}
+
+/// Where a closure finds a variable of an enclosing function when it's created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Local(u16),   // slot of the function that creates the closure
+    Upvalue(u16), // upvalue of the function that creates the closure
+}
+
+/// A captured variable, it stays on the stack while its scope is alive
+/// and moves into the upvalue once the scope ends.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize), // stack index
+    Closed(Value),
+}
+
+/// A compiled function together with the globals of the module that defined it.
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub globals: Rc<RefCell<Environment>>,
+    pub superclass: Option<Rc<Class>>, // what `super` refers to inside of methods
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
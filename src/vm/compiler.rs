@@ -0,0 +1,1065 @@
+use std::{collections::HashSet, path::Path, rc::Rc};
+
+use crate::{
+    lexer::Span,
+    macros::bail,
+    parser::{
+        AssignmentOperator, BinaryOperator, ImportKind, LogicalOperator, Node, NodeKind,
+        UnaryOperator,
+    },
+    runtime::{import_path, Signature, Value, ANONYMOUS},
+};
+
+use super::{
+    chunk::{Capture, Chunk, CompiledFunction, Instruction},
+    error::CompileError,
+};
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_constant: bool,
+    captured: bool, // closed over by a nested function, so it has to outlive the scope
+}
+
+struct Loop {
+    start: usize,       // where `continue` jumps to
+    depth: usize,       // scope depth of the locals that outlive the loop body
+    breaks: Vec<usize>, // jumps to patch once the end of the loop is known
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+}
+
+struct FunctionState {
+    name: String,
+    signature: Signature,
+    kind: FunctionKind,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    captures: Vec<(Capture, bool)>, // capture, is_constant
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: String, signature: Signature, kind: FunctionKind) -> Self {
+        // slot 0 holds the called function itself, or `self` inside of methods
+        let reserved = Local {
+            name: match kind {
+                FunctionKind::Method => "self".to_string(),
+                _ => String::new(),
+            },
+            depth: 0,
+            is_constant: true,
+            captured: false,
+        };
+
+        Self {
+            name,
+            signature,
+            kind,
+            chunk: Chunk::default(),
+            locals: vec![reserved],
+            captures: vec![],
+            scope_depth: 0,
+            loops: vec![],
+        }
+    }
+}
+
+enum Resolution {
+    Local(u16, bool),   // slot, is_constant
+    Upvalue(u16, bool), // upvalue index, is_constant
+    Global,
+}
+
+/// Lowers a `NodeKind::Program` into bytecode.
+///
+/// Variables declared at the top level of the script are globals and are
+/// looked up by name, everything else lives in a numbered stack slot.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    globals: HashSet<String>,
+    file: Option<Rc<Path>>,
+    span: Span, // of the node being compiled, instructions remember it for runtime errors
+}
+
+impl Compiler {
+    /// `globals` are names that are already defined in the VM (builtins, host objects).
+    pub fn new(globals: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            functions: vec![],
+            globals: globals.into_iter().collect(),
+            file: None,
+            span: Span::default(),
+        }
+    }
+
+    /// Sets the file the program comes from, imports are resolved relative to it.
+    pub fn with_file(mut self, file: &Path) -> Self {
+        self.file = Some(Rc::from(file));
+        self
+    }
+
+    pub fn compile(&mut self, program: &Node) -> Result<Rc<CompiledFunction>, CompileError> {
+        let statements = match &program.kind {
+            NodeKind::Program(statements) => statements,
+            _ => bail!(CompileError::Unsupported(
+                "Compiling anything but a program".to_string()
+            )),
+        };
+
+        self.declare_globals(statements);
+        self.functions.push(FunctionState::new(
+            "<script>".to_string(),
+            Signature::default(),
+            FunctionKind::Script,
+        ));
+
+        for (i, statement) in statements.iter().enumerate() {
+            // the value of the last expression is the result of the script
+            if i + 1 == statements.len() && Self::is_expression(statement) {
+                self.expression(statement)?;
+                self.emit(Instruction::Return);
+                return Ok(self.finish_function());
+            }
+            self.statement(statement)?;
+        }
+
+        self.emit(Instruction::Null);
+        self.emit(Instruction::Return);
+        Ok(self.finish_function())
+    }
+
+    // collects the names that will be defined in the global scope by the script
+    fn declare_globals(&mut self, statements: &[Box<Node>]) {
+        for statement in statements {
+            let name = match &statement.kind {
+                NodeKind::VariableDeclaration(name, _, _) => name,
+                NodeKind::FunctionDeclaration(id, _, _) | NodeKind::ClassDeclaration(id, _, _) => {
+                    match &id.kind {
+                        NodeKind::Identifier(name) => name,
+                        _ => continue,
+                    }
+                }
+                NodeKind::AssignmentExpression(target, _, _) => match &target.kind {
+                    NodeKind::Identifier(name) => name,
+                    _ => continue,
+                },
+                NodeKind::ImportStatement(path, kind) => {
+                    let name = import_path(path).map(|(_, name)| name);
+                    match kind {
+                        ImportKind::Module(alias) => {
+                            self.globals.extend(alias.clone().or(name));
+                        }
+                        ImportKind::Names(names) => {
+                            for (name, alias) in names {
+                                self.globals.insert(alias.as_ref().unwrap_or(name).clone());
+                            }
+                        }
+                        // the names are only known once the module is loaded
+                        ImportKind::Wildcard => {}
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            self.globals.insert(name.clone());
+        }
+    }
+
+    fn is_expression(node: &Node) -> bool {
+        !matches!(
+            &node.kind,
+            NodeKind::Program(_)
+                | NodeKind::VariableDeclaration(..)
+                | NodeKind::BlockStatement(_)
+                | NodeKind::FunctionDeclaration(..)
+                | NodeKind::IfStatement(..)
+                | NodeKind::ForInStatement(..)
+                | NodeKind::WhileStatement(..)
+                | NodeKind::BreakStatement()
+                | NodeKind::ContinueStatement()
+                | NodeKind::ReturnStatement(_)
+                | NodeKind::ImportStatement(..)
+                | NodeKind::ClassDeclaration(..)
+                | NodeKind::PropertyDefinition(..)
+                | NodeKind::MethodDefinition(..)
+        )
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("compiler should always have a function to compile into")
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let span = self.span;
+        self.current().chunk.emit(instruction, span)
+    }
+
+    fn constant(&mut self, value: Value) -> Result<u16, CompileError> {
+        self.current().chunk.add_constant(value)
+    }
+
+    fn name_constant(&mut self, name: &str) -> Result<u16, CompileError> {
+        self.constant(Value::String(name.to_string()))
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        self.current().chunk.patch_jump(offset);
+    }
+
+    fn finish_function(&mut self) -> Rc<CompiledFunction> {
+        let state = self
+            .functions
+            .pop()
+            .expect("compiler should always have a function to compile into");
+
+        Rc::new(CompiledFunction {
+            name: state.name,
+            signature: state.signature,
+            chunk: state.chunk,
+            captures: state
+                .captures
+                .into_iter()
+                .map(|(capture, _)| capture)
+                .collect(),
+            file: self.file.clone(),
+        })
+    }
+
+    fn is_global_scope(&mut self) -> bool {
+        let current = self.current();
+        current.kind == FunctionKind::Script && current.scope_depth == 0
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let current = self.current();
+        current.scope_depth -= 1;
+        let depth = current.scope_depth;
+
+        let mut dropped = vec![];
+        while current
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > depth)
+        {
+            if let Some(local) = current.locals.pop() {
+                dropped.push(local.captured);
+            }
+        }
+        for captured in dropped {
+            self.drop_local(captured);
+        }
+    }
+
+    fn drop_local(&mut self, captured: bool) {
+        match captured {
+            true => self.emit(Instruction::CloseUpvalue),
+            false => self.emit(Instruction::Pop),
+        };
+    }
+
+    fn begin_loop(&mut self, start: usize) {
+        let depth = self.current().scope_depth;
+        self.current().loops.push(Loop {
+            start,
+            depth,
+            breaks: vec![],
+        });
+    }
+
+    fn end_loop(&mut self) {
+        if let Some(ended) = self.current().loops.pop() {
+            for jump in ended.breaks {
+                self.patch_jump(jump);
+            }
+        }
+    }
+
+    // jumps out of the loop body, dropping its locals on the way
+    fn loop_jump(&mut self, is_break: bool) -> Result<(), CompileError> {
+        let current = self.current();
+        let (start, depth) = match current.loops.last() {
+            Some(target) => (target.start, target.depth),
+            None => bail!(CompileError::OutsideOfLoop),
+        };
+        let dropped: Vec<bool> = current
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .map(|local| local.captured)
+            .collect();
+        for captured in dropped {
+            self.drop_local(captured);
+        }
+
+        if is_break {
+            let jump = self.emit(Instruction::Jump(0));
+            if let Some(target) = self.current().loops.last_mut() {
+                target.breaks.push(jump);
+            }
+        } else {
+            self.emit(Instruction::Jump(start as u32));
+        }
+        Ok(())
+    }
+
+    // the value of the local is expected to be on top of the stack already
+    fn add_local(&mut self, name: &str, is_constant: bool) -> Result<u16, CompileError> {
+        let current = self.current();
+        if current.locals.len() > u16::MAX as usize {
+            bail!(CompileError::TooManyLocals)
+        }
+        current.locals.push(Local {
+            name: name.to_string(),
+            depth: current.scope_depth,
+            is_constant,
+            captured: false,
+        });
+        Ok((current.locals.len() - 1) as u16)
+    }
+
+    // stores the value on top of the stack into a new variable of the current scope
+    fn define_variable(&mut self, name: &str, is_constant: bool) -> Result<(), CompileError> {
+        if self.is_global_scope() {
+            let name = self.name_constant(name)?;
+            self.emit(Instruction::DefineGlobal(name, is_constant));
+            return Ok(());
+        }
+
+        // re-declaring a variable in the same scope reuses its slot
+        let current = self.current();
+        let depth = current.scope_depth;
+        let existing = current
+            .locals
+            .iter()
+            .rposition(|local| local.depth == depth && local.name == name);
+        match existing {
+            Some(slot) if current.locals[slot].is_constant => {
+                bail!(CompileError::ConstantReassignment(name.to_string()))
+            }
+            Some(slot) => {
+                current.locals[slot].is_constant = is_constant;
+                self.emit(Instruction::SetLocal(slot as u16));
+                self.emit(Instruction::Pop);
+            }
+            None => {
+                self.add_local(name, is_constant)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, name: &str) -> Result<Resolution, CompileError> {
+        let current = self.functions.len() - 1;
+        if let Some(slot) = self.local_slot(current, name) {
+            let is_constant = self.functions[current].locals[slot].is_constant;
+            return Ok(Resolution::Local(slot as u16, is_constant));
+        }
+
+        Ok(match self.resolve_upvalue(current, name)? {
+            Some((index, is_constant)) => Resolution::Upvalue(index, is_constant),
+            None => Resolution::Global,
+        })
+    }
+
+    fn local_slot(&self, function: usize, name: &str) -> Option<usize> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+    }
+
+    // looks for `name` in the enclosing functions, every function in between
+    // captures it as well so the closure can hand it down
+    fn resolve_upvalue(
+        &mut self,
+        function: usize,
+        name: &str,
+    ) -> Result<Option<(u16, bool)>, CompileError> {
+        if function == 0 {
+            return Ok(None);
+        }
+
+        let enclosing = function - 1;
+        if let Some(slot) = self.local_slot(enclosing, name) {
+            let local = &mut self.functions[enclosing].locals[slot];
+            local.captured = true;
+            let is_constant = local.is_constant;
+            return self
+                .add_capture(function, Capture::Local(slot as u16), is_constant)
+                .map(Some);
+        }
+
+        match self.resolve_upvalue(enclosing, name)? {
+            Some((index, is_constant)) => self
+                .add_capture(function, Capture::Upvalue(index), is_constant)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_capture(
+        &mut self,
+        function: usize,
+        capture: Capture,
+        is_constant: bool,
+    ) -> Result<(u16, bool), CompileError> {
+        let captures = &mut self.functions[function].captures;
+        if let Some(index) = captures
+            .iter()
+            .position(|(existing, _)| *existing == capture)
+        {
+            return Ok((index as u16, is_constant));
+        }
+        if captures.len() > u16::MAX as usize {
+            bail!(CompileError::TooManyUpvalues)
+        }
+        captures.push((capture, is_constant));
+        Ok(((captures.len() - 1) as u16, is_constant))
+    }
+
+    // instructions of a node that come after its children are compiled still belong to it
+    fn statement(&mut self, node: &Node) -> Result<(), CompileError> {
+        let span = std::mem::replace(&mut self.span, node.span);
+        let result = self.compile_statement(node);
+        self.span = span;
+        result
+    }
+
+    fn expression(&mut self, node: &Node) -> Result<(), CompileError> {
+        let span = std::mem::replace(&mut self.span, node.span);
+        let result = self.compile_expression(node);
+        self.span = span;
+        result
+    }
+
+    fn compile_statement(&mut self, node: &Node) -> Result<(), CompileError> {
+        match &node.kind {
+            NodeKind::VariableDeclaration(name, value, is_constant) => {
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(Instruction::Null);
+                    }
+                }
+                self.define_variable(name, *is_constant)
+            }
+            NodeKind::BlockStatement(statements) => {
+                self.begin_scope();
+                for statement in statements {
+                    self.statement(statement)?;
+                }
+                self.end_scope();
+                Ok(())
+            }
+            NodeKind::FunctionDeclaration(id, params, body) => {
+                let name = Self::identifier_name(id)?;
+                if self.is_global_scope() {
+                    self.closure(&name, params, body)?;
+                    return self.define_variable(&name, false);
+                }
+
+                // a local function is declared before its body, so it can capture itself
+                self.emit(Instruction::Null);
+                self.define_variable(&name, false)?;
+                self.closure(&name, params, body)?;
+                self.set_variable(&name)?;
+                self.emit(Instruction::Pop);
+                Ok(())
+            }
+            NodeKind::IfStatement(condition, consequent, alternate) => {
+                self.expression(condition)?;
+                let else_jump = self.emit(Instruction::JumpIfFalse(0));
+                self.statement(consequent)?;
+                match alternate {
+                    Some(alternate) => {
+                        let end_jump = self.emit(Instruction::Jump(0));
+                        self.patch_jump(else_jump);
+                        self.statement(alternate)?;
+                        self.patch_jump(end_jump);
+                    }
+                    None => self.patch_jump(else_jump),
+                }
+                Ok(())
+            }
+            NodeKind::ForInStatement(left, right, body) => {
+                let name = Self::identifier_name(left)?;
+
+                self.expression(right)?;
+                self.emit(Instruction::Iterate);
+                let zero = self.constant(Value::Integer(0))?;
+                self.emit(Instruction::Constant(zero));
+
+                self.begin_scope();
+                // hidden locals that can't clash with identifiers
+                let items = self.add_local(" items", false)?;
+                self.add_local(" index", false)?;
+
+                let loop_start = self.current().chunk.code.len();
+                self.begin_loop(loop_start);
+                let exit_jump = self.emit(Instruction::IterNext(items, 0));
+                // every iteration gets its own slot for the loop variable
+                self.begin_scope();
+                self.add_local(&name, false)?;
+                self.statement(body)?;
+                self.end_scope();
+                self.emit(Instruction::Jump(loop_start as u32));
+                self.patch_jump(exit_jump);
+                self.end_loop();
+
+                self.end_scope();
+                Ok(())
+            }
+            NodeKind::WhileStatement(condition, body) => {
+                let loop_start = self.current().chunk.code.len();
+                self.begin_loop(loop_start);
+                self.expression(condition)?;
+                let exit_jump = self.emit(Instruction::JumpIfFalse(0));
+                self.statement(body)?;
+                self.emit(Instruction::Jump(loop_start as u32));
+                self.patch_jump(exit_jump);
+                self.end_loop();
+                Ok(())
+            }
+            NodeKind::BreakStatement() => self.loop_jump(true),
+            NodeKind::ContinueStatement() => self.loop_jump(false),
+            NodeKind::ReturnStatement(value) => {
+                if self.current().kind == FunctionKind::Script {
+                    bail!(CompileError::ReturnOutsideFunction)
+                }
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(Instruction::Null);
+                    }
+                }
+                self.emit(Instruction::Return);
+                Ok(())
+            }
+            NodeKind::ImportStatement(path, kind) => self.import(path, kind),
+            NodeKind::ClassDeclaration(id, super_class, body) => self.class(id, super_class, body),
+            NodeKind::Program(_)
+            | NodeKind::PropertyDefinition(..)
+            | NodeKind::MethodDefinition(..) => {
+                bail!(CompileError::Unsupported(
+                    "Class members outside of a class body".to_string()
+                ))
+            }
+            NodeKind::AssignmentExpression(target, AssignmentOperator::Equals, value) => {
+                if let NodeKind::Identifier(name) = &target.kind {
+                    if !self.is_global_scope() {
+                        return self.assignment_statement(name, value);
+                    }
+                }
+                self.expression(node)?;
+                self.emit(Instruction::Pop);
+                Ok(())
+            }
+            _ => {
+                self.expression(node)?;
+                self.emit(Instruction::Pop);
+                Ok(())
+            }
+        }
+    }
+
+    // `name = value` as a statement, assigning to an unknown name declares it in the current
+    // scope, so does every unknown name of a chain like `a = b = 3`, inner ones first
+    fn assignment_statement(&mut self, name: &str, value: &Node) -> Result<(), CompileError> {
+        match &value.kind {
+            NodeKind::AssignmentExpression(target, AssignmentOperator::Equals, inner_value)
+                if matches!(target.kind, NodeKind::Identifier(_)) =>
+            {
+                let inner = Self::identifier_name(target)?;
+                self.assignment_statement(&inner, inner_value)?;
+                self.get_variable(&inner)?;
+            }
+            _ => self.expression(value)?,
+        }
+
+        if self.declares_implicitly(name) {
+            self.add_local(name, false)?;
+        } else {
+            self.set_variable(name)?;
+            self.emit(Instruction::Pop);
+        }
+        Ok(())
+    }
+
+    fn declares_implicitly(&mut self, name: &str) -> bool {
+        !self.is_global_scope()
+            && matches!(self.resolve(name), Ok(Resolution::Global))
+            && !self.globals.contains(name)
+    }
+
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Box<Node>],
+        body: &Node,
+        kind: FunctionKind,
+    ) -> Result<Rc<CompiledFunction>, CompileError> {
+        let (signature, defaults) = match Signature::from_nodes(params) {
+            Some(signature) => signature,
+            None => bail!(CompileError::InvalidSignature(name.to_string())),
+        };
+        let names = signature.params.clone();
+        let rest = signature.rest.clone();
+
+        self.functions
+            .push(FunctionState::new(name.to_string(), signature, kind));
+        self.begin_scope();
+        // a default only sees the parameters before it, like in the interpreter
+        for (param, default) in names.iter().zip(&defaults) {
+            let slot = self.add_local(param, false)?;
+            if let Some(default) = default {
+                let jump = self.emit(Instruction::JumpIfGiven(slot, 0));
+                self.expression(default)?;
+                self.emit(Instruction::SetLocal(slot));
+                self.emit(Instruction::Pop);
+                self.patch_jump(jump);
+            }
+        }
+        if let Some(rest) = rest {
+            self.add_local(&rest, false)?;
+        }
+
+        let statements = match &body.kind {
+            NodeKind::BlockStatement(statements) => statements,
+            _ => bail!(CompileError::Unsupported(
+                "Function body without a block".to_string()
+            )),
+        };
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        self.emit(Instruction::Null);
+        self.emit(Instruction::Return);
+
+        Ok(self.finish_function())
+    }
+
+    fn closure(
+        &mut self,
+        name: &str,
+        params: &[Box<Node>],
+        body: &Node,
+    ) -> Result<(), CompileError> {
+        let function = self.function(name, params, body, FunctionKind::Function)?;
+        let function = self.constant(Value::CompiledFunction(function))?;
+        self.emit(Instruction::Closure(function));
+        Ok(())
+    }
+
+    // pushes the positional arguments followed by the named ones and calls
+    // the value below them
+    fn call(&mut self, args: &[Box<Node>]) -> Result<(), CompileError> {
+        if args.len() > u8::MAX as usize {
+            bail!(CompileError::TooManyArguments)
+        }
+        let mut names = vec![];
+        for arg in args {
+            match &arg.kind {
+                NodeKind::NamedArgument(name, value) => {
+                    self.expression(value)?;
+                    names.push(Value::String(name.clone()));
+                }
+                _ => self.expression(arg)?,
+            }
+        }
+
+        let argc = args.len() as u8;
+        match names.is_empty() {
+            true => self.emit(Instruction::Call(argc)),
+            false => {
+                let names = self.constant(Value::list(names))?;
+                self.emit(Instruction::CallNamed(argc, names))
+            }
+        };
+        Ok(())
+    }
+
+    fn import(&mut self, path: &Node, kind: &ImportKind) -> Result<(), CompileError> {
+        let (path, name) = match import_path(path) {
+            Some(import) => import,
+            None => bail!(CompileError::InvalidImport),
+        };
+        let path = self.name_constant(&path)?;
+
+        match kind {
+            ImportKind::Module(alias) => {
+                self.emit(Instruction::Import(path));
+                self.define_variable(alias.as_ref().unwrap_or(&name), false)
+            }
+            ImportKind::Wildcard => {
+                // locals need to be known at compile time
+                if !self.is_global_scope() {
+                    bail!(CompileError::Unsupported(
+                        "Wildcard import outside of the top level".to_string()
+                    ))
+                }
+                self.emit(Instruction::ImportAll(path));
+                Ok(())
+            }
+            ImportKind::Names(names) => {
+                for (name, alias) in names {
+                    self.emit(Instruction::Import(path));
+                    let export = self.name_constant(name)?;
+                    self.emit(Instruction::GetProperty(export));
+                    self.define_variable(alias.as_ref().unwrap_or(name), false)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn class(
+        &mut self,
+        id: &Node,
+        super_class: &Option<Box<Node>>,
+        body: &[Box<Node>],
+    ) -> Result<(), CompileError> {
+        let name = Self::identifier_name(id)?;
+        match super_class {
+            Some(super_class) => self.expression(super_class)?,
+            None => {
+                self.emit(Instruction::Null);
+            }
+        }
+
+        let mut fields = 0;
+        for member in body {
+            match &member.kind {
+                NodeKind::PropertyDefinition(property, value, false) => {
+                    let property = self.name_constant(&Self::identifier_name(property)?)?;
+                    self.emit(Instruction::Constant(property));
+                    self.expression(value)?;
+                    fields += 1;
+                }
+                NodeKind::PropertyDefinition(_, _, true) | NodeKind::MethodDefinition(..) => {}
+                _ => bail!(CompileError::Unsupported(
+                    "Statements inside of a class body".to_string()
+                )),
+            }
+        }
+
+        let mut methods = 0;
+        for member in body {
+            if let NodeKind::MethodDefinition(key, params, body, false) = &member.kind {
+                let key = Self::identifier_name(key)?;
+                let method = self.function(&key, params, body, FunctionKind::Method)?;
+                let key = self.name_constant(&key)?;
+                let method = self.constant(Value::CompiledFunction(method))?;
+                self.emit(Instruction::Constant(key));
+                self.emit(Instruction::Closure(method));
+                methods += 1;
+            }
+        }
+
+        let class_name = self.name_constant(&name)?;
+        self.emit(Instruction::Class(class_name, fields, methods));
+
+        // static members are stored on the finished class
+        for member in body {
+            match &member.kind {
+                NodeKind::PropertyDefinition(property, value, true) => {
+                    let property = self.name_constant(&Self::identifier_name(property)?)?;
+                    self.emit(Instruction::Dup);
+                    self.expression(value)?;
+                    self.emit(Instruction::SetProperty(property));
+                    self.emit(Instruction::Pop);
+                }
+                NodeKind::MethodDefinition(key, params, body, true) => {
+                    let key = Self::identifier_name(key)?;
+                    let method = self.function(&key, params, body, FunctionKind::Function)?;
+                    let key = self.name_constant(&key)?;
+                    let method = self.constant(Value::CompiledFunction(method))?;
+                    self.emit(Instruction::Dup);
+                    self.emit(Instruction::Closure(method));
+                    self.emit(Instruction::SetProperty(key));
+                    self.emit(Instruction::Pop);
+                }
+                _ => {}
+            }
+        }
+
+        self.define_variable(&name, false)
+    }
+
+    fn is_super(node: &Node) -> bool {
+        matches!(&node.kind, NodeKind::Identifier(name) if name == "super")
+    }
+
+    // binds the method of the superclass to `self`, which is in slot 0 of methods
+    fn super_method(&mut self, name: &str) -> Result<(), CompileError> {
+        // functions nested in a method capture its `self`
+        match self.resolve("self")? {
+            Resolution::Global => {
+                self.emit(Instruction::GetLocal(0));
+            }
+            _ => self.get_variable("self")?,
+        }
+        let name = self.name_constant(name)?;
+        self.emit(Instruction::GetSuper(name));
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, node: &Node) -> Result<(), CompileError> {
+        match &node.kind {
+            NodeKind::IntegerLiteral(value) => {
+                let value = match i64::try_from(*value) {
+                    Ok(value) => value,
+                    Err(_) => bail!(CompileError::Unsupported(
+                        "Integer literal larger than 64 bits".to_string()
+                    )),
+                };
+                let constant = self.constant(Value::Integer(value))?;
+                self.emit(Instruction::Constant(constant));
+            }
+            NodeKind::DecimalLiteral(value) => {
+                let constant = self.constant(Value::Decimal(*value))?;
+                self.emit(Instruction::Constant(constant));
+            }
+            NodeKind::StringLiteral(value) => {
+                let constant = self.constant(Value::String(value.clone()))?;
+                self.emit(Instruction::Constant(constant));
+            }
+            NodeKind::BoolLiteral(true) => {
+                self.emit(Instruction::True);
+            }
+            NodeKind::BoolLiteral(false) => {
+                self.emit(Instruction::False);
+            }
+            NodeKind::NullLiteral() => {
+                self.emit(Instruction::Null);
+            }
+            NodeKind::Identifier(name) => self.get_variable(name)?,
+            NodeKind::ArrayExpression(items) => {
+                for item in items {
+                    self.expression(item)?;
+                }
+                if items.len() > u16::MAX as usize {
+                    bail!(CompileError::TooManyConstants)
+                }
+                self.emit(Instruction::BuildList(items.len() as u16));
+            }
+            NodeKind::ObjectExpression(properties) => {
+                for (key, value, computed) in properties {
+                    match (&key.kind, computed) {
+                        (_, true) => self.expression(key)?,
+                        (NodeKind::Identifier(name) | NodeKind::StringLiteral(name), false) => {
+                            let key = self.name_constant(name)?;
+                            self.emit(Instruction::Constant(key));
+                        }
+                        _ => bail!(CompileError::InvalidAssignmentTarget),
+                    }
+                    self.expression(value)?;
+                }
+                if properties.len() > u16::MAX as usize {
+                    bail!(CompileError::TooManyConstants)
+                }
+                self.emit(Instruction::BuildObject(properties.len() as u16));
+            }
+            NodeKind::BinaryExpression(left, operator, right) => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit(Instruction::Binary(*operator));
+            }
+            NodeKind::ComparisonExpression(first, comparisons) => {
+                self.expression(first)?;
+                let mut jumps = Vec::new();
+                for (i, (operator, right)) in comparisons.iter().enumerate() {
+                    self.expression(right)?;
+                    if i + 1 == comparisons.len() {
+                        self.emit(Instruction::Binary(*operator));
+                    } else {
+                        jumps.push(self.emit(Instruction::CompareOrJump(*operator, 0)));
+                    }
+                }
+                for jump in jumps {
+                    self.patch_jump(jump);
+                }
+            }
+            NodeKind::LogicalExpression(left, operator, right) => {
+                self.expression(left)?;
+                let jump = match operator {
+                    LogicalOperator::And => self.emit(Instruction::JumpIfFalseOrPop(0)),
+                    LogicalOperator::Or => self.emit(Instruction::JumpIfTrueOrPop(0)),
+                };
+                self.expression(right)?;
+                self.patch_jump(jump);
+            }
+            NodeKind::UnaryExpression(target, operator) => match operator {
+                UnaryOperator::Increment => self.assignment(
+                    target,
+                    Some(BinaryOperator::Plus),
+                    &Node::new(NodeKind::IntegerLiteral(1), node.span),
+                )?,
+                UnaryOperator::Decrement => self.assignment(
+                    target,
+                    Some(BinaryOperator::Minus),
+                    &Node::new(NodeKind::IntegerLiteral(1), node.span),
+                )?,
+                operator => {
+                    self.expression(target)?;
+                    self.emit(Instruction::Unary(*operator));
+                }
+            },
+            NodeKind::MemberExpression(object, property, false) if Self::is_super(object) => {
+                self.super_method(&Self::identifier_name(property)?)?;
+            }
+            NodeKind::MemberExpression(object, property, computed) => {
+                self.expression(object)?;
+                if *computed {
+                    self.expression(property)?;
+                    self.emit(Instruction::GetIndex);
+                } else {
+                    let name = self.name_constant(&Self::identifier_name(property)?)?;
+                    self.emit(Instruction::GetProperty(name));
+                }
+            }
+            NodeKind::CallExpression(callee, args) => {
+                match Self::is_super(callee) {
+                    true => self.super_method("init")?,
+                    false => self.expression(callee)?,
+                }
+                self.call(args)?;
+            }
+            NodeKind::FunctionExpression(params, body) => self.closure(ANONYMOUS, params, body)?,
+            NodeKind::NewExpression(class, args) => {
+                self.expression(class)?;
+                self.emit(Instruction::New);
+                self.call(args)?;
+            }
+            NodeKind::AssignmentExpression(target, operator, value) => {
+                let operator = match operator {
+                    AssignmentOperator::Equals => None,
+                    AssignmentOperator::Addition => Some(BinaryOperator::Plus),
+                    AssignmentOperator::Subtraction => Some(BinaryOperator::Minus),
+                    AssignmentOperator::Multiplication => Some(BinaryOperator::Multiply),
+                    AssignmentOperator::Division => Some(BinaryOperator::Divide),
+                    AssignmentOperator::Modulation => Some(BinaryOperator::Modulo),
+                };
+                self.assignment(target, operator, value)?;
+            }
+            _ => bail!(CompileError::Unsupported(
+                "Using a statement as an expression".to_string()
+            )),
+        }
+
+        Ok(())
+    }
+
+    fn get_variable(&mut self, name: &str) -> Result<(), CompileError> {
+        match self.resolve(name)? {
+            Resolution::Local(slot, _) => {
+                self.emit(Instruction::GetLocal(slot));
+            }
+            Resolution::Upvalue(index, _) => {
+                self.emit(Instruction::GetUpvalue(index));
+            }
+            Resolution::Global => {
+                let name = self.name_constant(name)?;
+                self.emit(Instruction::GetGlobal(name));
+            }
+        }
+        Ok(())
+    }
+
+    // stores the value on top of the stack into an existing variable, leaving it there
+    fn set_variable(&mut self, name: &str) -> Result<(), CompileError> {
+        match self.resolve(name)? {
+            Resolution::Local(_, true) | Resolution::Upvalue(_, true) => {
+                bail!(CompileError::ConstantReassignment(name.to_string()))
+            }
+            Resolution::Local(slot, false) => {
+                self.emit(Instruction::SetLocal(slot));
+            }
+            Resolution::Upvalue(index, false) => {
+                self.emit(Instruction::SetUpvalue(index));
+            }
+            Resolution::Global => {
+                let name = self.name_constant(name)?;
+                self.emit(Instruction::SetGlobal(name));
+            }
+        }
+        Ok(())
+    }
+
+    // leaves the assigned value on the stack
+    fn assignment(
+        &mut self,
+        target: &Node,
+        operator: Option<BinaryOperator>,
+        value: &Node,
+    ) -> Result<(), CompileError> {
+        match &target.kind {
+            NodeKind::Identifier(name) => {
+                if operator.is_none() && self.declares_implicitly(name) {
+                    bail!(CompileError::Unsupported(format!(
+                        "Declaring '{}' inside of an expression",
+                        name
+                    )))
+                }
+                if let Some(operator) = operator {
+                    self.get_variable(name)?;
+                    self.expression(value)?;
+                    self.emit(Instruction::Binary(operator));
+                } else {
+                    self.expression(value)?;
+                }
+                self.set_variable(name)?;
+            }
+            NodeKind::MemberExpression(object, property, computed) => {
+                self.expression(object)?;
+                if *computed {
+                    self.expression(property)?;
+                    if let Some(operator) = operator {
+                        self.emit(Instruction::Dup2);
+                        self.emit(Instruction::GetIndex);
+                        self.expression(value)?;
+                        self.emit(Instruction::Binary(operator));
+                    } else {
+                        self.expression(value)?;
+                    }
+                    self.emit(Instruction::SetIndex);
+                } else {
+                    let name = self.name_constant(&Self::identifier_name(property)?)?;
+                    if let Some(operator) = operator {
+                        self.emit(Instruction::Dup);
+                        self.emit(Instruction::GetProperty(name));
+                        self.expression(value)?;
+                        self.emit(Instruction::Binary(operator));
+                    } else {
+                        self.expression(value)?;
+                    }
+                    self.emit(Instruction::SetProperty(name));
+                }
+            }
+            _ => bail!(CompileError::InvalidAssignmentTarget),
+        }
+
+        Ok(())
+    }
+
+    fn identifier_name(node: &Node) -> Result<String, CompileError> {
+        match &node.kind {
+            NodeKind::Identifier(name) => Ok(name.clone()),
+            _ => bail!(CompileError::InvalidAssignmentTarget),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::runtime::Value;
+
+use super::chunk::{CompiledFunction, Instruction};
+
+/// Renders the bytecode of `function` and of every function nested in it.
+pub fn disassemble(function: &CompiledFunction) -> String {
+    let mut output = String::new();
+    disassemble_into(function, &mut output);
+    output
+}
+
+fn disassemble_into(function: &CompiledFunction, output: &mut String) {
+    let chunk = &function.chunk;
+    output.push_str(&format!("== {} ==\n", function.name));
+
+    for (offset, instruction) in chunk.code.iter().enumerate() {
+        let operand = match instruction {
+            Instruction::Constant(index)
+            | Instruction::DefineGlobal(index, _)
+            | Instruction::GetGlobal(index)
+            | Instruction::SetGlobal(index)
+            | Instruction::GetProperty(index)
+            | Instruction::SetProperty(index)
+            | Instruction::Class(index, _, _)
+            | Instruction::GetSuper(index)
+            | Instruction::Closure(index)
+            | Instruction::CallNamed(_, index)
+            | Instruction::Import(index)
+            | Instruction::ImportAll(index) => match &chunk.constants[*index as usize] {
+                Value::String(value) => format!(" ; {:?}", value),
+                value => format!(" ; {}", value),
+            },
+            _ => String::new(),
+        };
+        output.push_str(&format!("{:04} {:?}{}\n", offset, instruction, operand));
+    }
+
+    for constant in &chunk.constants {
+        if let Value::CompiledFunction(nested) = constant {
+            output.push('\n');
+            disassemble_into(nested, output);
+        }
+    }
+}
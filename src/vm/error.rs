@@ -0,0 +1,53 @@
+#[derive(Debug)]
+pub enum CompileError {
+    ConstantReassignment(String), // constant_name
+    InvalidAssignmentTarget,
+    InvalidSignature(String), // function_name
+    InvalidImport,
+    ReturnOutsideFunction,
+    OutsideOfLoop,
+    TooManyConstants,
+    TooManyLocals,
+    TooManyUpvalues,
+    TooManyArguments,
+    Unsupported(String), // feature description
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::ConstantReassignment(name) => {
+                write!(f, "Cannot reassign constant '{}'", name)
+            }
+            CompileError::InvalidAssignmentTarget => write!(f, "Invalid assignment target"),
+            CompileError::InvalidSignature(name) => {
+                write!(f, "Function '{}' has an invalid parameter list", name)
+            }
+            CompileError::InvalidImport => write!(f, "Only module paths can be imported"),
+            CompileError::ReturnOutsideFunction => {
+                write!(f, "'return' is only allowed inside of a function")
+            }
+            CompileError::OutsideOfLoop => {
+                write!(
+                    f,
+                    "'break' and 'continue' are only allowed inside of a loop"
+                )
+            }
+            CompileError::TooManyConstants => {
+                write!(f, "Too many constants in one function")
+            }
+            CompileError::TooManyLocals => {
+                write!(f, "Too many local variables in one function")
+            }
+            CompileError::TooManyUpvalues => {
+                write!(f, "Too many captured variables in one function")
+            }
+            CompileError::TooManyArguments => write!(f, "Too many arguments in one call"),
+            CompileError::Unsupported(feature) => {
+                write!(f, "{} is not supported by the compiler yet", feature)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
@@ -0,0 +1,1585 @@
+use std::{
+    cell::{RefCell, RefMut},
+    collections::HashMap,
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
+
+use crate::{
+    macros::bail,
+    parser::Node,
+    runtime::{
+        binary_operation, builtins, unary_operation, Class, Environment, Instance, Module,
+        ModuleResolver, Object, Resolved, RuntimeError, Value,
+    },
+};
+
+use super::{
+    budget::{Budget, Fuel, Outcome},
+    chunk::{Capture, Closure, CompiledFunction, Instruction, Upvalue},
+    compiler::Compiler,
+    error::CompileError,
+    timer::{self, Clock},
+};
+
+const MAX_FRAMES: usize = 1024;
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base: usize,            // stack index of the frame's slot 0
+    returns_receiver: bool, // `init` gives back the new instance instead of its own result
+    missing: Vec<u16>,      // slots of the parameters that take their default value
+}
+
+// an execution that ran out of budget or waits for a timer,
+// everything above these sizes belongs to it
+struct Suspended {
+    depth: usize,
+    stack_size: usize,
+    wake_at: Option<Duration>,
+}
+
+/// Stack based virtual machine executing compiled functions.
+///
+/// Execution can be limited with a `Budget`, an execution that runs out of it
+/// is suspended and picks up where it stopped on `Vm::resume`. The same goes
+/// for scripts calling `timer.run_and_wait`, they are resumed once the host
+/// advanced the simulated time far enough.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    builtins: Rc<RefCell<Environment>>,
+    globals: Rc<RefCell<Environment>>, // globals of the main script
+    modules: ModuleResolver,
+    suspended: Option<Suspended>,
+    clock: Rc<RefCell<Clock>>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>, // captured locals that are still on the stack
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let builtins = Rc::new(RefCell::new(builtins::scope()));
+        let clock = Rc::new(RefCell::new(Clock::default()));
+        let mut modules = builtins::resolver();
+        modules.register(timer::module(&clock));
+
+        Self {
+            stack: vec![],
+            frames: vec![],
+            globals: Rc::new(RefCell::new(Environment::with_parent(builtins.clone()))),
+            builtins,
+            modules,
+            suspended: None,
+            clock,
+            open_upvalues: vec![],
+        }
+    }
+
+    pub fn globals(&mut self) -> RefMut<'_, Environment> {
+        self.globals.borrow_mut()
+    }
+
+    pub fn modules(&mut self) -> &mut ModuleResolver {
+        &mut self.modules
+    }
+
+    /// Simulated time in seconds, see `advance_time`.
+    pub fn time(&self) -> f64 {
+        self.clock.borrow().now().as_secs_f64()
+    }
+
+    /// Moves the simulated time forward, this is the only way it passes,
+    /// so timers behave the same on every run.
+    pub fn advance_time(&mut self, seconds: f64) -> Result<(), RuntimeError> {
+        let delta = timer::seconds("advance_time", seconds)?;
+        self.clock.borrow_mut().advance(delta);
+        Ok(())
+    }
+
+    /// True while the suspended execution waits for a timer that didn't fire yet.
+    pub fn is_waiting(&self) -> bool {
+        match self.suspended {
+            Some(Suspended {
+                wake_at: Some(wake_at),
+                ..
+            }) => wake_at > self.clock.borrow().now(),
+            _ => false,
+        }
+    }
+
+    pub fn compile(&self, program: &Node) -> Result<Rc<CompiledFunction>, CompileError> {
+        Compiler::new(self.globals.borrow().names()).compile(program)
+    }
+
+    /// Same as `compile`, but imports are resolved relative to `file`.
+    pub fn compile_file(
+        &self,
+        program: &Node,
+        file: &Path,
+    ) -> Result<Rc<CompiledFunction>, CompileError> {
+        Compiler::new(self.globals.borrow().names())
+            .with_file(file)
+            .compile(program)
+    }
+
+    /// Runs a compiled script and returns the value of its last statement.
+    pub fn run(&mut self, script: Rc<CompiledFunction>) -> Result<Value, RuntimeError> {
+        self.call(&Value::CompiledFunction(script), vec![])
+    }
+
+    pub fn run_with_budget(
+        &mut self,
+        script: Rc<CompiledFunction>,
+        budget: Budget,
+    ) -> Result<Outcome, RuntimeError> {
+        self.call_with_budget(&Value::CompiledFunction(script), vec![], budget)
+    }
+
+    /// Calls a script value from the host and runs it to completion.
+    ///
+    /// This works even while another execution is suspended, which stays suspended.
+    pub fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let depth = self.frames.len();
+        let stack_size = self.stack.len();
+
+        let result = self
+            .start_call(callee, args)
+            .and_then(|called| match called {
+                true => self.execute(depth, &mut Budget::unlimited().start()),
+                false => Ok(self.finish_native()),
+            });
+
+        match result {
+            Ok(Outcome::Finished(value)) => Ok(value),
+            Ok(Outcome::BudgetExhausted) => unreachable!("unlimited budget should not run out"),
+            // there is nobody who could resume it
+            Ok(Outcome::Waiting) => {
+                self.clock.borrow_mut().take_request();
+                self.unwind(depth, stack_size);
+                Err(RuntimeError::CannotWait)
+            }
+            Err(err) => {
+                self.unwind(depth, stack_size);
+                Err(err)
+            }
+        }
+    }
+
+    /// Calls a script value from the host and runs it until it finishes or
+    /// runs out of `budget`.
+    pub fn call_with_budget(
+        &mut self,
+        callee: &Value,
+        args: Vec<Value>,
+        budget: Budget,
+    ) -> Result<Outcome, RuntimeError> {
+        if self.suspended.is_some() {
+            bail!(RuntimeError::AlreadySuspended)
+        }
+
+        let suspended = Suspended {
+            depth: self.frames.len(),
+            stack_size: self.stack.len(),
+            wake_at: None,
+        };
+        match self.start_call(callee, args) {
+            Ok(true) => {
+                self.suspended = Some(suspended);
+                self.resume(budget)
+            }
+            Ok(false) => match self.finish_native() {
+                Outcome::Waiting => {
+                    self.clock.borrow_mut().take_request();
+                    self.unwind(suspended.depth, suspended.stack_size);
+                    Err(RuntimeError::CannotWait)
+                }
+                outcome => Ok(outcome),
+            },
+            Err(err) => {
+                self.unwind(suspended.depth, suspended.stack_size);
+                Err(err)
+            }
+        }
+    }
+
+    /// Continues the suspended execution with a new `budget`.
+    ///
+    /// An execution waiting for a timer stays suspended until its time has come.
+    pub fn resume(&mut self, budget: Budget) -> Result<Outcome, RuntimeError> {
+        if self.is_waiting() {
+            return Ok(Outcome::Waiting);
+        }
+        let depth = match self.suspended {
+            Some(ref mut suspended) => {
+                suspended.wake_at = None;
+                suspended.depth
+            }
+            None => bail!(RuntimeError::NotSuspended),
+        };
+
+        let result = self.execute(depth, &mut budget.start());
+        match result {
+            Ok(Outcome::BudgetExhausted) => {}
+            Ok(Outcome::Waiting) => {
+                let mut clock = self.clock.borrow_mut();
+                let delay = clock.take_request().unwrap_or_default();
+                if let Some(ref mut suspended) = self.suspended {
+                    suspended.wake_at = Some(clock.now() + delay);
+                }
+            }
+            Ok(Outcome::Finished(_)) => self.suspended = None,
+            Err(_) => self.abort(),
+        }
+        result
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    /// Throws away the suspended execution, if there is one.
+    pub fn abort(&mut self) {
+        if let Some(suspended) = self.suspended.take() {
+            self.unwind(suspended.depth, suspended.stack_size);
+        }
+    }
+
+    // pushes the callee with its arguments and calls it,
+    // returns false if it already finished (no frame was pushed)
+    fn start_call(&mut self, callee: &Value, args: Vec<Value>) -> Result<bool, RuntimeError> {
+        self.stack.push(callee.clone());
+        let argc = args.len();
+        self.stack.extend(args);
+        self.call_value(argc, &[])
+    }
+
+    // takes the result of a native function called right from the host
+    fn finish_native(&mut self) -> Outcome {
+        let value = self.pop();
+        match self.clock.borrow().has_request() {
+            true => Outcome::Waiting,
+            false => Outcome::Finished(value),
+        }
+    }
+
+    // throws away everything that an execution left behind
+    fn unwind(&mut self, depth: usize, stack_size: usize) {
+        self.close_upvalues(stack_size);
+        self.frames.truncate(depth);
+        self.stack.truncate(stack_size);
+    }
+
+    // the upvalue of the local at `index`, closures capturing the same local share it
+    fn capture_upvalue(&mut self, index: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == index));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(index)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    // moves the captured locals at or above `from` off the stack into their upvalues
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(index) if index >= from => {
+                    *upvalue = Upvalue::Closed(stack[index].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack should not underflow")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("stack should not underflow")
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames
+            .last()
+            .expect("vm should have a frame to execute")
+    }
+
+    fn frame_globals(&self) -> Rc<RefCell<Environment>> {
+        self.frame().closure.globals.clone()
+    }
+
+    fn constant(&self, index: u16) -> Value {
+        self.frame().closure.function.chunk.constants[index as usize].clone()
+    }
+
+    fn name(&self, index: u16) -> String {
+        match &self.frame().closure.function.chunk.constants[index as usize] {
+            Value::String(name) => name.clone(),
+            constant => constant.to_string(),
+        }
+    }
+
+    // runs until the frame at `depth` returns or the fuel runs out,
+    // errors get the span of the instruction that failed
+    fn execute(&mut self, depth: usize, fuel: &mut Fuel) -> Result<Outcome, RuntimeError> {
+        self.execute_instructions(depth, fuel).map_err(|err| {
+            let Some(frame) = self.frames.last() else {
+                return err;
+            };
+            let function = &frame.closure.function;
+            match function.chunk.spans.get(frame.ip.saturating_sub(1)) {
+                Some(span) => err.at(*span, function.file.clone()),
+                None => err,
+            }
+        })
+    }
+
+    fn execute_instructions(
+        &mut self,
+        depth: usize,
+        fuel: &mut Fuel,
+    ) -> Result<Outcome, RuntimeError> {
+        loop {
+            if !fuel.take() {
+                return Ok(Outcome::BudgetExhausted);
+            }
+
+            let frame = self
+                .frames
+                .last_mut()
+                .expect("vm should have a frame to execute");
+            let instruction = frame.closure.function.chunk.code[frame.ip];
+            let base = frame.base;
+            frame.ip += 1;
+
+            match instruction {
+                Instruction::Constant(index) => {
+                    let constant = self.constant(index);
+                    self.stack.push(constant);
+                }
+                Instruction::Null => self.stack.push(Value::Null),
+                Instruction::True => self.stack.push(Value::Bool(true)),
+                Instruction::False => self.stack.push(Value::Bool(false)),
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Dup => self.stack.push(self.peek().clone()),
+                Instruction::Dup2 => {
+                    let length = self.stack.len();
+                    self.stack.extend_from_within(length - 2..);
+                }
+                Instruction::DefineGlobal(name, is_constant) => {
+                    let name = self.name(name);
+                    let value = self.pop();
+                    self.frame_globals()
+                        .borrow_mut()
+                        .declare(&name, value, is_constant)?;
+                }
+                Instruction::GetGlobal(name) => {
+                    let value = self.frame_globals().borrow().lookup(&self.name(name))?;
+                    self.stack.push(value);
+                }
+                Instruction::SetGlobal(name) => {
+                    let name = self.name(name);
+                    let value = self.peek().clone();
+                    let globals = self.frame_globals();
+                    let mut globals = globals.borrow_mut();
+                    if !globals.assign(&name, value.clone())? {
+                        globals.declare(&name, value, false)?;
+                    }
+                }
+                Instruction::GetLocal(slot) => {
+                    self.stack.push(self.stack[base + slot as usize].clone());
+                }
+                Instruction::SetLocal(slot) => {
+                    self.stack[base + slot as usize] = self.peek().clone();
+                }
+                Instruction::GetUpvalue(index) => {
+                    let upvalue = self.frame().closure.upvalues[index as usize].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(index) => self.stack[*index].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Instruction::SetUpvalue(index) => {
+                    let upvalue = self.frame().closure.upvalues[index as usize].clone();
+                    let value = self.peek().clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(index) => self.stack[*index] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                Instruction::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                Instruction::Binary(operator) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(binary_operation(operator, &left, &right)?);
+                }
+                Instruction::CompareOrJump(operator, target) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    if binary_operation(operator, &left, &right)?.is_truthy() {
+                        // the right operand is the left one of the next comparison
+                        self.stack.push(right);
+                    } else {
+                        self.stack.push(Value::Bool(false));
+                        self.jump(target);
+                    }
+                }
+                Instruction::Unary(operator) => {
+                    let operand = self.pop();
+                    self.stack.push(unary_operation(operator, &operand)?);
+                }
+                Instruction::Jump(target) => self.jump(target),
+                Instruction::JumpIfGiven(slot, target) => {
+                    if !self.frame().missing.contains(&slot) {
+                        self.jump(target);
+                    }
+                }
+                Instruction::JumpIfFalse(target) => {
+                    if !self.pop().is_truthy() {
+                        self.jump(target);
+                    }
+                }
+                Instruction::JumpIfFalseOrPop(target) => {
+                    if self.peek().is_truthy() {
+                        self.pop();
+                    } else {
+                        self.jump(target);
+                    }
+                }
+                Instruction::JumpIfTrueOrPop(target) => {
+                    if self.peek().is_truthy() {
+                        self.jump(target);
+                    } else {
+                        self.pop();
+                    }
+                }
+                Instruction::BuildList(count) => {
+                    let items = self.stack.split_off(self.stack.len() - count as usize);
+                    self.stack.push(Value::list(items));
+                }
+                Instruction::BuildObject(count) => {
+                    let mut values = self
+                        .stack
+                        .split_off(self.stack.len() - 2 * count as usize)
+                        .into_iter();
+                    let mut pairs = vec![];
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        pairs.push((key, value));
+                    }
+                    self.stack.push(Value::object(Object::from_pairs(pairs)?));
+                }
+                Instruction::GetProperty(name) => {
+                    let object = self.pop();
+                    self.stack.push(object.get_property(&self.name(name))?);
+                }
+                Instruction::SetProperty(name) => {
+                    let value = self.pop();
+                    let object = self.pop();
+                    object.set_property(&self.name(name), value.clone())?;
+                    self.stack.push(value);
+                }
+                Instruction::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    self.stack.push(object.get_index(&index)?);
+                }
+                Instruction::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    object.set_index(&index, value.clone())?;
+                    self.stack.push(value);
+                }
+                Instruction::Iterate => {
+                    let iterable = self.pop();
+                    self.stack.push(Value::list(iterable.iterate()?));
+                }
+                Instruction::IterNext(slot, exit) => {
+                    let slot = base + slot as usize;
+                    let index = match self.stack[slot + 1] {
+                        Value::Integer(index) => index as usize,
+                        _ => unreachable!("iterator index should be an integer"),
+                    };
+                    let item = match &self.stack[slot] {
+                        Value::List(items) => items.borrow().get(index).cloned(),
+                        _ => unreachable!("iterator items should be a list"),
+                    };
+                    match item {
+                        Some(item) => {
+                            self.stack[slot + 1] = Value::Integer(index as i64 + 1);
+                            self.stack.push(item);
+                        }
+                        None => self.jump(exit),
+                    }
+                }
+                Instruction::Call(argc) => {
+                    // the native result is already on the stack when the script wakes up
+                    if !self.call_value(argc as usize, &[])? && self.clock.borrow().has_request() {
+                        return Ok(Outcome::Waiting);
+                    }
+                }
+                Instruction::CallNamed(argc, names) => {
+                    let names: Vec<String> = match self.constant(names) {
+                        Value::List(names) => {
+                            names.borrow().iter().map(|name| name.to_string()).collect()
+                        }
+                        _ => unreachable!("argument names should be a list"),
+                    };
+                    if !self.call_value(argc as usize, &names)? && self.clock.borrow().has_request()
+                    {
+                        return Ok(Outcome::Waiting);
+                    }
+                }
+                Instruction::New => {
+                    let class = self.peek();
+                    if !matches!(class, Value::Class(_)) {
+                        bail!(RuntimeError::NotConstructible(class.type_name()))
+                    }
+                }
+                Instruction::Closure(function) => {
+                    let function = match self.constant(function) {
+                        Value::CompiledFunction(function) => function,
+                        _ => unreachable!("closure constant should be a function"),
+                    };
+                    let mut upvalues = vec![];
+                    for capture in &function.captures {
+                        upvalues.push(match *capture {
+                            Capture::Local(slot) => self.capture_upvalue(base + slot as usize),
+                            Capture::Upvalue(index) => {
+                                self.frame().closure.upvalues[index as usize].clone()
+                            }
+                        });
+                    }
+                    // functions nested in a method share its `super`
+                    let closure = Closure {
+                        function,
+                        globals: self.frame_globals(),
+                        superclass: self.frame().closure.superclass.clone(),
+                        upvalues,
+                    };
+                    self.stack.push(Value::Closure(Rc::new(closure)));
+                }
+                Instruction::Import(path) => {
+                    let module = self.import(&self.name(path))?;
+                    self.stack.push(Value::Module(module));
+                }
+                Instruction::ImportAll(path) => {
+                    let module = self.import(&self.name(path))?;
+                    let globals = self.frame_globals();
+                    let mut globals = globals.borrow_mut();
+                    for (name, value) in &module.exports {
+                        globals.declare(name, value.clone(), false)?;
+                    }
+                }
+                Instruction::Class(name, fields, methods) => {
+                    let class = self.class(name, fields, methods)?;
+                    self.stack.push(class);
+                }
+                Instruction::GetSuper(name) => {
+                    let receiver = self.pop();
+                    let method = match &self.frame().closure.superclass {
+                        Some(superclass) => superclass.bind_super(receiver, &self.name(name))?,
+                        None => bail!(RuntimeError::MissingSuper),
+                    };
+                    self.stack.push(method);
+                }
+                Instruction::Return => {
+                    let mut result = self.pop();
+                    let frame = self
+                        .frames
+                        .pop()
+                        .expect("vm should have a frame to return from");
+                    if frame.returns_receiver {
+                        result = self.stack[frame.base].clone();
+                    }
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+
+                    if self.frames.len() == depth {
+                        return Ok(Outcome::Finished(result));
+                    }
+                    self.stack.push(result);
+                }
+            }
+        }
+    }
+
+    fn jump(&mut self, target: u32) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = target as usize;
+        }
+    }
+
+    // calls the value below the `argc` arguments on top of the stack, the last
+    // `names.len()` of them are named, returns false if the result is already
+    // on the stack (no frame was pushed)
+    fn call_value(&mut self, argc: usize, names: &[String]) -> Result<bool, RuntimeError> {
+        let callee_index = self.stack.len() - argc - 1;
+        let callee = self.stack[callee_index].clone();
+
+        match callee {
+            Value::Closure(closure) => {
+                self.push_frame(closure, argc, names, false)?;
+                Ok(true)
+            }
+            // functions handed over by the host run in the globals of the main script
+            Value::CompiledFunction(function) => {
+                let closure = Closure {
+                    function,
+                    globals: self.globals.clone(),
+                    superclass: None,
+                    upvalues: vec![],
+                };
+                self.push_frame(Rc::new(closure), argc, names, false)?;
+                Ok(true)
+            }
+            Value::BoundMethod(bound) => match &bound.method {
+                Value::Closure(method) => {
+                    self.stack[callee_index] = bound.receiver.clone();
+                    self.push_frame(method.clone(), argc, names, false)?;
+                    Ok(true)
+                }
+                _ => bail!(RuntimeError::Unsupported(
+                    "Calling a tree-walking method from bytecode".to_string()
+                )),
+            },
+            Value::NativeFunction(native) => {
+                if !names.is_empty() {
+                    bail!(RuntimeError::InvalidArgument(
+                        native.name.clone(),
+                        "doesn't take named arguments".to_string()
+                    ))
+                }
+                if let Some(arity) = native.arity {
+                    if arity != argc {
+                        bail!(RuntimeError::ArityMismatch(
+                            native.name.clone(),
+                            arity,
+                            argc
+                        ))
+                    }
+                }
+                let args = self.stack.split_off(callee_index + 1);
+                self.pop();
+                let result = (native.function)(args)?;
+                self.stack.push(result);
+                Ok(false)
+            }
+            Value::Class(class) => {
+                let instance = Instance::new(&class);
+                self.stack[callee_index] = Value::Instance(Rc::new(RefCell::new(instance)));
+                match class.find_method("init") {
+                    Some(Value::Closure(init)) => {
+                        self.push_frame(init, argc, names, true)?;
+                        Ok(true)
+                    }
+                    Some(_) => bail!(RuntimeError::Unsupported(
+                        "Calling a tree-walking method from bytecode".to_string()
+                    )),
+                    None if !names.is_empty() => bail!(RuntimeError::InvalidArgument(
+                        class.name.clone(),
+                        "doesn't take named arguments".to_string()
+                    )),
+                    None if argc > 0 => {
+                        bail!(RuntimeError::ArityMismatch(class.name.clone(), 0, argc))
+                    }
+                    None => Ok(false),
+                }
+            }
+            Value::Function(_) => bail!(RuntimeError::Unsupported(
+                "Calling a tree-walking function from bytecode".to_string()
+            )),
+            value => bail!(RuntimeError::NotCallable(value.type_name())),
+        }
+    }
+
+    fn push_frame(
+        &mut self,
+        closure: Rc<Closure>,
+        argc: usize,
+        names: &[String],
+        returns_receiver: bool,
+    ) -> Result<(), RuntimeError> {
+        if self.frames.len() >= MAX_FRAMES {
+            bail!(RuntimeError::StackOverflow)
+        }
+
+        let base = self.stack.len() - argc - 1;
+        let function = &closure.function;
+        let mut missing = vec![];
+        // only calls that don't simply pass every parameter need their arguments rearranged
+        if !names.is_empty() || function.signature.rest.is_some() || function.arity() != argc {
+            let mut args = self.stack.split_off(base + 1);
+            let named = args.split_off(args.len() - names.len());
+            let named = names.iter().cloned().zip(named).collect();
+            let values = function.signature.bind(&function.name, args, named)?;
+            for (slot, value) in (1..).zip(values) {
+                self.stack.push(value.unwrap_or_else(|| {
+                    missing.push(slot);
+                    Value::Null
+                }));
+            }
+        }
+
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base,
+            returns_receiver,
+            missing,
+        });
+        Ok(())
+    }
+
+    // user modules run to completion right away, even inside of a budgeted execution
+    fn import(&mut self, path: &str) -> Result<Rc<Module>, RuntimeError> {
+        let importer = self.frame().closure.function.file.clone();
+        let (file, program) = match self.modules.resolve(path, importer.as_deref())? {
+            Resolved::Loaded(module) => return Ok(module),
+            Resolved::Program(file, program) => (file, program),
+        };
+
+        // every module gets its own globals
+        let globals = Rc::new(RefCell::new(Environment::with_parent(
+            self.builtins.clone(),
+        )));
+        let compiled = Compiler::new(globals.borrow().names())
+            .with_file(&file)
+            .compile(&program);
+        let result = match compiled {
+            Ok(function) => {
+                let closure = Closure {
+                    function,
+                    globals: globals.clone(),
+                    superclass: None,
+                    upvalues: vec![],
+                };
+                self.call(&Value::Closure(Rc::new(closure)), vec![])
+            }
+            Err(err) => Err(RuntimeError::InvalidModule(
+                file.display().to_string(),
+                err.to_string(),
+            )),
+        };
+
+        match result {
+            Ok(_) => Ok(self.modules.finish(file, globals.borrow().variables())),
+            Err(err) => {
+                self.modules.fail(&file);
+                Err(err)
+            }
+        }
+    }
+
+    // builds a class out of the superclass (or null), `fields` name/value pairs
+    // and `methods` name/function pairs
+    fn class(&mut self, name: u16, fields: u16, methods: u16) -> Result<Value, RuntimeError> {
+        let name = self.name(name);
+        let mut members = self
+            .stack
+            .split_off(self.stack.len() - 2 * (fields as usize + methods as usize))
+            .into_iter();
+        let superclass = match self.pop() {
+            Value::Class(superclass) => Some(superclass),
+            Value::Null => None,
+            value => bail!(RuntimeError::WrongType(
+                format!("Superclass of '{}'", name),
+                "class".to_string(),
+                value.type_name()
+            )),
+        };
+
+        let mut field_values = vec![];
+        for _ in 0..fields {
+            match (members.next(), members.next()) {
+                (Some(Value::String(field)), Some(value)) => field_values.push((field, value)),
+                _ => unreachable!("class fields should be name/value pairs"),
+            }
+        }
+        let mut method_values = HashMap::new();
+        for _ in 0..methods {
+            let (Some(Value::String(method)), Some(Value::Closure(closure))) =
+                (members.next(), members.next())
+            else {
+                unreachable!("class methods should be name/closure pairs")
+            };
+            // methods remember the superclass for `super`
+            let closure = Closure {
+                function: closure.function.clone(),
+                globals: closure.globals.clone(),
+                superclass: superclass.clone(),
+                upvalues: closure.upvalues.clone(),
+            };
+            method_values.insert(method, Value::Closure(Rc::new(closure)));
+        }
+
+        Ok(Value::Class(Rc::new(Class {
+            name,
+            superclass,
+            fields: field_values,
+            methods: method_values,
+            statics: RefCell::new(HashMap::new()),
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        lexer::{Lexer, Span},
+        parser::{NodeKind, Parser},
+        runtime::Interpreter,
+        vm::disassemble,
+    };
+
+    fn parse(source: &str) -> Node {
+        let mut lexer = Lexer::new(source.to_string());
+        if lexer.tokenize().is_err() {
+            panic!("Failed to tokenize source: {}", source);
+        }
+        match Parser::new(lexer.tokens).produce_ast() {
+            Ok(program) => program,
+            Err(err) => panic!("Failed to parse source: {}", err),
+        }
+    }
+
+    // errors come without their location, the tests match on what went wrong
+    fn run(source: &str) -> Result<Value, RuntimeError> {
+        let program = parse(source);
+        let mut vm = Vm::new();
+        match vm.compile(&program) {
+            Ok(script) => vm.run(script).map_err(RuntimeError::without_location),
+            Err(err) => panic!("Failed to compile source: {}", err),
+        }
+    }
+
+    // runs the source on both backends and expects the same value
+    fn run_both(source: &str) -> String {
+        let expected = match Interpreter::new().interpret(&parse(source)) {
+            Ok(value) => format!("{:?}", value),
+            Err(err) => panic!("Failed to interpret source: {}", err),
+        };
+        let got = match run(source) {
+            Ok(value) => format!("{:?}", value),
+            Err(err) => panic!("Failed to run source: {}", err),
+        };
+        assert_eq!(got, expected, "backends disagree on: {}", source);
+        got
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(run_both("5 + 10 - (3 * 10)"), "-15");
+        assert_eq!(run_both("50 / 10"), "5.0");
+        assert_eq!(run_both("[1, \"two\", 3.0]"), "[1, \"two\", 3.0]");
+        assert_eq!(run_both("null or 0 or \"x\""), "\"x\"");
+        assert_eq!(run_both("1 and 0"), "0");
+        assert_eq!(run_both("-(2 + 3)"), "-5");
+        assert_eq!(run_both("!(1 > 2)"), "true");
+        assert_eq!(run_both("\"ab\" * 2"), "\"abab\"");
+    }
+
+    #[test]
+    fn test_cycles() {
+        // lists and objects that contain themselves are compared and printed without recursing forever
+        let source = "let a = [0]\na[0] = a\nlet b = [0]\nb[0] = b\n\
+                      [a == a, a == b, a == [a], a != [1], a]";
+        assert_eq!(run_both(source), "[true, true, true, true, [[...]]]");
+        let source = "let o = {\"n\": 1}\no.me = o\nlet p = {\"n\": 1}\np.me = p\n\
+                      [o == p, o]";
+        assert_eq!(run_both(source), "[true, {\"n\": 1, \"me\": {...}}]");
+        assert_eq!(run_both("let a = []\nlet b = [a, a]\nb"), "[[], []]");
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(
+            run_both("[\"ab\" * -3, [1] * -9223372036854775807, 2 * [1, 2]]"),
+            "[\"\", [], [1, 2, 1, 2]]"
+        );
+        assert_eq!(
+            run_both("[\"\" * 9223372036854775807, [] * 9223372036854775807]"),
+            "[\"\", []]"
+        );
+        // results that don't fit in memory are errors rather than crashes
+        let cases = [
+            ("\"ab\" * 4611686018427387904", false),
+            ("[1] * 9223372036854775807", false),
+            ("9223372036854775807 * [1, 2, 3]", true),
+        ];
+        for (source, overflows) in cases {
+            let interpreted = Interpreter::new()
+                .interpret(&parse(source))
+                .map_err(RuntimeError::without_location);
+            for result in [interpreted, run(source)] {
+                match result {
+                    Err(RuntimeError::IntegerOverflow) if overflows => {}
+                    Err(RuntimeError::TooLarge(operator, _)) if !overflows => {
+                        assert_eq!(operator, "*")
+                    }
+                    result => panic!("Unexpected result of {}: {:?}", source, result),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_locations() {
+        // both backends point at the innermost code that failed: line, first and last column
+        let cases = [
+            (
+                "let speed = 1\nfn turn(by) {\n    return speed + by\n}\nturn(null)",
+                (3, 11, 21),
+            ),
+            ("let xs = [1]\nlet x = xs[3]", (2, 8, 13)),
+            ("fn f() { }\nlet x = 1\n  f(x)", (3, 2, 6)),
+        ];
+        for (source, (line, start, end)) in cases {
+            let interpreted = Interpreter::new().interpret(&parse(source)).unwrap_err();
+            let mut vm = Vm::new();
+            let compiled = vm.run(vm.compile(&parse(source)).unwrap()).unwrap_err();
+            for err in [interpreted, compiled] {
+                let location = err.location().expect("errors should have a location");
+                let span = location.span;
+                assert_eq!(
+                    (span.start.line, span.start.column, span.end.column),
+                    (line, start, end),
+                    "wrong location for '{}' in: {}",
+                    err,
+                    source
+                );
+                assert!(location.file.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(run_both("let y = 5\ny += 56\ny -= 12\ny *= 2\ny"), "98");
+        assert_eq!(run_both("let t\nt"), "null");
+        assert_eq!(run_both("let i = 1\ni++\ni++\ni--\ni"), "2");
+        assert_eq!(run_both("x = 3\nx"), "3");
+        assert_eq!(run_both("let a = 1\nif true { let a = 2 }\na"), "1");
+        assert_eq!(run_both("let a = 1\nif true { a = 2 }\na"), "2");
+        assert!(matches!(
+            run("const PI = 3.14\nPI = 3"),
+            Err(RuntimeError::ConstantReassignment(_))
+        ));
+        assert!(matches!(
+            run("unknown"),
+            Err(RuntimeError::UndefinedVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_eq!(
+            run_both("let x = 0\nif x > 1 { x = 10 } else if x == 0 { x = 20 } else { x = 30 }\nx"),
+            "20"
+        );
+        assert_eq!(
+            run_both("let sum = 0\nfor i in range(5) { sum += i }\nsum"),
+            "10"
+        );
+        assert_eq!(
+            run_both(
+                "let out = [\"\"]\nfor c in \"ab\" { for n in [1, 2] { out = out + [c * n] } }\nout"
+            ),
+            "[\"\", \"a\", \"aa\", \"b\", \"bb\"]"
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(run_both("fn add(a, b) { return a + b }\nadd(2, 3)"), "5");
+        assert_eq!(
+            run_both(
+                "fn fib(n) { if n < 2 { return n }\nreturn fib(n - 1) + fib(n - 2) }\nfib(15)"
+            ),
+            "610"
+        );
+        assert_eq!(
+            run_both(
+                "fn f() { let x = 1\nfor i in [1, 2, 3] { if i == 2 { return i + x } } }\nf()"
+            ),
+            "3"
+        );
+        assert_eq!(run_both("fn f() { }\nf()"), "null");
+        assert_eq!(
+            run_both("fn f(x) { if x { return }\nreturn 1 }\n[f(true), f(false)]"),
+            "[null, 1]"
+        );
+        assert_eq!(
+            run_both("class A { x = 0\nfn init() { self.x = 1\nreturn\nself.x = 2 } }\nA().x"),
+            "1"
+        );
+        assert_eq!(run_both("len([1, 2, 3])"), "3");
+        assert_eq!(
+            run_both("fn f() { a = b = 3\nb += 1\nreturn [a, b] }\nf()"),
+            "[3, 4]"
+        );
+        assert_eq!(
+            run_both("fn f() { let a = 0\na = b = c = 2\nreturn [a, b, c] }\nf()"),
+            "[2, 2, 2]"
+        );
+        assert!(matches!(
+            run("fn f(a) { }\nf()"),
+            Err(RuntimeError::ArityMismatch(_, 1, 0))
+        ));
+        assert!(matches!(
+            run("fn f() { f() }\nf()"),
+            Err(RuntimeError::StackOverflow)
+        ));
+
+        // the parser only produces parameters, a tree built by hand may not
+        let node = |kind| Box::new(Node::new(kind, Span::default()));
+        let program = Node::new(
+            NodeKind::Program(vec![node(NodeKind::FunctionDeclaration(
+                node(NodeKind::Identifier("f".to_string())),
+                vec![node(NodeKind::Identifier("x".to_string()))],
+                node(NodeKind::BlockStatement(vec![])),
+            ))]),
+            Span::default(),
+        );
+        assert!(matches!(
+            Vm::new().compile(&program),
+            Err(CompileError::InvalidSignature(name)) if name == "f"
+        ));
+    }
+
+    #[test]
+    fn test_classes() {
+        let source = "class Counter {\n\
+                          count = 0\n\
+                          fn init(start) { self.count = start }\n\
+                          fn increment() { self.count += 1\nreturn self.count }\n\
+                      }\n\
+                      let c = Counter(5)\n\
+                      c.increment()\n\
+                      c.increment()";
+        assert_eq!(run_both(source), "7");
+        assert_eq!(
+            run_both("class A { items = [0] }\nlet a = A()\nlet b = A()\na.items = [1]\nb.items"),
+            "[0]"
+        );
+        // defaults are copied for every instance, changing one in place leaves the others alone
+        let source = "class Bag { items = [0]\nmeta = {\"n\": 0} }\n\
+                      class Sack from Bag {}\n\
+                      let a = Bag()\nlet b = Bag()\nlet c = Sack()\n\
+                      a.items[0] = 1\n\
+                      a.meta.n = 1\n\
+                      [a.items, b.items, c.items, b.meta.n]";
+        assert_eq!(run_both(source), "[[1], [0], [0], 0]");
+        assert_eq!(
+            run_both("let xs = [1, 2]\nxs[0] += 10\nxs[1] = 5\nxs"),
+            "[11, 5]"
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let source = "let i = 0\n\
+                      let odd = 0\n\
+                      while true {\n\
+                          i += 1\n\
+                          let half = i / 2\n\
+                          if i % 2 == 0 { continue }\n\
+                          if i > 9 { break }\n\
+                          odd += i\n\
+                      }\n\
+                      [i, odd]";
+        assert_eq!(run_both(source), "[11, 25]");
+
+        let source = "fn pairs(n) {\n\
+                          let found = 0\n\
+                          for i in range(n) {\n\
+                              let j = 0\n\
+                              while j < n {\n\
+                                  j += 1\n\
+                                  if j < i + 1 { continue }\n\
+                                  if j == 4 { break }\n\
+                                  let pair = [i, j]\n\
+                                  found += 1\n\
+                              }\n\
+                              if i == 2 { break }\n\
+                          }\n\
+                          return found\n\
+                      }\n\
+                      pairs(5)";
+        assert_eq!(run_both(source), "6");
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        let source = "let calls = 0\n\
+                      fn mid() {\n\
+                          calls += 1\n\
+                          return 5\n\
+                      }\n\
+                      let x = 5\n\
+                      let checks = [0 <= x < 10, 0 <= x < 5, 1 == 1 == 1, 1 < mid() <= 5 >= 2]\n\
+                      let skipped = 9 < mid() < missing\n\
+                      let result = [checks, skipped, calls]\n\
+                      result";
+        assert_eq!(run_both(source), "[[true, false, true, true], false, 2]");
+    }
+
+    #[test]
+    fn test_not_and_is() {
+        let source = "let xs = [1]\n\
+                      let same = xs\n\
+                      let found = null\n\
+                      let checks = [not found and true, not 1 == 2, xs is same, xs is [1]]\n\
+                      let nulls = [found is null, 0 is not null, null is not null is null]\n\
+                      let result = [checks, nulls]\n\
+                      result";
+        assert_eq!(
+            run_both(source),
+            "[[true, true, true, false], [true, true, false]]"
+        );
+    }
+
+    #[test]
+    fn test_function_expression() {
+        let source = "fn apply(f, x) { return f(x) }\n\
+                      let offset = 10\n\
+                      let handlers = { on_detect: fn(obj) { return obj + offset } }\n\
+                      let twice = def(x) => x * 2\n\
+                      let result = [apply(handlers.on_detect, 1), apply(twice, 4), (fn() => offset)()]\n\
+                      result";
+        assert_eq!(run_both(source), "[11, 8, 10]");
+    }
+
+    #[test]
+    fn test_parameters() {
+        let source = "fn move(x, speed = x * 10, *rest) { return [x, speed, rest] }\n\
+                      class Robot {\n\
+                          speed = 0\n\
+                          fn init(speed = 50) { self.speed = speed }\n\
+                          fn turn(angle = 90, clockwise = true) { return [angle, clockwise] }\n\
+                      }\n\
+                      let robot = new Robot(speed=20)\n\
+                      let counter = 0\n\
+                      fn next(step = counter += 1) { return step }\n\
+                      let result = [move(1), move(1, 2, 3, 4), move(speed=30, x=2), move(2, speed=5),\n\
+                          robot.speed, Robot().speed, robot.turn(clockwise=false), next(), next(7), next()]\n\
+                      result";
+        assert_eq!(
+            run_both(source),
+            "[[1, 10, []], [1, 2, [3, 4]], [2, 30, []], [2, 5, []], \
+             20, 50, [90, false], 1, 7, 2]"
+        );
+
+        for source in [
+            "fn f(a, b = 1) {}\nf(b=2)",
+            "fn f(a) {}\nf(1, a=2)",
+            "fn f(a) {}\nf(b=2)",
+            "len(value=[1])",
+            "class A { }\nA(a=1)",
+        ] {
+            assert!(
+                matches!(run(source), Err(RuntimeError::InvalidArgument(..))),
+                "{}",
+                source
+            );
+        }
+        assert!(matches!(
+            run("fn f(a, b = 1) {}\nf()"),
+            Err(RuntimeError::ArityMismatch(_, 1, 0))
+        ));
+        assert!(matches!(
+            run("fn f(a, b = 1) {}\nf(1, 2, 3)"),
+            Err(RuntimeError::ArityMismatch(_, 2, 3))
+        ));
+        // a default can't see the parameters after it
+        assert!(matches!(
+            run("fn f(a = b, b = 1) { return a }\nf()"),
+            Err(RuntimeError::UndefinedVariable(..))
+        ));
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "fn counter() {\n\
+                          let count = 0\n\
+                          fn increment() {\n\
+                              count += 1\n\
+                              return count\n\
+                          }\n\
+                          return increment\n\
+                      }\n\
+                      let a = counter()\n\
+                      let b = counter()\n\
+                      a()\n\
+                      a()\n\
+                      let result = [a(), b()]\n\
+                      result";
+        assert_eq!(run_both(source), "[3, 1]");
+
+        // closures share the variable, not a copy of its value
+        let source = "fn pair() {\n\
+                          let value = 1\n\
+                          let get = fn() => value\n\
+                          let set = fn(v) { value = v }\n\
+                          return [get, set]\n\
+                      }\n\
+                      let accessors = pair()\n\
+                      accessors[1](5)\n\
+                      accessors[0]()";
+        assert_eq!(run_both(source), "5");
+
+        // captured through a function in between
+        let source = "fn outer(x) {\n\
+                          fn middle() {\n\
+                              return fn(y) => x + y\n\
+                          }\n\
+                          x += 1\n\
+                          return middle()\n\
+                      }\n\
+                      outer(10)(5)";
+        assert_eq!(run_both(source), "16");
+
+        let source = "fn countdown() {\n\
+                          let steps = 0\n\
+                          fn step(n) {\n\
+                              if n == 0 { return steps }\n\
+                              steps += 1\n\
+                              return step(n - 1)\n\
+                          }\n\
+                          return step(4)\n\
+                      }\n\
+                      countdown()";
+        assert_eq!(run_both(source), "4");
+    }
+
+    #[test]
+    fn test_loop_captures() {
+        // every iteration of a for loop captures its own variable
+        let source = "let callbacks = [null, null, null, null]\n\
+                      for i in range(4) {\n\
+                          if i == 1 { continue }\n\
+                          let scaled = i * 10\n\
+                          callbacks[i] = fn() => [i, scaled]\n\
+                          if i == 2 { break }\n\
+                      }\n\
+                      let result = [callbacks[0](), callbacks[1], callbacks[2](), callbacks[3]]\n\
+                      result";
+        assert_eq!(run_both(source), "[[0, 0], null, [2, 20], null]");
+
+        // variables declared outside of the loop are shared by all iterations
+        let source = "fn collect() {\n\
+                          let callbacks = [null, null, null]\n\
+                          let j = 0\n\
+                          while j < 3 {\n\
+                              let k = j\n\
+                              callbacks[j] = fn() => [j, k]\n\
+                              j += 1\n\
+                          }\n\
+                          return callbacks\n\
+                      }\n\
+                      let callbacks = collect()\n\
+                      let result = [callbacks[0](), callbacks[2]()]\n\
+                      result";
+        assert_eq!(run_both(source), "[[3, 0], [3, 2]]");
+    }
+
+    #[test]
+    fn test_method_captures() {
+        let source = "class Sensor {\n\
+                          fn init() { self.handlers = [] }\n\
+                          fn trigger(handler) { return handler(7) }\n\
+                      }\n\
+                      class Robot from Sensor {\n\
+                          seen = 0\n\
+                          fn watch() {\n\
+                              return super.trigger(fn(obj) {\n\
+                                  self.seen += obj\n\
+                                  return super.trigger(fn(x) => x + self.seen)\n\
+                              })\n\
+                          }\n\
+                      }\n\
+                      let robot = Robot()\n\
+                      let result = [robot.watch(), robot.seen]\n\
+                      result";
+        assert_eq!(run_both(source), "[14, 7]");
+    }
+
+    #[test]
+    fn test_capture_errors() {
+        let vm = Vm::new();
+        assert!(matches!(
+            vm.compile(&parse("fn f() {\nconst x = 1\nfn g() { x = 2 }\n}")),
+            Err(CompileError::ConstantReassignment(name)) if name == "x"
+        ));
+
+        // a failed call still hands the captured values over to its closures
+        let mut vm = Vm::new();
+        let script = vm
+            .compile(&parse(
+                "let saved = null\nfn f() {\nlet x = 1\nsaved = fn() => x\nx = 2\nmissing()\n}\nf()",
+            ))
+            .unwrap();
+        assert!(vm.run(script).is_err());
+        let saved = vm.globals().lookup("saved").unwrap();
+        assert_eq!(vm.call(&saved, vec![]).unwrap(), Value::Integer(2));
+    }
+
+    #[test]
+    fn test_objects() {
+        let source = "let key = \"speed\"\n\
+                      let robot = { \"name\": \"R2\", [key]: 1.5, parts: { wheels: 4 }, }\n\
+                      robot.parts.wheels += 2\n\
+                      robot[\"name\"] = \"R3\"\n\
+                      { robot.color = \"red\" }\n\
+                      [robot, len(robot), robot.parts == { wheels: 6 }]";
+        assert_eq!(
+            run_both(source),
+            "[{\"name\": \"R3\", \"speed\": 1.5, \"parts\": {\"wheels\": 6}, \"color\": \"red\"}, 4, true]"
+        );
+        assert!(matches!(
+            run("let o = { [1]: 1 }"),
+            Err(RuntimeError::WrongType(..))
+        ));
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let source = "class Creature {\n\
+                          kind = \"unknown\"\n\
+                          legs = 0\n\
+                          fn init(kind) { self.kind = kind }\n\
+                          fn describe() { return [self.kind, self.legs] }\n\
+                      }\n\
+                      class Human from Creature {\n\
+                          legs = 2\n\
+                          fn init() { super(\"human\") }\n\
+                      }\n\
+                      class Person from Human {\n\
+                          name = null\n\
+                          static count = 0\n\
+                          fn init(name) { super()\nself.name = name\nPerson.count += 1 }\n\
+                          static fn default() { return new Person(\"unknown\") }\n\
+                          fn describe() { return [self.name, super.describe()] }\n\
+                      }\n\
+                      let jeff = new Person(\"Jeff\")\n\
+                      let nobody = Person.default()\n\
+                      let result = [jeff.describe(), nobody.name, Person.count]\n\
+                      result";
+        assert_eq!(
+            run_both(source),
+            "[[\"Jeff\", [\"human\", 2]], \"unknown\", 2]"
+        );
+
+        assert!(matches!(
+            run("class A { fn init() { super() } }\nA()"),
+            Err(RuntimeError::MissingSuper)
+        ));
+        assert!(matches!(
+            run("class A { }\nclass B from A { fn init() { super(1) } }\nB()"),
+            Err(RuntimeError::ArityMismatch(class, 0, 1)) if class == "A"
+        ));
+        assert!(matches!(
+            run("let A = 5\nclass B from A { }"),
+            Err(RuntimeError::WrongType(..))
+        ));
+        assert!(matches!(
+            run("new len([0])"),
+            Err(RuntimeError::NotConstructible(type_name)) if type_name == "function"
+        ));
+    }
+
+    #[test]
+    fn test_host_call() {
+        let mut vm = Vm::new();
+        let script = vm.compile(&parse("fn double(x) { return x * 2 }")).unwrap();
+        vm.run(script).unwrap();
+
+        let double = vm.globals().lookup("double").unwrap();
+        let result = vm.call(&double, vec![Value::Integer(21)]).unwrap();
+        assert_eq!(result, Value::Integer(42));
+
+        // a failed call leaves the vm usable
+        assert!(vm.call(&double, vec![]).is_err());
+        let result = vm.call(&double, vec![Value::Integer(1)]).unwrap();
+        assert_eq!(result, Value::Integer(2));
+    }
+
+    #[test]
+    fn test_step_budget() {
+        let mut vm = Vm::new();
+        let script = vm
+            .compile(&parse("let sum = 0\nfor i in range(100) { sum += i }\nsum"))
+            .unwrap();
+
+        let len = vm.globals().lookup("len").unwrap();
+        let mut outcome = vm.run_with_budget(script, Budget::steps(50)).unwrap();
+        let mut ticks = 1;
+        while outcome == Outcome::BudgetExhausted {
+            assert!(vm.is_suspended());
+            // the host can still call into the vm between ticks
+            assert_eq!(
+                vm.call(&len, vec![Value::list(vec![])]).unwrap(),
+                Value::Integer(0)
+            );
+            outcome = vm.resume(Budget::steps(50)).unwrap();
+            ticks += 1;
+        }
+
+        assert_eq!(outcome, Outcome::Finished(Value::Integer(4950)));
+        assert!(ticks > 10);
+        assert!(!vm.is_suspended());
+        assert!(matches!(
+            vm.resume(Budget::steps(50)),
+            Err(RuntimeError::NotSuspended)
+        ));
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let mut vm = Vm::new();
+        let script = vm
+            .compile(&parse(
+                "for i in range(100000) { for j in range(100000) { let x = i * j } }",
+            ))
+            .unwrap();
+
+        let outcome = vm
+            .run_with_budget(script.clone(), Budget::time(Duration::from_millis(5)))
+            .unwrap();
+        assert_eq!(outcome, Outcome::BudgetExhausted);
+        assert!(matches!(
+            vm.run_with_budget(script, Budget::steps(10)),
+            Err(RuntimeError::AlreadySuspended)
+        ));
+
+        vm.abort();
+        assert!(!vm.is_suspended());
+        assert_eq!(
+            vm.run(vm.compile(&parse("1 + 1")).unwrap()).unwrap(),
+            Value::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_error_after_resume() {
+        let mut vm = Vm::new();
+        let script = vm
+            .compile(&parse(
+                "let xs = [1, 2, 3]\nfor i in range(10) { xs[i] = 0 }",
+            ))
+            .unwrap();
+
+        let mut outcome = vm.run_with_budget(script, Budget::steps(5));
+        while let Ok(Outcome::BudgetExhausted) = outcome {
+            outcome = vm.resume(Budget::steps(5));
+        }
+        assert!(matches!(
+            outcome.map_err(RuntimeError::without_location),
+            Err(RuntimeError::IndexOutOfBounds(3, 3))
+        ));
+        assert!(!vm.is_suspended());
+    }
+
+    #[test]
+    fn test_timer_wait() {
+        let mut vm = Vm::new();
+        let script = vm
+            .compile(&parse(
+                "import timer\n\
+                 let steps = 0\n\
+                 for i in range(3) { steps += 1\ntimer.run_and_wait(1.5) }\n\
+                 timer.now()",
+            ))
+            .unwrap();
+
+        let mut outcome = vm.run_with_budget(script, Budget::unlimited()).unwrap();
+        assert_eq!(outcome, Outcome::Waiting);
+        assert_eq!(vm.globals().lookup("steps").unwrap(), Value::Integer(1));
+
+        // a waiting script doesn't move until enough time has passed
+        let mut frames = 0;
+        while outcome == Outcome::Waiting {
+            assert!(vm.is_suspended());
+            vm.advance_time(0.1).unwrap();
+            outcome = vm.resume(Budget::unlimited()).unwrap();
+            frames += 1;
+        }
+        assert_eq!(frames, 45);
+        assert_eq!(outcome, Outcome::Finished(Value::Decimal(4.5)));
+        assert_eq!(vm.globals().lookup("steps").unwrap(), Value::Integer(3));
+        assert!(!vm.is_waiting());
+    }
+
+    #[test]
+    fn test_timer_errors() {
+        let source = "import timer\nfn wait() { timer.run_and_wait(1) }";
+        let mut vm = Vm::new();
+        vm.run(vm.compile(&parse(source)).unwrap()).unwrap();
+        let wait = vm.globals().lookup("wait").unwrap();
+
+        // nothing could resume a call that has to run to completion
+        assert!(matches!(
+            vm.call(&wait, vec![])
+                .map_err(RuntimeError::without_location),
+            Err(RuntimeError::CannotWait)
+        ));
+        assert!(!vm.is_suspended());
+        assert_eq!(
+            vm.call_with_budget(&wait, vec![], Budget::steps(100))
+                .unwrap(),
+            Outcome::Waiting
+        );
+        assert!(vm.is_waiting());
+        vm.abort();
+
+        assert!(matches!(
+            run("import timer\ntimer.run_and_wait(-1)"),
+            Err(RuntimeError::InvalidArgument(name, _)) if name == "run_and_wait"
+        ));
+        assert!(matches!(
+            vm.advance_time(f64::NAN),
+            Err(RuntimeError::InvalidArgument(..))
+        ));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let vm = Vm::new();
+        let script = vm
+            .compile(&parse("fn add(a, b) { return a + b }\nadd(1, 2)"))
+            .unwrap();
+        let output = disassemble(&script);
+        assert!(output.contains("== <script> =="));
+        assert!(output.contains("== add =="));
+        assert!(output.contains("GetLocal(1)"));
+        assert!(output.contains("Binary(Plus)"));
+        assert!(output.contains("Call(2)"));
+    }
+}
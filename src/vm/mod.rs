@@ -0,0 +1,14 @@
+mod budget;
+mod chunk;
+mod compiler;
+mod disassembler;
+mod error;
+mod machine;
+pub(crate) mod timer;
+
+pub use budget::{Budget, Outcome};
+pub use chunk::{Capture, Chunk, Closure, CompiledFunction, Instruction, Upvalue};
+pub use compiler::Compiler;
+pub use disassembler::disassemble;
+pub use error::CompileError;
+pub use machine::Vm;
@@ -0,0 +1,85 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use crate::{
+    macros::bail,
+    runtime::{FromArgs, Module, RuntimeError, Value},
+};
+
+/// Simulated game time, it only moves when the host advances it.
+#[derive(Debug, Default)]
+pub struct Clock {
+    now: Duration,
+    request: Option<Duration>, // delay asked for by the last `run_and_wait`
+}
+
+impl Clock {
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn advance(&mut self, delta: Duration) {
+        self.now += delta;
+    }
+
+    pub fn has_request(&self) -> bool {
+        self.request.is_some()
+    }
+
+    pub fn take_request(&mut self) -> Option<Duration> {
+        self.request.take()
+    }
+}
+
+/// Converts seconds coming from the host or a script into a duration.
+pub fn seconds(function_name: &str, seconds: f64) -> Result<Duration, RuntimeError> {
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => Ok(duration),
+        Err(_) => bail!(RuntimeError::InvalidArgument(
+            function_name.to_string(),
+            format!("{} is not a valid amount of seconds", seconds)
+        )),
+    }
+}
+
+/// The `timer` module, bound to the clock of one vm.
+pub fn module(clock: &Rc<RefCell<Clock>>) -> Module {
+    let wait_clock = clock.clone();
+    let run_and_wait = Value::native("run_and_wait", Some(1), move |args| {
+        let (delay,) = <(f64,)>::from_args("run_and_wait", args)?;
+        // the vm suspends the script right after this call returns
+        wait_clock.borrow_mut().request = Some(seconds("run_and_wait", delay)?);
+        Ok(Value::Null)
+    });
+
+    Module::new(
+        "timer",
+        vec![
+            ("run_and_wait".to_string(), run_and_wait),
+            ("now".to_string(), now(clock)),
+        ],
+    )
+}
+
+/// The `timer` module of the interpreter, it can't suspend a script, so `run_and_wait` always fails.
+pub fn module_without_waiting(clock: &Rc<RefCell<Clock>>) -> Module {
+    let run_and_wait = Value::native("run_and_wait", Some(1), |args| {
+        let (delay,) = <(f64,)>::from_args("run_and_wait", args)?;
+        seconds("run_and_wait", delay)?;
+        bail!(RuntimeError::CannotWait)
+    });
+
+    Module::new(
+        "timer",
+        vec![
+            ("run_and_wait".to_string(), run_and_wait),
+            ("now".to_string(), now(clock)),
+        ],
+    )
+}
+
+fn now(clock: &Rc<RefCell<Clock>>) -> Value {
+    let clock = clock.clone();
+    Value::native("now", Some(0), move |_| {
+        Ok(Value::Decimal(clock.borrow().now().as_secs_f64()))
+    })
+}